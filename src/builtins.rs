@@ -0,0 +1,339 @@
+use crate::error::GlassError;
+use crate::function::{Function, NativeFunction};
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::math;
+use crate::sandbox::{self, Capability};
+use crate::structs::{Struct, StructDef};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const BUILTINS: &[NativeFunction] = &[
+    NativeFunction {
+        name: "print",
+        arity: None,
+        func: print,
+    },
+    NativeFunction {
+        name: "println",
+        arity: None,
+        func: println,
+    },
+    NativeFunction {
+        name: "len",
+        arity: Some(1),
+        func: len,
+    },
+    NativeFunction {
+        name: "type",
+        arity: Some(1),
+        func: type_of,
+    },
+    NativeFunction {
+        name: "str",
+        arity: Some(1),
+        func: str,
+    },
+    NativeFunction {
+        name: "set",
+        arity: None,
+        func: set,
+    },
+    NativeFunction {
+        name: "copy",
+        arity: Some(1),
+        func: copy,
+    },
+    NativeFunction {
+        name: "deepcopy",
+        arity: Some(1),
+        func: deepcopy,
+    },
+    NativeFunction {
+        name: "exit",
+        arity: None,
+        func: exit,
+    },
+    NativeFunction {
+        name: "assert",
+        arity: None,
+        func: assert,
+    },
+    NativeFunction {
+        name: "assert_eq",
+        arity: Some(2),
+        func: assert_eq,
+    },
+];
+
+// builtin variables that reach outside of the script, so they need a capability to be read
+const GUARDED_VARIABLES: &[(&str, Capability)] = &[("args", Capability::Environment)];
+
+thread_local! {
+    // shared by every error value, since instances of different structs are never equal
+    static ERROR_STRUCT: Rc<StructDef> = Rc::new(StructDef {
+        name: "Error".into(),
+        fields: vec!["message".into(), "kind".into(), "span".into()],
+        methods: HashMap::new(),
+        read_only: false,
+    });
+}
+
+pub fn get_builtins() -> HashMap<String, Value> {
+    let mut builtins: HashMap<_, _> = BUILTINS
+        .iter()
+        .map(|builtin| {
+            (
+                builtin.name.to_string(),
+                Value::Func(Rc::new(Function::Native(builtin.clone()))),
+            )
+        })
+        .collect();
+
+    builtins.insert("Error".into(), Value::StructDef(error_struct()));
+    builtins.insert("math".into(), math::module());
+    // the arguments passed to the script on the command line, which are set when it is run
+    builtins.insert("args".into(), Value::list(Vec::new()));
+    builtins
+}
+
+/// Fails if reading the global variable `name` needs a capability that is denied.
+pub fn check_variable(name: &str) -> Result<(), GlassError> {
+    match GUARDED_VARIABLES
+        .iter()
+        .find(|(variable, _)| *variable == name)
+    {
+        Some((_, capability)) => sandbox::check_capability(name, *capability),
+        None => Ok(()),
+    }
+}
+
+/// The struct of the errors caught by scripts. Scripts can create their own errors with
+/// `Error(message, kind, span)` to throw them.
+pub fn error_struct() -> Rc<StructDef> {
+    ERROR_STRUCT.with(Rc::clone)
+}
+
+/// Creates an error value. `span` is the `(line, column)` the error happened at, or void if it
+/// isn't known.
+pub fn error_value(message: String, kind: &str, span: Value) -> Value {
+    Value::Struct(Rc::new(RefCell::new(Struct {
+        def: error_struct(),
+        values: vec![Value::Str(message), Value::Str(kind.into()), span],
+    })))
+}
+
+fn join_args(args: &[Value]) -> Result<String, GlassError> {
+    Ok(args
+        .iter()
+        .map(|arg| arg.format(false))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(" "))
+}
+
+fn print(interpreter: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    interpreter.write_output(&join_args(&args)?)?;
+    Ok(Value::Void)
+}
+
+fn println(interpreter: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    interpreter.write_output(&format!("{}\n", join_args(&args)?))?;
+    Ok(Value::Void)
+}
+
+fn len(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let len = match &args[0] {
+        Value::Str(str) => str.chars().count(),
+        Value::List(list) => list.borrow().len(),
+        Value::Tuple(items) => items.len(),
+        Value::Dict(dict) => dict.borrow().len(),
+        Value::Set(set) => set.borrow().len(),
+        value => {
+            return Err(GlassError::InvalidArgument {
+                function: "len".into(),
+                message: format!("type '{}' has no length", value.get_type()),
+            })
+        }
+    };
+
+    Ok(Value::Num(len as f64))
+}
+
+fn type_of(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Str(args[0].get_type()))
+}
+
+fn str(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let str = args[0].format(false)?;
+    sandbox::check_str(str.len())?;
+
+    Ok(Value::Str(str))
+}
+
+/// `set()` or `set(items)`, where the items are anything a `for` loop can go through.
+fn set(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match <[Value; 1]>::try_from(args) {
+        Ok([items]) => Value::set(items.into_items()?),
+        Err(args) if args.is_empty() => Value::set([]),
+        Err(args) => Err(GlassError::InvalidArgument {
+            function: "set".into(),
+            message: format!(
+                "expected an optional iterable but {} arguments were given",
+                args.len()
+            ),
+        }),
+    }
+}
+
+fn copy(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    args[0].copy()
+}
+
+fn deepcopy(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    args[0].deepcopy()
+}
+
+/// `exit()` or `exit(code)`, which stops the script. The code has to be a whole number, and is 0
+/// if it isn't given.
+fn exit(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    sandbox::check_capability("exit", Capability::Process)?;
+
+    let code = match &args[..] {
+        [] => 0.0,
+        [Value::Num(code)] => *code,
+        [value] => {
+            return Err(GlassError::InvalidArgument {
+                function: "exit".into(),
+                message: format!("expected a number but found '{}'", value.get_type()),
+            })
+        }
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: "exit".into(),
+                message: format!(
+                    "expected an optional exit code but {} arguments were given",
+                    args.len()
+                ),
+            })
+        }
+    };
+
+    // the OS only keeps the low byte of the code, so 256 would look like success
+    if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+        return Err(GlassError::InvalidArgument {
+            function: "exit".into(),
+            message: format!(
+                "{} is not a valid exit code, which must be a whole number from 0 to 255",
+                code
+            ),
+        });
+    }
+
+    Err(GlassError::Exit { code: code as i32 })
+}
+
+/// `assert(condition)` or `assert(condition, message)`
+fn assert(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let (condition, message) = match &args[..] {
+        [condition] => (condition, None),
+        [condition, message] => (condition, Some(message.format(false)?)),
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: "assert".into(),
+                message: format!(
+                    "expected a condition and an optional message but {} argument(s) were given",
+                    args.len()
+                ),
+            })
+        }
+    };
+
+    match condition {
+        Value::Bool(true) => Ok(Value::Void),
+        Value::Bool(false) => Err(GlassError::AssertionFailed { message }),
+        value => Err(GlassError::InvalidArgument {
+            function: "assert".into(),
+            message: format!("expected a boolean but found '{}'", value.get_type()),
+        }),
+    }
+}
+
+fn assert_eq(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    if args[0] == args[1] {
+        return Ok(Value::Void);
+    }
+
+    Err(GlassError::AssertionNotEqual {
+        diff: diff(&diff_lines(&args[0])?, &diff_lines(&args[1])?),
+    })
+}
+
+fn repr(value: &Value) -> Result<String, GlassError> {
+    value.format(true)
+}
+
+// collections are split into a line per item, so that only the items that differ are marked
+fn diff_lines(value: &Value) -> Result<Vec<String>, GlassError> {
+    let (open, items, close): (_, Result<Vec<_>, GlassError>, _) = match value {
+        Value::List(list) => ("[", list.borrow().iter().map(repr).collect(), "]"),
+        Value::Tuple(items) => ("(", items.iter().map(repr).collect(), ")"),
+        Value::Dict(dict) => (
+            "{",
+            dict.borrow()
+                .iter()
+                .map(|(key, value)| Ok(format!("{}: {}", repr(&key.to_value())?, repr(value)?)))
+                .collect(),
+            "}",
+        ),
+        value => return Ok(vec![repr(value)?]),
+    };
+
+    let items = items?;
+
+    if items.is_empty() {
+        return Ok(vec![format!("{}{}", open, close)]);
+    }
+
+    let mut lines = vec![open.to_string()];
+    lines.extend(items.into_iter().map(|item| format!("    {},", item)));
+    lines.push(close.into());
+
+    Ok(lines)
+}
+
+/// A line diff of the left value (`-`) against the right one (`+`), from their longest common
+/// subsequence of lines.
+fn diff(left: &[String], right: &[String]) -> String {
+    // common[i][j] is the length of the longest common subsequence of left[i..] and right[j..]
+    let mut common = vec![vec![0; right.len() + 1]; left.len() + 1];
+
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            common[i][j] = if left[i] == right[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            lines.push(format!("  {}", left[i]));
+            i += 1;
+            j += 1;
+        } else if j == right.len() || (i < left.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", left[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", right[j]));
+            j += 1;
+        }
+    }
+
+    lines.join("\n")
+}
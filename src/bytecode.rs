@@ -0,0 +1,227 @@
+use crate::interpreter::InterpreterResult;
+use crate::lexer::Token;
+use crate::node::Slot;
+use crate::value::Value;
+use logos::Span;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// A binary operator, resolved from its token at compile time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinOp {
+    pub fn from_token(token: &Token) -> Option<Self> {
+        Some(match token {
+            Token::Plus | Token::PlusEqual => BinOp::Add,
+            Token::Minus | Token::MinusEqual => BinOp::Sub,
+            Token::Star | Token::StarEqual => BinOp::Mul,
+            Token::Slash | Token::SlashEqual => BinOp::Div,
+            Token::Percent | Token::PercentEqual => BinOp::Rem,
+            Token::StarStar | Token::StarStarEqual => BinOp::Pow,
+            Token::EqualEqual => BinOp::Eq,
+            Token::ExclamationEqual => BinOp::Ne,
+            Token::LessThan => BinOp::Lt,
+            Token::GreaterThan => BinOp::Gt,
+            Token::LessThanEqual => BinOp::Le,
+            Token::GreaterThanEqual => BinOp::Ge,
+            Token::And => BinOp::And,
+            Token::Or => BinOp::Or,
+            _ => return None,
+        })
+    }
+
+    pub fn apply(self, left: Value, right: Value) -> InterpreterResult {
+        match self {
+            BinOp::Add => left.add(right),
+            BinOp::Sub => left.sub(right),
+            BinOp::Mul => left.mul(right),
+            BinOp::Div => left.div(right),
+            BinOp::Rem => left.rem(right),
+            BinOp::Pow => left.pow(right),
+            BinOp::Eq => left.eq(right),
+            BinOp::Ne => left.ne(right),
+            BinOp::Lt => left.lt(right),
+            BinOp::Gt => left.gt(right),
+            BinOp::Le => left.le(right),
+            BinOp::Ge => left.ge(right),
+            BinOp::And => left.and(right),
+            BinOp::Or => left.or(right),
+        }
+    }
+}
+
+/// A single VM instruction. Operands that refer to a constant are indices into the constant
+/// pool of the chunk the instruction is in, and jump targets are instruction indices.
+///
+/// Variables are read from the slots the resolver gave them, falling back to looking them up by
+/// name, and are assigned to a slot of the current call unless they are outside of any function.
+/// Instructions that define a variable push its value, to be assigned by the next instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Constant(u32),
+    True,
+    False,
+    Void,
+    Pop,
+    Dup,
+    Dup2,
+    // a name and the index of the variable's slots in the chunk's slot table
+    Load { name: u32, slots: u32 },
+    Store(u32),
+    StoreSlot(u32),
+    BinaryOp(BinOp),
+    Negate,
+    Not,
+    List(u32),
+    Tuple(u32),
+    Dict(u32),
+    Index,
+    SetIndex,
+    GetField(u32),
+    SetField(u32),
+    Call(u32),
+    CallMethod(u32, u32),
+    Closure(u32),
+    Struct(u32),
+    Jump(u32),
+    JumpIfFalse(u32),
+    // jumps if the value on top of the stack is false/true, leaving it there as the result
+    AndShortCircuit(u32),
+    OrShortCircuit(u32),
+    // a numeric for loop keeps the counter and the end on the stack
+    RangeInit { inclusive: bool },
+    RangeNext { inclusive: bool, exit: u32 },
+    RangeStep,
+    // a for each loop keeps a snapshot of the items and the next index on the stack
+    IterInit,
+    IterNext { exit: u32 },
+    Return,
+    // registers a handler for errors raised before the matching EndTry. A catch handler jumps
+    // with the error value on the stack, a finally handler keeps the error to rethrow it later
+    Try(u32),
+    TryFinally(u32),
+    EndTry,
+    Throw,
+    // raises the error kept by the finally handler that jumped to the current finally block
+    Rethrow,
+    // break or continue outside of a loop, which is an error once it is executed
+    OutsideLoop { is_break: bool },
+}
+
+#[derive(Debug)]
+pub enum Constant {
+    Num(f64),
+    Str(String),
+    Function(Rc<FunctionProto>),
+    Struct(Rc<StructProto>),
+}
+
+/// A compiled function body, which becomes a function value once it is paired with the context
+/// it is created in.
+#[derive(Debug)]
+pub struct FunctionProto {
+    pub name: String,
+    pub params: Vec<String>,
+    // the variable of each slot a call needs, the first of which are the parameters
+    pub locals: Rc<[String]>,
+    pub chunk: Chunk,
+}
+
+/// A compiled struct declaration. Its methods are compiled as closures that are on the stack
+/// when the struct is created.
+#[derive(Debug)]
+pub struct StructProto {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Constant>,
+    // the source spans of the instructions that can raise an error, ordered by instruction
+    pub spans: Vec<(u32, Span)>,
+    // the slots of the variables read by `Load` instructions, nearest call first
+    pub slots: Vec<Vec<Slot>>,
+}
+
+impl Chunk {
+    /// Returns the name stored in a string constant.
+    pub fn name(&self, index: u32) -> &str {
+        match &self.constants[index as usize] {
+            Constant::Str(name) => name,
+            constant => panic!("Expected a name constant but found {:?}", constant),
+        }
+    }
+
+    /// Returns the source span of an instruction, if it has one.
+    pub fn span(&self, index: usize) -> Option<&Span> {
+        self.spans
+            .binary_search_by_key(&(index as u32), |(instruction, _)| *instruction)
+            .ok()
+            .map(|found| &self.spans[found].1)
+    }
+}
+
+// disassembly, used by --debug
+impl Display for Chunk {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, instruction) in self.code.iter().enumerate() {
+            write!(f, "{:>5} {:?}", i, instruction)?;
+
+            match instruction {
+                Instruction::Constant(index)
+                | Instruction::Load { name: index, .. }
+                | Instruction::Store(index)
+                | Instruction::GetField(index)
+                | Instruction::SetField(index)
+                | Instruction::CallMethod(index, _) => match &self.constants[*index as usize] {
+                    Constant::Num(num) => write!(f, " ; {}", num)?,
+                    Constant::Str(str) => write!(f, " ; {:?}", str)?,
+                    _ => {}
+                },
+                _ => {}
+            }
+
+            writeln!(f)?;
+        }
+
+        for constant in &self.constants {
+            match constant {
+                Constant::Function(function) => {
+                    writeln!(
+                        f,
+                        "\nfunction {}({}):",
+                        function.name,
+                        function.params.join(", ")
+                    )?;
+                    write!(f, "{}", function.chunk)?;
+                }
+                Constant::Struct(def) => {
+                    writeln!(f, "\nstruct {} {{ {} }}", def.name, def.fields.join(", "))?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
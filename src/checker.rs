@@ -0,0 +1,1088 @@
+//! The type checker run by `glass check`, which finds type errors without running the script.
+//!
+//! Types are inferred by applying each operation to a sample value of every operand's type, so
+//! the checker follows the same rules as `value.rs` instead of keeping a copy of them. Typing is
+//! gradual: anything that can't be known, like an unannotated parameter, is `any`, which is
+//! never an error.
+//!
+//! A variable has a single type in the scope it is declared in, which is the join of every value
+//! assigned to it. Since a variable can be used before the assignment that gives it its type
+//! (like a function calling another one defined after it), the script is checked repeatedly
+//! until the types stop changing, and errors are only reported by the last pass.
+
+use crate::bytecode::BinOp;
+use crate::context::Context;
+use crate::dict::{Dict, DictKey};
+use crate::error::GlassError;
+use crate::function::{Function, NativeFunction};
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::lexer::Token;
+use crate::methods;
+use crate::node::{Node, Slot, TypeAnnotation};
+use crate::structs::{Struct, StructDef};
+use crate::value::Value;
+use logos::Span;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::mem;
+use std::rc::Rc;
+
+// the types usually settle after two or three passes, this only stops pathological scripts
+const MAX_PASSES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    // nothing has been inferred yet, which fits anywhere
+    Unknown,
+    Any,
+    Num,
+    Str,
+    Bool,
+    List,
+    Tuple,
+    Dict,
+    Set,
+    Void,
+    // the signature is only known for builtins and functions defined in the script
+    Function(Option<Rc<Signature>>),
+    StructDef(String),
+    Struct(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Signature {
+    name: String,
+    // `None` accepts any number of arguments
+    params: Option<Vec<Type>>,
+    ret: Type,
+}
+
+impl Type {
+    /// The type written in an annotation, which uses the same names as `type()`.
+    fn from_name(name: &str, structs: &HashMap<String, Rc<StructDef>>) -> Option<Type> {
+        Some(match name {
+            "any" => Type::Any,
+            "number" => Type::Num,
+            "string" => Type::Str,
+            "boolean" => Type::Bool,
+            "list" => Type::List,
+            "tuple" => Type::Tuple,
+            "dictionary" => Type::Dict,
+            "set" => Type::Set,
+            "void" => Type::Void,
+            "function" => Type::Function(None),
+            name if structs.contains_key(name) => Type::Struct(name.into()),
+            _ => return None,
+        })
+    }
+
+    fn of(value: &Value) -> Type {
+        match value {
+            Value::Num(_) => Type::Num,
+            Value::Str(_) => Type::Str,
+            Value::Bool(_) => Type::Bool,
+            Value::List(_) => Type::List,
+            Value::Tuple(_) => Type::Tuple,
+            Value::Dict(_) => Type::Dict,
+            Value::Set(_) => Type::Set,
+            Value::Void => Type::Void,
+            Value::Func(_) => Type::Function(None),
+            Value::StructDef(def) => Type::StructDef(def.name.clone()),
+            Value::Struct(instance) => Type::Struct(instance.borrow().def.name.clone()),
+        }
+    }
+
+    fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (Type::Unknown, other) | (other, Type::Unknown) => other,
+            (a, b) if a == b => a,
+            (Type::Function(_), Type::Function(_)) => Type::Function(None),
+            _ => Type::Any,
+        }
+    }
+
+    /// Whether a value of this type can be used where a value of type `expected` is.
+    fn fits(&self, expected: &Type) -> bool {
+        match (self, expected) {
+            (Type::Unknown | Type::Any, _) | (_, Type::Any) => true,
+            (Type::Function(_), Type::Function(_)) => true,
+            (found, expected) => found == expected,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Unknown | Type::Any => "any",
+            Type::Num => "number",
+            Type::Str => "string",
+            Type::Bool => "boolean",
+            Type::List => "list",
+            Type::Tuple => "tuple",
+            Type::Dict => "dictionary",
+            Type::Set => "set",
+            Type::Void => "void",
+            Type::Function(_) => "function",
+            Type::StructDef(_) => "struct",
+            // instances are of the type of their struct, like in `Value::get_type`
+            Type::Struct(name) => name,
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Everything inferred by a pass, which the next pass reads from.
+#[derive(Default, PartialEq)]
+struct Types {
+    // keyed by the scope the variable is declared in, see `Checker::scope`
+    variables: HashMap<(usize, String), Type>,
+    // the types returned by each function, keyed by its scope
+    returns: HashMap<usize, Type>,
+    // keyed by the struct name and the method name
+    methods: HashMap<(String, String), Type>,
+}
+
+/// What the checker found out about a script.
+pub struct Analysis {
+    pub errors: Vec<GlassError>,
+    // the type of every variable where it is named, for editors to show
+    pub types: Vec<(Span, String)>,
+}
+
+struct Frame {
+    scope: usize,
+    return_type: Option<Type>,
+}
+
+pub struct Checker {
+    src: Rc<str>,
+    filename: Rc<str>,
+    // a sample definition of every struct in the script, used to check fields and methods
+    structs: HashMap<String, Rc<StructDef>>,
+    // builtin instances like math by the name of their struct, whose fields are known
+    instances: HashMap<String, Value>,
+    // variables with an annotated type, which never changes
+    annotations: HashMap<(usize, String), Type>,
+    globals: Vec<(String, Type)>,
+    previous: Types,
+    current: Types,
+    // the functions being checked, innermost last
+    frames: Vec<Frame>,
+    report: bool,
+    errors: Vec<GlassError>,
+    types: Vec<(Span, String)>,
+}
+
+impl Checker {
+    pub fn new(src: Rc<str>, filename: Rc<str>) -> Self {
+        Self {
+            src,
+            filename,
+            structs: HashMap::new(),
+            instances: HashMap::new(),
+            annotations: HashMap::new(),
+            globals: Vec::new(),
+            previous: Types::default(),
+            current: Types::default(),
+            frames: Vec::new(),
+            report: false,
+            errors: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Checks a resolved AST that is going to be run in `context`, returning every type error
+    /// found in it.
+    pub fn check(self, ast: &Node, context: &Context) -> Vec<GlassError> {
+        self.analyze(ast, context).errors
+    }
+
+    /// Like `check`, but also returns the types of the variables.
+    pub fn analyze(mut self, ast: &Node, context: &Context) -> Analysis {
+        self.collect_structs(ast);
+
+        for name in context.names() {
+            if let Some(value) = context.get(name) {
+                // builtin structs like Error and the struct of builtin instances like math, which
+                // the script's own structs take the place of
+                let def = match &value {
+                    Value::StructDef(def) => Some(Rc::clone(def)),
+                    Value::Struct(instance) => Some(Rc::clone(&instance.borrow().def)),
+                    _ => None,
+                };
+
+                if let Some(def) = def {
+                    let known = self.structs.entry(def.name.clone()).or_insert(def);
+
+                    if matches!(&value, Value::Struct(instance) if Rc::ptr_eq(known, &instance.borrow().def))
+                    {
+                        self.instances.insert(known.name.clone(), value.clone());
+                    }
+                }
+
+                self.globals.push((name.clone(), builtin_type(&value)));
+            }
+        }
+
+        for (name, ty) in &self.globals {
+            self.previous
+                .variables
+                .insert((0, name.clone()), ty.clone());
+        }
+
+        for _ in 0..MAX_PASSES {
+            self.pass(ast);
+
+            let settled = self.current == self.previous;
+            self.previous = mem::take(&mut self.current);
+
+            if settled {
+                break;
+            }
+        }
+
+        self.report = true;
+        self.pass(ast);
+
+        Analysis {
+            errors: self.errors,
+            types: self.types,
+        }
+    }
+
+    fn pass(&mut self, ast: &Node) {
+        self.current = Types::default();
+
+        for (name, ty) in &self.globals {
+            self.current.variables.insert((0, name.clone()), ty.clone());
+        }
+
+        self.check_node(ast);
+    }
+
+    fn collect_structs(&mut self, node: &Node) {
+        if let Node::StructDefinition {
+            name,
+            fields,
+            methods,
+            ..
+        } = node
+        {
+            let def = StructDef {
+                name: name.clone(),
+                fields: fields.clone(),
+                methods: methods
+                    .iter()
+                    .map(|(method, _)| (method.clone(), sample_function()))
+                    .collect(),
+                read_only: false,
+            };
+
+            self.structs.insert(name.clone(), Rc::new(def));
+        }
+
+        for child in node.children() {
+            self.collect_structs(child);
+        }
+    }
+
+    fn check_node(&mut self, node: &Node) -> Type {
+        match node {
+            Node::String { .. } => Type::Str,
+            Node::Number { .. } => Type::Num,
+            Node::Bool { .. } => Type::Bool,
+            Node::Void => Type::Void,
+            Node::Identifier { name, span, slots } => {
+                let ty = self.variable(name, slots);
+                self.record(span, &ty);
+                ty
+            }
+            Node::List { items } => {
+                self.check_all(items);
+                Type::List
+            }
+            Node::Tuple { items } => {
+                self.check_all(items);
+                Type::Tuple
+            }
+            Node::Dict { entries } => {
+                for (key, value) in entries {
+                    let key_type = self.check_node(key);
+
+                    if let Some(Err(err)) =
+                        self.sample(&key_type).map(|key| DictKey::from_value(&key))
+                    {
+                        self.error(err, key.span());
+                    }
+
+                    self.check_node(value);
+                }
+
+                Type::Dict
+            }
+            Node::Index {
+                target,
+                index,
+                span,
+            } => {
+                let target = self.check_node(target);
+                let index = self.check_node(index);
+
+                self.index(target, index, span, false)
+            }
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.check_node(left);
+                let right = self.check_node(right);
+
+                self.binary_op(op, left, right, span)
+            }
+            Node::UnaryOp { op, expr, span } => {
+                let operand = self.check_node(expr);
+                self.unary_op(op, operand, span)
+            }
+            Node::Assignment {
+                op,
+                left,
+                right,
+                annotation,
+            } => {
+                self.assignment(op, left, right, annotation.as_ref());
+                Type::Void
+            }
+            Node::FunctionCall {
+                function,
+                args,
+                span,
+            } => {
+                let function = self.check_node(function);
+                let args = self.check_all(args);
+
+                self.call(function, args, span)
+            }
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => {
+                let target = self.check_node(target);
+                self.field(target, field, span)
+            }
+            Node::MethodCall {
+                target,
+                method,
+                args,
+                span,
+            } => {
+                let target = self.check_node(target);
+                let args = self.check_all(args);
+
+                self.method_call(target, method, args, span)
+            }
+            Node::FunctionDefinition {
+                name,
+                signature,
+                param_spans,
+                param_types,
+                return_type,
+                body,
+                ..
+            } => {
+                let function =
+                    self.function(name, signature, param_types, return_type.as_ref(), body);
+
+                if let Type::Function(Some(signature)) = &function {
+                    for (span, ty) in param_spans.iter().zip(signature.params.iter().flatten()) {
+                        self.record(span, ty);
+                    }
+                }
+
+                function
+            }
+            Node::StructDefinition { name, methods, .. } => {
+                for (method, function) in methods {
+                    let ty = self.check_node(function);
+                    self.current
+                        .methods
+                        .insert((name.clone(), method.clone()), ty);
+                }
+
+                self.assign(self.scope(name), Type::StructDef(name.clone()), None);
+                Type::Void
+            }
+            Node::Return { value, span } => {
+                let ty = self.check_node(value);
+                let frame = self.frames.last().expect("Return outside of a function");
+                let (scope, return_type) = (frame.scope, frame.return_type.clone());
+
+                if let Some(return_type) = return_type {
+                    if !ty.fits(&return_type) {
+                        let error = GlassError::TypeMismatch {
+                            expected: return_type.to_string(),
+                            found: ty.to_string(),
+                        };
+                        self.error(error, Some(span.clone()));
+                    }
+                }
+
+                let returned = self.current.returns.remove(&scope).unwrap_or(Type::Unknown);
+                self.current.returns.insert(scope, returned.join(ty));
+
+                Type::Void
+            }
+            Node::Break { .. } | Node::Continue { .. } => Type::Void,
+            Node::Throw { value, .. } => {
+                self.check_node(value);
+                Type::Void
+            }
+            Node::Try {
+                body,
+                variable,
+                variable_span,
+                catch_body,
+                finally_body,
+                ..
+            } => {
+                self.check_node(body);
+
+                // anything can be thrown, not only error values
+                if let Some(variable) = variable {
+                    self.assign(self.scope(variable), Type::Any, None);
+                }
+
+                if let Some(span) = variable_span {
+                    self.record(span, &Type::Any);
+                }
+
+                if let Some(catch_body) = catch_body {
+                    self.check_node(catch_body);
+                }
+
+                if let Some(finally_body) = finally_body {
+                    self.check_node(finally_body);
+                }
+
+                Type::Void
+            }
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                self.condition(condition);
+                self.check_node(body);
+
+                if let Some(else_body) = else_body {
+                    self.check_node(else_body);
+                }
+
+                Type::Void
+            }
+            Node::While { condition, body } => {
+                self.condition(condition);
+                self.check_node(body);
+                Type::Void
+            }
+            Node::For {
+                variable,
+                variable_span,
+                start,
+                end,
+                inclusive,
+                body,
+                ..
+            } => {
+                let start_type = self.check_node(start);
+                let end_type = self.check_node(end);
+
+                if !start_type.fits(&Type::Num) || !end_type.fits(&Type::Num) {
+                    let error = GlassError::InvalidOperation {
+                        operation: if *inclusive { "..=" } else { ".." }.into(),
+                        left: start_type.to_string(),
+                        right: end_type.to_string(),
+                    };
+                    self.error(error, start.span());
+                }
+
+                self.assign(self.scope(variable), Type::Num, None);
+                self.record(variable_span, &Type::Num);
+                self.check_node(body);
+                Type::Void
+            }
+            Node::ForEach {
+                variable,
+                variable_span,
+                iterable,
+                body,
+                ..
+            } => {
+                let item = match self.check_node(iterable) {
+                    Type::Str => Type::Str,
+                    Type::Unknown => Type::Unknown,
+                    Type::Any | Type::List | Type::Tuple | Type::Dict | Type::Set => Type::Any,
+                    ty => {
+                        let error = GlassError::NotIterable {
+                            type_name: ty.to_string(),
+                        };
+                        self.error(error, iterable.span());
+                        Type::Any
+                    }
+                };
+
+                self.record(variable_span, &item);
+                self.assign(self.scope(variable), item, None);
+                self.check_node(body);
+                Type::Void
+            }
+            Node::Block { statements } => {
+                self.check_all(statements);
+                Type::Void
+            }
+        }
+    }
+
+    fn check_all(&mut self, nodes: &[Node]) -> Vec<Type> {
+        nodes.iter().map(|node| self.check_node(node)).collect()
+    }
+
+    /// The key of a variable declared in the current scope. Variables outside of any function
+    /// are in scope 0, and the scope of a function is the address of its body, which every
+    /// function value created from it shares.
+    fn scope(&self, name: &str) -> (usize, String) {
+        let scope = self.frames.last().map_or(0, |frame| frame.scope);
+        (scope, name.into())
+    }
+
+    /// The type of a variable that is read. Like at runtime, the slots of the enclosing
+    /// functions are tried in order before the global variable.
+    fn variable(&self, name: &str, slots: &[Slot]) -> Type {
+        let scopes = slots
+            .iter()
+            .filter_map(|slot| self.frames.len().checked_sub(slot.depth + 1))
+            .map(|frame| self.frames[frame].scope)
+            .chain([0]);
+
+        for scope in scopes {
+            let key = (scope, name.to_string());
+
+            if let Some(ty) = self.annotations.get(&key) {
+                return ty.clone();
+            }
+
+            match self.previous.variables.get(&key) {
+                Some(Type::Unknown) | None => {}
+                Some(ty) => return ty.clone(),
+            }
+        }
+
+        Type::Unknown
+    }
+
+    fn assign(&mut self, key: (usize, String), ty: Type, span: Option<Span>) {
+        if let Some(expected) = self.annotations.get(&key) {
+            if !ty.fits(expected) {
+                let error = GlassError::TypeMismatch {
+                    expected: expected.to_string(),
+                    found: ty.to_string(),
+                };
+                self.error(error, span);
+            }
+
+            return;
+        }
+
+        let assigned = self.current.variables.remove(&key).unwrap_or(Type::Unknown);
+        self.current.variables.insert(key, assigned.join(ty));
+    }
+
+    fn assignment(
+        &mut self,
+        op: &Token,
+        left: &Node,
+        right: &Node,
+        annotation: Option<&TypeAnnotation>,
+    ) {
+        let value = self.check_node(right);
+
+        match left {
+            Node::Identifier { name, span, slots } => {
+                let key = self.scope(name);
+
+                if let Some(ty) = annotation.and_then(|annotation| self.annotation(annotation)) {
+                    self.annotations.insert(key.clone(), ty);
+                }
+
+                let value = match op {
+                    Token::Equal => value,
+                    op => {
+                        let current = self.variable(name, slots);
+                        self.binary_op(op, current, value, span)
+                    }
+                };
+
+                // the variable has the type of everything assigned to it, not only this value
+                let ty = self.variable(name, slots).join(value.clone());
+                self.record(span, &ty);
+
+                let span = annotation.map_or(span, |annotation| &annotation.span);
+                self.assign(key, value, Some(span.clone()));
+            }
+            Node::Index {
+                target,
+                index,
+                span,
+            } => {
+                let target = self.check_node(target);
+                let index = self.check_node(index);
+
+                self.index(target, index, span, true);
+            }
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => {
+                let target = self.check_node(target);
+
+                if let Some(Err(err)) = self
+                    .sample(&target)
+                    .map(|target| target.set_field(field, Value::Void))
+                {
+                    self.error(err, Some(span.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn annotation(&mut self, annotation: &TypeAnnotation) -> Option<Type> {
+        let ty = Type::from_name(&annotation.name, &self.structs);
+
+        if ty.is_none() {
+            let error = GlassError::UnknownType {
+                name: annotation.name.clone(),
+            };
+            self.error(error, Some(annotation.span.clone()));
+        }
+
+        ty
+    }
+
+    fn function(
+        &mut self,
+        name: &str,
+        signature: &[String],
+        param_types: &[Option<TypeAnnotation>],
+        return_type: Option<&TypeAnnotation>,
+        body: &Rc<Node>,
+    ) -> Type {
+        let scope = Rc::as_ptr(body) as usize;
+
+        // unannotated parameters can be anything
+        let params: Vec<Type> = param_types
+            .iter()
+            .map(|annotation| {
+                annotation
+                    .as_ref()
+                    .and_then(|annotation| self.annotation(annotation))
+                    .unwrap_or(Type::Any)
+            })
+            .collect();
+
+        for (param, ty) in signature.iter().zip(&params) {
+            self.annotations.insert((scope, param.clone()), ty.clone());
+        }
+
+        let annotated = return_type.and_then(|annotation| self.annotation(annotation));
+
+        self.frames.push(Frame {
+            scope,
+            return_type: annotated.clone(),
+        });
+        self.check_node(body);
+        self.frames.pop();
+
+        let ret = match (annotated, return_type) {
+            (Some(ty), Some(annotation)) => {
+                // falling off the end of a function returns void
+                if !always_returns(body) && !Type::Void.fits(&ty) {
+                    let error = GlassError::TypeMismatch {
+                        expected: ty.to_string(),
+                        found: Type::Void.to_string(),
+                    };
+                    self.error(error, Some(annotation.span.clone()));
+                }
+
+                ty
+            }
+            _ => {
+                let returned = self
+                    .current
+                    .returns
+                    .get(&scope)
+                    .cloned()
+                    .unwrap_or(Type::Unknown);
+
+                if always_returns(body) {
+                    returned
+                } else {
+                    returned.join(Type::Void)
+                }
+            }
+        };
+
+        Type::Function(Some(Rc::new(Signature {
+            name: name.into(),
+            params: Some(params),
+            ret,
+        })))
+    }
+
+    fn condition(&mut self, condition: &Node) {
+        let ty = self.check_node(condition);
+
+        if !ty.fits(&Type::Bool) {
+            let error = GlassError::InvalidCondition {
+                condition_type: ty.to_string(),
+            };
+            self.error(error, condition.span());
+        }
+    }
+
+    fn binary_op(&mut self, op: &Token, left: Type, right: Type, span: &Span) -> Type {
+        let bin_op = match BinOp::from_token(op) {
+            Some(bin_op) => bin_op,
+            None => return Type::Any,
+        };
+
+        // these are always boolean, whatever they are applied to
+        let fallback = match bin_op {
+            BinOp::Eq
+            | BinOp::Ne
+            | BinOp::Lt
+            | BinOp::Gt
+            | BinOp::Le
+            | BinOp::Ge
+            | BinOp::And
+            | BinOp::Or => Type::Bool,
+            _ if left == Type::Unknown || right == Type::Unknown => Type::Unknown,
+            _ => Type::Any,
+        };
+
+        match (self.sample(&left), self.sample(&right)) {
+            (Some(left), Some(right)) => match bin_op.apply(left, right) {
+                Ok(result) => Type::of(&result),
+                Err(err) => {
+                    self.error(err, Some(span.clone()));
+                    Type::Any
+                }
+            },
+            _ => fallback,
+        }
+    }
+
+    fn unary_op(&mut self, op: &Token, operand: Type, span: &Span) -> Type {
+        let sample = match self.sample(&operand) {
+            Some(sample) => sample,
+            None if *op == Token::Not => return Type::Bool,
+            None => return operand,
+        };
+
+        let result = match op {
+            Token::Minus => sample.neg(),
+            Token::Not => sample.not(),
+            _ => return operand,
+        };
+
+        match result {
+            Ok(result) => Type::of(&result),
+            Err(err) => {
+                self.error(err, Some(span.clone()));
+                Type::Any
+            }
+        }
+    }
+
+    /// Checks reading (or with `set`, assigning) an item. Missing items can't be found without
+    /// running the script, so only errors caused by the types are reported.
+    fn index(&mut self, target: Type, index: Type, span: &Span, set: bool) -> Type {
+        if let (Some(target), Some(index)) = (self.sample(&target), self.sample(&index)) {
+            let result = if set {
+                target.set_index(index, Value::Void)
+            } else {
+                target.index(index).map(|_| ())
+            };
+
+            match result {
+                Err(GlassError::IndexOutOfBounds { .. } | GlassError::KeyNotFound { .. }) => {}
+                Err(err) => self.error(err, Some(span.clone())),
+                Ok(()) => {}
+            }
+        }
+
+        // the items of a container aren't tracked
+        match target {
+            Type::Unknown => Type::Unknown,
+            _ => Type::Any,
+        }
+    }
+
+    fn field(&mut self, target: Type, field: &str, span: &Span) -> Type {
+        if let Type::StructDef(name) = &target {
+            if let Some(method) = self.previous.methods.get(&(name.clone(), field.into())) {
+                return method.clone();
+            }
+        }
+
+        if let Type::Struct(name) = &target {
+            if let Some(instance) = self.instances.get(name) {
+                return match instance.get_field(field) {
+                    Ok(value) => builtin_type(&value),
+                    Err(err) => {
+                        self.error(err, Some(span.clone()));
+                        Type::Any
+                    }
+                };
+            }
+        }
+
+        match self.sample(&target) {
+            Some(sample) => {
+                if let Err(err) = sample.get_field(field) {
+                    self.error(err, Some(span.clone()));
+                }
+
+                Type::Any
+            }
+            None => target,
+        }
+    }
+
+    fn call(&mut self, function: Type, args: Vec<Type>, span: &Span) -> Type {
+        match function {
+            Type::Function(Some(signature)) => {
+                if let Some(params) = &signature.params {
+                    if params.len() != args.len() {
+                        let error = GlassError::ArgumentCount {
+                            function: signature.name.clone(),
+                            expected: params.len(),
+                            found: args.len(),
+                        };
+                        self.error(error, Some(span.clone()));
+                    } else {
+                        for (param, arg) in params.iter().zip(&args) {
+                            if !arg.fits(param) {
+                                let error = GlassError::InvalidArgument {
+                                    function: signature.name.clone(),
+                                    message: format!("expected '{}' but found '{}'", param, arg),
+                                };
+                                self.error(error, Some(span.clone()));
+                            }
+                        }
+                    }
+                }
+
+                signature.ret.clone()
+            }
+            Type::StructDef(name) => {
+                let fields = self.structs.get(&name).map(|def| def.fields.len());
+
+                if let Some(fields) = fields.filter(|&fields| fields != args.len()) {
+                    let error = GlassError::ArgumentCount {
+                        function: name.clone(),
+                        expected: fields,
+                        found: args.len(),
+                    };
+                    self.error(error, Some(span.clone()));
+                }
+
+                Type::Struct(name)
+            }
+            Type::Unknown => Type::Unknown,
+            Type::Function(None) | Type::Any => Type::Any,
+            ty => {
+                let error = GlassError::NotCallable {
+                    type_name: ty.to_string(),
+                };
+                self.error(error, Some(span.clone()));
+                Type::Any
+            }
+        }
+    }
+
+    fn method_call(&mut self, target: Type, method: &str, args: Vec<Type>, span: &Span) -> Type {
+        match &target {
+            Type::Struct(name) => {
+                let key = (name.clone(), method.to_string());
+
+                // methods get the instance they were called on as their first argument, self
+                if let Some(function) = self.previous.methods.get(&key).cloned() {
+                    let mut method_args = vec![target.clone()];
+                    method_args.extend(args);
+
+                    return self.call(function, method_args, span);
+                }
+
+                // a field holding a function can be called like a method, but without self
+                if let Some(Ok(function)) = self
+                    .instances
+                    .get(name)
+                    .map(|instance| instance.get_field(method))
+                {
+                    return self.call(builtin_type(&function), args, span);
+                }
+
+                if self
+                    .structs
+                    .get(name)
+                    .is_some_and(|def| def.field_index(method).is_some())
+                {
+                    return Type::Any;
+                }
+            }
+            Type::StructDef(name) => {
+                let key = (name.clone(), method.to_string());
+
+                if let Some(function) = self.previous.methods.get(&key).cloned() {
+                    return self.call(function, args, span);
+                }
+            }
+            _ => {}
+        }
+
+        let sample = match self.sample(&target) {
+            Some(sample) => sample,
+            None => return target,
+        };
+
+        match methods::find_method(&sample, method) {
+            Some(found) => {
+                if let Some(arity) = found.arity.filter(|&arity| arity != args.len()) {
+                    let error = GlassError::ArgumentCount {
+                        function: format!("{}.{}", target, method),
+                        expected: arity,
+                        found: args.len(),
+                    };
+                    self.error(error, Some(span.clone()));
+                }
+            }
+            None => {
+                let error = GlassError::NoSuchMethod {
+                    method: method.into(),
+                    type_name: target.to_string(),
+                };
+                self.error(error, Some(span.clone()));
+            }
+        }
+
+        Type::Any
+    }
+
+    /// A value of type `ty` to apply operations to, or `None` if the type isn't known.
+    fn sample(&self, ty: &Type) -> Option<Value> {
+        Some(match ty {
+            Type::Unknown | Type::Any => return None,
+            Type::Num => Value::Num(1.0),
+            Type::Str => Value::Str("a".into()),
+            Type::Bool => Value::Bool(true),
+            Type::List => Value::list(Vec::new()),
+            Type::Tuple => Value::Tuple(Vec::new()),
+            Type::Dict => Value::dict(Dict::new()),
+            Type::Set => Value::Set(Rc::new(RefCell::new(Dict::new()))),
+            Type::Void => Value::Void,
+            Type::Function(_) => sample_function(),
+            Type::StructDef(name) => Value::StructDef(Rc::clone(self.structs.get(name)?)),
+            Type::Struct(name) => {
+                let def = self.structs.get(name)?;
+
+                Value::Struct(Rc::new(RefCell::new(Struct {
+                    def: Rc::clone(def),
+                    values: vec![Value::Void; def.fields.len()],
+                })))
+            }
+        })
+    }
+
+    fn record(&mut self, span: &Span, ty: &Type) {
+        if self.report {
+            self.types.push((span.clone(), ty.to_string()));
+        }
+    }
+
+    fn error(&mut self, error: GlassError, span: Option<Span>) {
+        if !self.report {
+            return;
+        }
+
+        self.errors.push(match span {
+            Some(span) => GlassError::Spanned {
+                error: Box::new(error),
+                src: Rc::clone(&self.src),
+                filename: Rc::clone(&self.filename),
+                span,
+            },
+            None => error,
+        });
+    }
+}
+
+// the type of a builtin value, with the signature of native functions
+fn builtin_type(value: &Value) -> Type {
+    let native = match value {
+        Value::Func(function) => match function.as_ref() {
+            Function::Native(native) => native,
+            _ => return Type::of(value),
+        },
+        value => return Type::of(value),
+    };
+
+    let ret = match native.name {
+        "len" => Type::Num,
+        "type" | "str" => Type::Str,
+        "set" => Type::Set,
+        "print" | "println" | "assert" | "assert_eq" | "exit" => Type::Void,
+        name if name.starts_with("math.") => Type::Num,
+        _ => Type::Any,
+    };
+
+    Type::Function(Some(Rc::new(Signature {
+        name: native.name.into(),
+        params: native.arity.map(|arity| vec![Type::Any; arity]),
+        ret,
+    })))
+}
+
+// a sample is never called, it's only used to check what can be done with a function
+fn sample_function() -> Value {
+    fn never_called(_: &Interpreter, _: Vec<Value>) -> InterpreterResult {
+        Ok(Value::Void)
+    }
+
+    Value::Func(Rc::new(Function::Native(NativeFunction {
+        name: "sample",
+        arity: None,
+        func: never_called,
+    })))
+}
+
+/// Whether every path through `node` ends in a return.
+fn always_returns(node: &Node) -> bool {
+    match node {
+        Node::Return { .. } => true,
+        Node::Block { statements } => statements.last().is_some_and(always_returns),
+        Node::If {
+            body,
+            else_body: Some(else_body),
+            ..
+        } => always_returns(body) && always_returns(else_body),
+        _ => false,
+    }
+}
@@ -0,0 +1,655 @@
+use crate::bytecode::{BinOp, Chunk, Constant, FunctionProto, Instruction, StructProto};
+use crate::error::GlassError;
+use crate::lexer::Token;
+use crate::node::{Node, Slot};
+use logos::Span;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The jumps out of the loop currently being compiled, which are patched once the loop's exit
+/// and continue targets are known.
+#[derive(Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// A try being compiled, which a return, break or continue leaving it has to end the handlers
+/// of and run the finally block of.
+#[derive(Clone, Copy)]
+struct Try<'a> {
+    handlers: usize,
+    finally: Option<&'a Node>,
+    // the number of loops the try is inside of, since break and continue only leave the tries
+    // inside of their loop
+    loops: usize,
+}
+
+/// Compiles a `Node` tree into a `Chunk` for the VM. Every expression leaves exactly one value
+/// on the stack, while statements leave the stack as they found it.
+#[derive(Default)]
+pub struct Compiler<'a> {
+    chunk: Chunk,
+    loops: Vec<Loop>,
+    tries: Vec<Try<'a>>,
+    strings: HashMap<String, u32>,
+    numbers: HashMap<u64, u32>,
+    slots: HashMap<Vec<Slot>, u32>,
+}
+
+type CompileResult = Result<(), GlassError>;
+
+impl<'a> Compiler<'a> {
+    pub fn compile(node: &'a Node) -> Result<Chunk, GlassError> {
+        let mut compiler = Self::default();
+        compiler.compile_statement(node)?;
+
+        Ok(compiler.chunk)
+    }
+
+    // functions get their own chunk, and break/continue can't jump out of them
+    fn compile_function(body: &'a Node) -> Result<Chunk, GlassError> {
+        let mut compiler = Self::default();
+        compiler.compile_statement(body)?;
+
+        // falling off the end of a function returns void
+        compiler.emit(Instruction::Void);
+        compiler.emit(Instruction::Return);
+
+        Ok(compiler.chunk)
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.chunk.code.push(instruction);
+        self.chunk.code.len() - 1
+    }
+
+    /// Emits an instruction that can raise an error, along with where it is in the source.
+    fn emit_spanned(&mut self, instruction: Instruction, span: &Span) -> usize {
+        let position = self.emit(instruction);
+        self.chunk.spans.push((position as u32, span.clone()));
+
+        position
+    }
+
+    fn add_constant(&mut self, constant: Constant) -> u32 {
+        self.chunk.constants.push(constant);
+        (self.chunk.constants.len() - 1) as u32
+    }
+
+    // strings and numbers are used over and over again (especially names), so they are only
+    // added to the constant pool once
+    fn string_constant(&mut self, str: &str) -> u32 {
+        if let Some(&index) = self.strings.get(str) {
+            return index;
+        }
+
+        let index = self.add_constant(Constant::Str(str.into()));
+        self.strings.insert(str.into(), index);
+
+        index
+    }
+
+    fn number_constant(&mut self, num: f64) -> u32 {
+        if let Some(&index) = self.numbers.get(&num.to_bits()) {
+            return index;
+        }
+
+        let index = self.add_constant(Constant::Num(num));
+        self.numbers.insert(num.to_bits(), index);
+
+        index
+    }
+
+    /// A read of a variable, which tries the slots the resolver found for it before looking it
+    /// up by name. Like constants, each list of slots is only added to the chunk once.
+    fn load(&mut self, name: &str, slots: &[Slot]) -> Instruction {
+        let name = self.string_constant(name);
+        let slots = match self.slots.get(slots) {
+            Some(&index) => index,
+            None => {
+                self.chunk.slots.push(slots.to_vec());
+                let index = (self.chunk.slots.len() - 1) as u32;
+                self.slots.insert(slots.to_vec(), index);
+
+                index
+            }
+        };
+
+        Instruction::Load { name, slots }
+    }
+
+    /// Assigns the value on top of the stack to a variable, which goes in its slot of the
+    /// current call if it has one.
+    fn store(&mut self, name: &str, slot: Option<usize>) {
+        let instruction = match slot {
+            Some(index) => Instruction::StoreSlot(index as u32),
+            None => Instruction::Store(self.string_constant(name)),
+        };
+
+        self.emit(instruction);
+    }
+
+    fn current_position(&self) -> u32 {
+        self.chunk.code.len() as u32
+    }
+
+    /// Points the jump at `position` to the next instruction that will be emitted.
+    fn patch_jump(&mut self, position: usize) {
+        let target = self.current_position();
+
+        match &mut self.chunk.code[position] {
+            Instruction::Jump(to)
+            | Instruction::JumpIfFalse(to)
+            | Instruction::AndShortCircuit(to)
+            | Instruction::OrShortCircuit(to)
+            | Instruction::Try(to)
+            | Instruction::TryFinally(to)
+            | Instruction::RangeNext { exit: to, .. }
+            | Instruction::IterNext { exit: to, .. } => *to = target,
+            instruction => panic!("Tried to patch non-jump instruction {:?}", instruction),
+        }
+    }
+
+    /// Points the continues of a loop at `continue_target` and its breaks at the next
+    /// instruction that will be emitted.
+    fn patch_loop(&mut self, loop_jumps: Loop, continue_target: u32) {
+        for position in loop_jumps.continues {
+            self.chunk.code[position] = Instruction::Jump(continue_target);
+        }
+
+        for position in loop_jumps.breaks {
+            self.patch_jump(position);
+        }
+    }
+
+    /// Leaves the innermost `count` tries, ending their handlers and running their finally
+    /// blocks. Each finally block is compiled outside of its own try, like it is run.
+    fn compile_unwind(&mut self, count: usize) -> CompileResult {
+        for index in (self.tries.len() - count..self.tries.len()).rev() {
+            let entry = self.tries[index];
+
+            for _ in 0..entry.handlers {
+                self.emit(Instruction::EndTry);
+            }
+
+            if let Some(finally_body) = entry.finally {
+                let inner = self.tries.split_off(index);
+                self.compile_statement(finally_body)?;
+                self.tries.extend(inner);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_try(
+        &mut self,
+        body: &'a Node,
+        variable: Option<&str>,
+        slot: Option<usize>,
+        catch_body: Option<&'a Node>,
+        finally_body: Option<&'a Node>,
+    ) -> CompileResult {
+        let to_finally = finally_body.map(|_| self.emit(Instruction::TryFinally(0)));
+        let to_catch = catch_body.map(|_| self.emit(Instruction::Try(0)));
+
+        self.tries.push(Try {
+            handlers: to_finally.iter().chain(&to_catch).count(),
+            finally: finally_body,
+            loops: self.loops.len(),
+        });
+        self.compile_statement(body)?;
+
+        if let (Some(to_catch), Some(catch_body)) = (to_catch, catch_body) {
+            self.emit(Instruction::EndTry);
+            let to_after = self.emit(Instruction::Jump(0));
+
+            // the catch handler is gone once it has jumped here, with the error on the stack
+            self.patch_jump(to_catch);
+            self.tries.last_mut().unwrap().handlers -= 1;
+
+            match variable {
+                Some(variable) => self.store(variable, slot),
+                None => {
+                    self.emit(Instruction::Pop);
+                }
+            }
+
+            self.compile_statement(catch_body)?;
+            self.patch_jump(to_after);
+        }
+
+        self.tries.pop();
+
+        if let (Some(to_finally), Some(finally_body)) = (to_finally, finally_body) {
+            self.emit(Instruction::EndTry);
+            self.compile_statement(finally_body)?;
+            let to_end = self.emit(Instruction::Jump(0));
+
+            // when an error wasn't caught, the finally block runs and then raises it again
+            self.patch_jump(to_finally);
+            self.compile_statement(finally_body)?;
+            self.emit(Instruction::Rethrow);
+            self.patch_jump(to_end);
+        }
+
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, node: &'a Node) -> CompileResult {
+        match node {
+            Node::Block { statements } => {
+                for statement in statements {
+                    self.compile_statement(statement)?;
+                }
+            }
+            Node::Assignment {
+                op, left, right, ..
+            } => self.compile_assignment(op, left, right)?,
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                self.compile_expression(condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_statement(body)?;
+
+                match else_body {
+                    Some(else_body) => {
+                        let to_end = self.emit(Instruction::Jump(0));
+                        self.patch_jump(to_else);
+                        self.compile_statement(else_body)?;
+                        self.patch_jump(to_end);
+                    }
+                    None => self.patch_jump(to_else),
+                }
+            }
+            Node::While { condition, body } => {
+                let start = self.current_position();
+
+                self.compile_expression(condition)?;
+                let to_exit = self.emit(Instruction::JumpIfFalse(0));
+
+                let loop_jumps = self.compile_loop_body(body)?;
+                self.emit(Instruction::Jump(start));
+                self.patch_jump(to_exit);
+                self.patch_loop(loop_jumps, start);
+            }
+            Node::For {
+                variable,
+                slot,
+                start,
+                end,
+                inclusive,
+                body,
+                ..
+            } => {
+                self.compile_expression(start)?;
+                self.compile_expression(end)?;
+                self.emit(Instruction::RangeInit {
+                    inclusive: *inclusive,
+                });
+
+                let loop_start = self.current_position();
+                let to_exit = self.emit(Instruction::RangeNext {
+                    inclusive: *inclusive,
+                    exit: 0,
+                });
+                self.store(variable, *slot);
+
+                let loop_jumps = self.compile_loop_body(body)?;
+                let step = self.emit(Instruction::RangeStep) as u32;
+                self.emit(Instruction::Jump(loop_start));
+                self.patch_jump(to_exit);
+                self.patch_loop(loop_jumps, step);
+
+                // the counter and the end
+                self.emit(Instruction::Pop);
+                self.emit(Instruction::Pop);
+            }
+            Node::ForEach {
+                variable,
+                slot,
+                iterable,
+                body,
+                ..
+            } => {
+                self.compile_expression(iterable)?;
+                self.emit(Instruction::IterInit);
+
+                let loop_start = self.current_position();
+                let to_exit = self.emit(Instruction::IterNext { exit: 0 });
+                self.store(variable, *slot);
+
+                let loop_jumps = self.compile_loop_body(body)?;
+                self.emit(Instruction::Jump(loop_start));
+                self.patch_jump(to_exit);
+                self.patch_loop(loop_jumps, loop_start);
+
+                // the items and the index
+                self.emit(Instruction::Pop);
+                self.emit(Instruction::Pop);
+            }
+            Node::Return { value, .. } => {
+                self.compile_expression(value)?;
+                self.compile_unwind(self.tries.len())?;
+                self.emit(Instruction::Return);
+            }
+            Node::Throw { value, span } => {
+                self.compile_expression(value)?;
+                self.emit_spanned(Instruction::Throw, span);
+            }
+            Node::Try {
+                body,
+                variable,
+                slot,
+                catch_body,
+                finally_body,
+                ..
+            } => self.compile_try(
+                body,
+                variable.as_deref(),
+                *slot,
+                catch_body.as_deref(),
+                finally_body.as_deref(),
+            )?,
+            Node::Break { .. } | Node::Continue { .. } => {
+                let is_break = matches!(node, Node::Break { .. });
+
+                if self.loops.is_empty() {
+                    self.emit(Instruction::OutsideLoop { is_break });
+                } else {
+                    let inside_loop = self
+                        .tries
+                        .iter()
+                        .filter(|entry| entry.loops >= self.loops.len())
+                        .count();
+                    self.compile_unwind(inside_loop)?;
+
+                    let position = self.emit(Instruction::Jump(0));
+                    let loop_jumps = self.loops.last_mut().unwrap();
+
+                    if is_break {
+                        loop_jumps.breaks.push(position);
+                    } else {
+                        loop_jumps.continues.push(position);
+                    }
+                }
+            }
+            Node::StructDefinition {
+                name,
+                slot,
+                fields,
+                methods,
+                ..
+            } => {
+                for (_, function) in methods {
+                    self.compile_expression(function)?;
+                }
+
+                let proto = StructProto {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                    methods: methods.iter().map(|(method, _)| method.clone()).collect(),
+                };
+
+                let index = self.add_constant(Constant::Struct(Rc::new(proto)));
+                self.emit(Instruction::Struct(index));
+                self.store(name, *slot);
+            }
+            expression => {
+                self.compile_expression(expression)?;
+                self.emit(Instruction::Pop);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_loop_body(&mut self, body: &'a Node) -> Result<Loop, GlassError> {
+        self.loops.push(Loop::default());
+        self.compile_statement(body)?;
+
+        Ok(self.loops.pop().unwrap_or_default())
+    }
+
+    fn compile_assignment(&mut self, op: &Token, left: &'a Node, right: &'a Node) -> CompileResult {
+        let bin_op = match op {
+            Token::Equal => None,
+            op => match BinOp::from_token(op) {
+                Some(bin_op) => Some(bin_op),
+                None => {
+                    return Err(GlassError::UnknownError {
+                        error_message: "Parsed invalid assignment operator".into(),
+                    })
+                }
+            },
+        };
+
+        match left {
+            Node::Identifier { name, slots, .. } => {
+                if let Some(bin_op) = bin_op {
+                    let load = self.load(name, slots);
+                    self.emit(load);
+                    self.compile_expression(right)?;
+                    self.emit(Instruction::BinaryOp(bin_op));
+                } else {
+                    self.compile_expression(right)?;
+                }
+
+                // like in the interpreter, only a slot of the current call is ever assigned
+                let slot = slots
+                    .first()
+                    .filter(|slot| slot.depth == 0)
+                    .map(|slot| slot.index);
+                self.store(name, slot);
+            }
+            Node::Index { target, index, .. } => {
+                self.compile_expression(target)?;
+                self.compile_expression(index)?;
+
+                if let Some(bin_op) = bin_op {
+                    self.emit(Instruction::Dup2);
+                    self.emit(Instruction::Index);
+                    self.compile_expression(right)?;
+                    self.emit(Instruction::BinaryOp(bin_op));
+                } else {
+                    self.compile_expression(right)?;
+                }
+
+                self.emit(Instruction::SetIndex);
+            }
+            Node::FieldAccess { target, field, .. } => {
+                let field = self.string_constant(field);
+                self.compile_expression(target)?;
+
+                if let Some(bin_op) = bin_op {
+                    self.emit(Instruction::Dup);
+                    self.emit(Instruction::GetField(field));
+                    self.compile_expression(right)?;
+                    self.emit(Instruction::BinaryOp(bin_op));
+                } else {
+                    self.compile_expression(right)?;
+                }
+
+                self.emit(Instruction::SetField(field));
+            }
+            _ => {
+                return Err(GlassError::UnknownError {
+                    error_message: "Parsed invalid assignment target".into(),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, node: &'a Node) -> CompileResult {
+        match node {
+            Node::String { value } => {
+                let index = self.string_constant(value);
+                self.emit(Instruction::Constant(index));
+            }
+            Node::Number { value } => {
+                let index = self.number_constant(*value);
+                self.emit(Instruction::Constant(index));
+            }
+            Node::Bool { value: true } => {
+                self.emit(Instruction::True);
+            }
+            Node::Bool { value: false } => {
+                self.emit(Instruction::False);
+            }
+            Node::Void => {
+                self.emit(Instruction::Void);
+            }
+            Node::Identifier { name, span, slots } => {
+                let load = self.load(name, slots);
+                self.emit_spanned(load, span);
+            }
+            Node::List { items } => {
+                for item in items {
+                    self.compile_expression(item)?;
+                }
+
+                self.emit(Instruction::List(items.len() as u32));
+            }
+            Node::Tuple { items } => {
+                for item in items {
+                    self.compile_expression(item)?;
+                }
+
+                self.emit(Instruction::Tuple(items.len() as u32));
+            }
+            Node::Dict { entries } => {
+                for (key, value) in entries {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+
+                self.emit(Instruction::Dict(entries.len() as u32));
+            }
+            Node::Index {
+                target,
+                index,
+                span,
+            } => {
+                self.compile_expression(target)?;
+                self.compile_expression(index)?;
+                self.emit_spanned(Instruction::Index, span);
+            }
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let bin_op = match BinOp::from_token(op) {
+                    Some(bin_op) => bin_op,
+                    None => {
+                        return Err(GlassError::UnknownError {
+                            error_message: "Parsed invalid binary operation expression".into(),
+                        })
+                    }
+                };
+
+                self.compile_expression(left)?;
+
+                let short_circuit = match bin_op {
+                    BinOp::And => Some(self.emit(Instruction::AndShortCircuit(0))),
+                    BinOp::Or => Some(self.emit(Instruction::OrShortCircuit(0))),
+                    _ => None,
+                };
+
+                self.compile_expression(right)?;
+                self.emit_spanned(Instruction::BinaryOp(bin_op), span);
+
+                if let Some(position) = short_circuit {
+                    self.patch_jump(position);
+                }
+            }
+            Node::UnaryOp { op, expr, span } => {
+                self.compile_expression(expr)?;
+
+                match op {
+                    Token::Minus => {
+                        self.emit_spanned(Instruction::Negate, span);
+                    }
+                    Token::Not => {
+                        self.emit_spanned(Instruction::Not, span);
+                    }
+                    Token::Plus => {}
+                    _ => {
+                        return Err(GlassError::UnknownError {
+                            error_message: "Parsed invalid unary expression".into(),
+                        })
+                    }
+                }
+            }
+            Node::FunctionCall {
+                function,
+                args,
+                span,
+            } => {
+                self.compile_expression(function)?;
+
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+
+                self.emit_spanned(Instruction::Call(args.len() as u32), span);
+            }
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => {
+                let field = self.string_constant(field);
+                self.compile_expression(target)?;
+                self.emit_spanned(Instruction::GetField(field), span);
+            }
+            Node::MethodCall {
+                target,
+                method,
+                args,
+                span,
+            } => {
+                let method = self.string_constant(method);
+                self.compile_expression(target)?;
+
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+
+                self.emit_spanned(Instruction::CallMethod(method, args.len() as u32), span);
+            }
+            Node::FunctionDefinition {
+                name,
+                signature,
+                body,
+                locals,
+                ..
+            } => {
+                let proto = FunctionProto {
+                    name: name.clone(),
+                    params: signature.clone(),
+                    locals: Rc::clone(locals),
+                    chunk: Self::compile_function(body)?,
+                };
+
+                let index = self.add_constant(Constant::Function(Rc::new(proto)));
+                self.emit(Instruction::Closure(index));
+            }
+            // only the parser's statement rules create the rest, so they never show up where a
+            // value is expected
+            statement => {
+                self.compile_statement(statement)?;
+                self.emit(Instruction::Void);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,53 +1,126 @@
+use crate::builtins;
+use crate::node::Slot;
 use crate::value::Value;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 pub struct Context {
     name: String,
-    parent: Option<Box<Context>>,
+    parent: Option<Rc<RefCell<Context>>>,
     variables: HashMap<String, Value>,
+    // the local variables of a function call, as resolved by the resolver. a slot is empty until
+    // the variable is assigned
+    slots: Vec<Option<Value>>,
+    // the name of the variable in each slot
+    slot_names: Rc<[String]>,
 }
 
 impl Context {
     pub fn new() -> Self {
         Self {
             parent: None,
-            variables: HashMap::new(), // todo add builtins
+            variables: builtins::get_builtins(),
+            slots: Vec::new(),
+            slot_names: Rc::from([]),
             name: "global".into(),
         }
     }
 
-    pub fn new_child<T: Into<String>>(&self, name: T) -> Self {
+    pub fn new_child<T: Into<String>>(
+        parent: Rc<RefCell<Context>>,
+        name: T,
+        slots: Rc<[String]>,
+    ) -> Self {
         Self {
-            parent: None,
+            parent: Some(parent),
             variables: HashMap::new(),
+            slots: vec![None; slots.len()],
+            slot_names: slots,
             name: name.into(),
         }
     }
 
-    // pub fn get(&self, name: &str) -> Option<Value> {
-    //     if let Some(&value) = self.variables.get(name) {
-    //         Some(value)
-    //     } else if let Some(parent) = &self.parent {
-    //         parent.get(name)
-    //     } else {
-    //         None
-    //     }
-    // }
-    //
-    // // todo: variables set in a child context should update the ones in the parent context if they have the same name?
-    // pub fn set(&mut self, name: &str, value: Value) {
-    //     self.variables.insert(name.into(), value);
-    // }
-    //
-    // pub fn stack_trace(&self) -> String {
-    //     let mut stack = vec![self.name.clone()];
-    //     let mut current = self;
-    //
-    //     while let Some(parent) = &current.parent {
-    //         stack.push(parent.name.clone());
-    //         current = parent;
-    //     }
-    //     stack.reverse();
-    //     stack.join(" -> ")
-    // }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> Option<&Rc<RefCell<Context>>> {
+        self.parent.as_ref()
+    }
+
+    /// Every variable defined in this context, not including its parents, sorted by name.
+    /// Slots that haven't been assigned yet are left out.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let slots = self
+            .slot_names
+            .iter()
+            .zip(&self.slots)
+            .filter_map(|(name, value)| Some((name.clone(), value.clone()?)));
+
+        let mut variables: Vec<_> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .chain(slots)
+            .collect();
+
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        variables
+    }
+
+    /// The names of the variables defined in this context, not including its parents.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.variables.keys()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.variables.get(name) {
+            Some(value.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.borrow().get(name)
+        } else {
+            None
+        }
+    }
+
+    // variables are always set in the current context, so assigning to a variable inside of a
+    // function shadows a variable of the same name outside of it instead of rebinding it
+    pub fn set(&mut self, name: &str, value: Value) {
+        // avoids allocating a new key when the variable already exists, which is the common case
+        match self.variables.get_mut(name) {
+            Some(variable) => *variable = value,
+            None => {
+                self.variables.insert(name.into(), value);
+            }
+        }
+    }
+
+    pub fn get_slot(&self, slot: Slot) -> Option<Value> {
+        if slot.depth == 0 {
+            self.slots.get(slot.index).cloned().flatten()
+        } else {
+            self.parent.as_ref()?.borrow().get_slot(Slot {
+                depth: slot.depth - 1,
+                index: slot.index,
+            })
+        }
+    }
+
+    pub fn set_slot(&mut self, index: usize, value: Value) {
+        self.slots[index] = Some(value);
+    }
+
+    pub fn stack_trace(&self) -> String {
+        let mut stack = vec![self.name.clone()];
+        let mut current = self.parent.clone();
+
+        while let Some(parent) = current {
+            stack.push(parent.borrow().name.clone());
+            current = parent.borrow().parent.clone();
+        }
+
+        stack.reverse();
+        stack.join(" -> ")
+    }
 }
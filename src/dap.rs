@@ -0,0 +1,527 @@
+//! `glass dap`, a debug adapter that editors talk to over stdin and stdout.
+//!
+//! The script runs on the same thread as the adapter, so requests are only read before it is
+//! started, while it is paused and after it has finished. Pausing and stepping is decided by
+//! the same `Stepper` as `glass debug`, and what the script prints is sent as output events
+//! since stdout carries the protocol.
+
+use crate::debugger::{evaluate, frame_variables, Debugger, Frame, Step, Stepper};
+use crate::error::GlassError;
+use crate::interpreter::Interpreter;
+use crate::lsp::{read_message, write_message};
+use crate::node::Node;
+use crate::value::Value;
+use crate::{parse_source, read_source, script_context};
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::io::StdinLock;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// https://microsoft.github.io/debug-adapter-protocol/specification
+// scripts only ever have the one thread
+const THREAD_ID: u64 = 1;
+
+/// Debugs a single script, from the client launching it until it disconnects.
+pub fn run() -> Result<(), GlassError> {
+    let adapter = Rc::new(RefCell::new(Adapter::new()));
+
+    // the script is started once the client has launched it and set its breakpoints
+    let flow = adapter.borrow_mut().serve()?;
+    let launch = match (flow, adapter.borrow().launch.clone()) {
+        (Flow::Run, Some(launch)) => launch,
+        _ => return Ok(()),
+    };
+
+    let result = run_script(&adapter, launch);
+    adapter.borrow_mut().finish(result)?;
+
+    Ok(())
+}
+
+fn run_script(adapter: &Rc<RefCell<Adapter>>, launch: Launch) -> Result<(), GlassError> {
+    let filename: Rc<str> = Rc::from(launch.program.to_string_lossy().to_string());
+    let src = read_source(&launch.program, &filename)?;
+
+    let context = script_context(launch.args);
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+
+    adapter.borrow_mut().state = State::Running;
+    adapter
+        .borrow_mut()
+        .stepper
+        .resume(if launch.stop_on_entry {
+            Step::In
+        } else {
+            Step::Continue
+        });
+
+    Interpreter::with_context(src, filename, context)
+        .with_debugger(Rc::clone(adapter) as Rc<RefCell<dyn Debugger>>)
+        .visit_node(&ast)
+        .map(|_| ())
+}
+
+#[derive(Clone)]
+struct Launch {
+    program: PathBuf,
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Configuring,
+    Running,
+    Paused,
+    Finished,
+}
+
+/// What to do after answering a request.
+enum Flow {
+    /// Start the script.
+    Run,
+    /// Resume the paused script.
+    Step(Step),
+    /// Stop the script, or stop debugging if it hasn't started.
+    Stop,
+}
+
+/// Something the client can ask for the variables of. References are numbered from 1 and only
+/// last until the script resumes.
+#[derive(Clone)]
+enum Reference {
+    Frame(usize),
+    Value(Value),
+}
+
+struct Adapter {
+    input: StdinLock<'static>,
+    seq: u64,
+    state: State,
+    stepper: Stepper,
+    references: Vec<Reference>,
+    launch: Option<Launch>,
+    // the script as a source of the protocol, see `stack_trace`
+    source: Json,
+    configured: bool,
+    disconnected: bool,
+    // whether the next pause is the one the client asked for with stopOnEntry
+    entry: bool,
+    // 1 if the client counts lines and columns from 1, and 0 if it counts them from 0
+    line_base: usize,
+    column_base: usize,
+}
+
+impl Adapter {
+    fn new() -> Self {
+        Self {
+            input: io::stdin().lock(),
+            seq: 0,
+            state: State::Configuring,
+            stepper: Stepper::default(),
+            references: Vec::new(),
+            launch: None,
+            source: Json::Null,
+            configured: false,
+            disconnected: false,
+            entry: false,
+            line_base: 1,
+            column_base: 1,
+        }
+    }
+
+    /// Answers requests until one of them starts, resumes or stops the script.
+    fn serve(&mut self) -> Result<Flow, GlassError> {
+        while let Some(body) = read_message(&mut self.input)? {
+            // there is no request to answer a malformed message with
+            let Ok(request) = serde_json::from_str::<Json>(&body) else {
+                continue;
+            };
+
+            if let Some(flow) = self.handle(&request)? {
+                return Ok(flow);
+            }
+        }
+
+        // the client went away without disconnecting
+        self.disconnected = true;
+        Ok(Flow::Stop)
+    }
+
+    fn handle(&mut self, request: &Json) -> Result<Option<Flow>, GlassError> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let paused = self.state == State::Paused;
+        let mut flow = None;
+
+        let body = match command {
+            "initialize" => {
+                self.line_base = usize::from(arguments["linesStartAt1"].as_bool() != Some(false));
+                self.column_base =
+                    usize::from(arguments["columnsStartAt1"].as_bool() != Some(false));
+
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsTerminateRequest": true,
+                }))
+            }
+            "launch" if self.state == State::Configuring => match self.read_launch(arguments) {
+                Some(launch) => {
+                    self.launch = Some(launch);
+                    flow = self.configured.then_some(Flow::Run);
+                    Ok(json!({}))
+                }
+                None => Err("Expected the path of the script to debug as program".to_string()),
+            },
+            "launch" => Err("The script has already been launched".into()),
+            "configurationDone" => {
+                self.configured = true;
+
+                if self.state == State::Configuring && self.launch.is_some() {
+                    flow = Some(Flow::Run);
+                }
+
+                Ok(json!({}))
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" if paused => Ok(self.stack_trace()),
+            "scopes" if paused => self.scopes(arguments),
+            "variables" if paused => self.variables(arguments),
+            "evaluate" if paused => self.evaluate(arguments),
+            "continue" | "next" | "stepIn" | "stepOut" if paused => {
+                flow = Some(Flow::Step(match command {
+                    "continue" => Step::Continue,
+                    "next" => Step::Over,
+                    "stepIn" => Step::In,
+                    _ => Step::Out,
+                }));
+
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "stackTrace" | "scopes" | "variables" | "evaluate" | "continue" | "next" | "stepIn"
+            | "stepOut" => Err("The script isn't paused".into()),
+            "disconnect" | "terminate" => {
+                self.disconnected = command == "disconnect";
+                flow = Some(Flow::Stop);
+                Ok(json!({}))
+            }
+            command => Err(format!("Unknown command '{}'", command)),
+        };
+
+        self.respond(request, body)?;
+
+        // the client waits for this before sending its breakpoints
+        if command == "initialize" {
+            self.event("initialized", json!({}))?;
+        }
+
+        Ok(flow)
+    }
+
+    fn read_launch(&mut self, arguments: &Json) -> Option<Launch> {
+        let program = PathBuf::from(arguments["program"].as_str()?);
+        let path = fs::canonicalize(&program).unwrap_or_else(|_| program.clone());
+
+        self.source = json!({
+            "name": program.file_name().map(|name| name.to_string_lossy()),
+            "path": path.to_string_lossy(),
+        });
+
+        let stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        self.entry = stop_on_entry;
+
+        Some(Launch {
+            program,
+            args: arguments["args"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect(),
+            stop_on_entry,
+        })
+    }
+
+    // breakpoints are set per line, since the script is the only source
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let lines: Vec<usize> = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|breakpoint| breakpoint["line"].as_u64())
+            .map(|line| line as usize + 1 - self.line_base)
+            .collect();
+
+        self.stepper.breakpoints = lines.iter().copied().collect();
+
+        let breakpoints: Vec<_> = lines
+            .iter()
+            .map(|line| json!({ "verified": true, "line": self.client_line(*line) }))
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace(&self) -> Json {
+        let frames: Vec<_> = self
+            .stepper
+            .frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(id, frame)| {
+                json!({
+                    "id": id,
+                    "name": frame.name,
+                    "line": self.client_line(frame.line),
+                    "column": frame.column + self.column_base - 1,
+                    "source": self.source,
+                })
+            })
+            .collect();
+
+        json!({ "totalFrames": frames.len(), "stackFrames": frames })
+    }
+
+    fn scopes(&mut self, arguments: &Json) -> Result<Json, String> {
+        let id = self.frame_id(arguments)?;
+        let mut scopes = Vec::new();
+
+        if id > 0 {
+            scopes.push(self.scope("Locals", id));
+        }
+
+        scopes.push(self.scope("Globals", 0));
+
+        Ok(json!({ "scopes": scopes }))
+    }
+
+    fn scope(&mut self, name: &str, id: usize) -> Json {
+        json!({
+            "name": name,
+            "variablesReference": self.reference(Reference::Frame(id)),
+            "expensive": false,
+        })
+    }
+
+    fn variables(&mut self, arguments: &Json) -> Result<Json, String> {
+        let reference = arguments["variablesReference"]
+            .as_u64()
+            .and_then(|reference| self.references.get((reference as usize).checked_sub(1)?))
+            .cloned()
+            .ok_or("Unknown variables reference")?;
+
+        let variables = match reference {
+            Reference::Frame(id) => frame_variables(&self.stepper.frames[id]),
+            Reference::Value(value) => children(&value),
+        };
+
+        let variables: Vec<_> = variables
+            .into_iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": name,
+                    "value": value.repr(),
+                    "type": value.get_type(),
+                    "variablesReference": self.value_reference(value),
+                })
+            })
+            .collect();
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn evaluate(&mut self, arguments: &Json) -> Result<Json, String> {
+        let id = match arguments.get("frameId") {
+            Some(_) => self.frame_id(arguments)?,
+            None => self.stepper.frames.len() - 1,
+        };
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+
+        let mut output = String::new();
+        let result = evaluate(&self.stepper.frames[id], expression, &mut output);
+
+        if !output.is_empty() {
+            self.output(&output).map_err(|err| err.message())?;
+        }
+
+        match result {
+            Ok(value) => Ok(json!({
+                "result": value.repr(),
+                "type": value.get_type(),
+                "variablesReference": self.value_reference(value),
+            })),
+            Err(err) => Err(err.message()),
+        }
+    }
+
+    fn frame_id(&self, arguments: &Json) -> Result<usize, String> {
+        arguments["frameId"]
+            .as_u64()
+            .map(|id| id as usize)
+            .filter(|id| *id < self.stepper.frames.len())
+            .ok_or_else(|| "Unknown frame".into())
+    }
+
+    fn reference(&mut self, reference: Reference) -> usize {
+        self.references.push(reference);
+        self.references.len()
+    }
+
+    // lists, dictionaries and structs can be expanded into their items, other values can't
+    fn value_reference(&mut self, value: Value) -> usize {
+        if children(&value).is_empty() {
+            0
+        } else {
+            self.reference(Reference::Value(value))
+        }
+    }
+
+    fn client_line(&self, line: usize) -> usize {
+        line + self.line_base - 1
+    }
+
+    /// Reports how the script ended, then answers requests until the client disconnects.
+    fn finish(&mut self, result: Result<(), GlassError>) -> Result<(), GlassError> {
+        self.state = State::Finished;
+
+        let code = match result {
+            Ok(()) => 0,
+            Err(GlassError::Exit { code }) => code,
+            Err(err) => {
+                self.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{}\n", err) }),
+                )?;
+                err.exit_code()
+            }
+        };
+
+        if self.disconnected {
+            return Ok(());
+        }
+
+        self.event("exited", json!({ "exitCode": code }))?;
+        self.event("terminated", json!({}))?;
+
+        while !matches!(self.serve()?, Flow::Stop) {}
+
+        Ok(())
+    }
+
+    fn respond(&mut self, request: &Json, body: Result<Json, String>) -> Result<(), GlassError> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<(), GlassError> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Json) -> Result<(), GlassError> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        write_message(&mut io::stdout().lock(), &message)
+    }
+}
+
+impl Debugger for Adapter {
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        statement: &Node,
+    ) -> Result<(), GlassError> {
+        if !self.stepper.should_pause(interpreter, statement) {
+            return Ok(());
+        }
+
+        let frame: &Frame = self
+            .stepper
+            .frames
+            .last()
+            .expect("Paused outside of a frame");
+        let reason = if self.entry {
+            "entry"
+        } else if self.stepper.breakpoints.contains(&frame.line) {
+            "breakpoint"
+        } else {
+            "step"
+        };
+
+        self.entry = false;
+        self.state = State::Paused;
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+
+        let flow = self.serve()?;
+
+        self.references.clear();
+        self.state = State::Running;
+
+        match flow {
+            Flow::Step(step) => {
+                self.stepper.resume(step);
+                Ok(())
+            }
+            // stopping the script is the same as the script exiting
+            Flow::Run | Flow::Stop => Err(GlassError::Exit { code: 0 }),
+        }
+    }
+
+    fn output(&mut self, text: &str) -> Result<(), GlassError> {
+        self.event("output", json!({ "category": "stdout", "output": text }))
+    }
+}
+
+/// The items of a value that can be expanded in the variables view, named by their index, key
+/// or field.
+fn children(value: &Value) -> Vec<(String, Value)> {
+    let indexed = |items: &[Value]| {
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (index.to_string(), item.clone()))
+            .collect()
+    };
+
+    match value {
+        Value::List(list) => indexed(&list.borrow()),
+        Value::Tuple(items) => indexed(items),
+        Value::Dict(dict) => dict
+            .borrow()
+            .iter()
+            .map(|(key, value)| (key.to_value().repr(), value.clone()))
+            .collect(),
+        Value::Set(set) => indexed(
+            &set.borrow()
+                .iter()
+                .map(|(item, _)| item.to_value())
+                .collect::<Vec<_>>(),
+        ),
+        Value::Struct(instance) => instance
+            .borrow()
+            .fields()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
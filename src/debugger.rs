@@ -0,0 +1,373 @@
+//! `glass debug`, which runs a script and pauses it at breakpoints or step by step, to look at
+//! its variables and evaluate expressions where it is paused.
+//!
+//! The interpreter tells its `Debugger` about every statement before running it. Deciding
+//! whether to pause there is left to the `Stepper`, which is shared by the command prompt here
+//! and the debug adapter, so they only differ in how they talk to the user while paused.
+
+use crate::builtins;
+use crate::context::Context;
+use crate::error::{line_and_column, GlassError};
+use crate::function::Function;
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::math;
+use crate::node::Node;
+use crate::value::Value;
+use crate::{parse_source, read_source, script_context};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+pub trait Debugger {
+    /// Called before a statement is run. Returning an error stops the script with it.
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        statement: &Node,
+    ) -> Result<(), GlassError>;
+
+    /// Called with what the script prints.
+    fn output(&mut self, text: &str) -> Result<(), GlassError> {
+        write_stdout(text)
+    }
+}
+
+pub fn write_stdout(text: &str) -> Result<(), GlassError> {
+    let mut stdout = io::stdout().lock();
+
+    stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(io_error)
+}
+
+/// How to go on after pausing.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Step {
+    /// Until the next breakpoint.
+    #[default]
+    Continue,
+    /// Until the next statement, including inside of a function it calls.
+    In,
+    /// Until the next statement of the current function or the one that called it.
+    Over,
+    /// Until the function returns.
+    Out,
+}
+
+/// A function call the script is in, or the top level of the script.
+pub struct Frame {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    pub context: Rc<RefCell<Context>>,
+}
+
+#[derive(Default)]
+pub struct Stepper {
+    pub breakpoints: BTreeSet<usize>,
+    // the calls the script is in, innermost last
+    pub frames: Vec<Frame>,
+    step: Step,
+    // the depth the last step started at
+    depth: usize,
+    // the depth, line and address of the last statement, see `should_pause`
+    previous: Option<(usize, usize, *const Node)>,
+}
+
+impl Stepper {
+    /// Records a statement that is about to be run, returning whether to pause before it. A
+    /// line with several statements on it only pauses before the first one.
+    pub fn should_pause(&mut self, interpreter: &Interpreter, statement: &Node) -> bool {
+        let (line, column) = statement
+            .start()
+            .map_or((0, 0), |start| line_and_column(interpreter.src(), start));
+        let depth = interpreter.depth();
+
+        self.frames.truncate(depth);
+        self.frames.push(Frame {
+            name: interpreter.context().borrow().name().into(),
+            line,
+            column,
+            context: Rc::clone(interpreter.context()),
+        });
+
+        let current = (depth, line, statement as *const Node);
+        let same_line = self
+            .previous
+            .is_some_and(|(previous_depth, previous_line, node)| {
+                (previous_depth, previous_line) == (depth, line) && node != current.2
+            });
+        self.previous = Some(current);
+
+        if same_line {
+            return false;
+        }
+
+        self.breakpoints.contains(&line)
+            || match self.step {
+                Step::Continue => false,
+                Step::In => true,
+                Step::Over => depth <= self.depth,
+                Step::Out => depth < self.depth,
+            }
+    }
+
+    /// Goes on from the statement the script is paused at.
+    pub fn resume(&mut self, step: Step) {
+        self.step = step;
+        self.depth = self.frames.len().saturating_sub(1);
+    }
+}
+
+/// The variables defined in a frame. Builtins are left out, since they are always there.
+pub fn frame_variables(frame: &Frame) -> Vec<(String, Value)> {
+    frame
+        .context
+        .borrow()
+        .variables()
+        .into_iter()
+        .filter(|(_, value)| !is_builtin(value))
+        .collect()
+}
+
+fn is_builtin(value: &Value) -> bool {
+    match value {
+        Value::Func(function) => matches!(function.as_ref(), Function::Native(_)),
+        Value::StructDef(def) => Rc::ptr_eq(def, &builtins::error_struct()),
+        Value::Struct(instance) => Rc::ptr_eq(&instance.borrow().def, &math::module_def()),
+        _ => false,
+    }
+}
+
+/// Evaluates code as if it was written where a frame is paused. The variables are copied, so
+/// assigning to one doesn't change the script, but changing the list or struct it holds does.
+/// Anything the code prints is added to `output`.
+pub fn evaluate(frame: &Frame, code: &str, output: &mut String) -> InterpreterResult {
+    let mut chain = vec![Rc::clone(&frame.context)];
+
+    loop {
+        let parent = chain[chain.len() - 1].borrow().parent().cloned();
+
+        match parent {
+            Some(parent) => chain.push(parent),
+            None => break,
+        }
+    }
+
+    // inner variables hide the outer ones with the same name
+    let mut variables = BTreeMap::new();
+
+    for context in chain.iter().rev() {
+        variables.extend(context.borrow().variables());
+    }
+
+    let mut context = Context::new();
+
+    for (name, value) in variables {
+        context.set(&name, value);
+    }
+
+    let src: Rc<str> = Rc::from(code);
+    let filename: Rc<str> = Rc::from("<debug>");
+    let context = Rc::new(RefCell::new(context));
+    let captured = Rc::new(RefCell::new(Captured::default()));
+
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+    let result = Interpreter::with_context(src, filename, context)
+        .with_debugger(Rc::clone(&captured) as Rc<RefCell<dyn Debugger>>)
+        .visit_node(&ast);
+
+    output.push_str(&captured.borrow().output);
+    result
+}
+
+// collects what evaluated code prints, which can't go through the debugger that is paused
+#[derive(Default)]
+struct Captured {
+    output: String,
+}
+
+impl Debugger for Captured {
+    fn before_statement(&mut self, _: &Interpreter, _: &Node) -> Result<(), GlassError> {
+        Ok(())
+    }
+
+    fn output(&mut self, text: &str) -> Result<(), GlassError> {
+        self.output.push_str(text);
+        Ok(())
+    }
+}
+
+const HELP: &str = "\
+break <line>   (b)   pause before the line is run
+delete <line>  (d)   remove the breakpoint on the line
+breakpoints          list the breakpoints
+continue       (c)   run until the next breakpoint
+step           (s)   run until the next statement, going into function calls
+next           (n)   run until the next statement, going over function calls
+out            (o)   run until the current function returns
+print <code>   (p)   evaluate code where the script is paused
+vars           (v)   list the variables of the current function
+stack          (bt)  list the function calls the script is in
+list           (l)   show the lines around the current one
+quit           (q)   stop the script";
+
+/// Runs a script in the debugger, pausing before its first statement.
+pub fn run_debugger(file: &Path, script_args: Vec<String>) -> Result<(), GlassError> {
+    let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+    let src = read_source(file, &filename)?;
+
+    let context = script_context(script_args);
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+
+    let mut prompt = Prompt::default();
+    prompt.stepper.resume(Step::In);
+
+    println!("Debugging {}, type help for the commands", filename);
+
+    let prompt = Rc::new(RefCell::new(prompt));
+    let interpreter = Interpreter::with_context(src, filename, context).with_debugger(prompt);
+    interpreter.visit_node(&ast)?;
+
+    println!("The script finished");
+
+    Ok(())
+}
+
+/// Talks to the user over stdin and stdout while the script is paused.
+#[derive(Default)]
+struct Prompt {
+    stepper: Stepper,
+}
+
+impl Debugger for Prompt {
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        statement: &Node,
+    ) -> Result<(), GlassError> {
+        if !self.stepper.should_pause(interpreter, statement) {
+            return Ok(());
+        }
+
+        let src = interpreter.src();
+        self.show_location(interpreter.filename(), src);
+
+        loop {
+            print!("(glass) ");
+            io::stdout().flush().map_err(io_error)?;
+
+            let mut line = String::new();
+
+            // the script stops once there is nothing more to read
+            if io::stdin().lock().read_line(&mut line).map_err(io_error)? == 0 {
+                return Err(GlassError::Exit { code: 0 });
+            }
+
+            let (command, argument) = match line.trim().split_once(' ') {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line.trim(), ""),
+            };
+
+            let step = match command {
+                "continue" | "c" => Step::Continue,
+                "step" | "s" => Step::In,
+                "next" | "n" => Step::Over,
+                "out" | "o" => Step::Out,
+                "quit" | "q" => return Err(GlassError::Exit { code: 0 }),
+                command => {
+                    self.command(command, argument, src);
+                    continue;
+                }
+            };
+
+            self.stepper.resume(step);
+            return Ok(());
+        }
+    }
+}
+
+impl Prompt {
+    /// Runs a command that doesn't resume the script.
+    fn command(&mut self, command: &str, argument: &str, src: &str) {
+        let frame = self
+            .stepper
+            .frames
+            .last()
+            .expect("Paused outside of a frame");
+
+        match command {
+            "break" | "b" | "delete" | "d" => match argument.parse::<usize>() {
+                Ok(line) if matches!(command, "break" | "b") => {
+                    self.stepper.breakpoints.insert(line);
+                    println!("Breakpoint set on line {}", line);
+                }
+                Ok(line) if self.stepper.breakpoints.remove(&line) => {
+                    println!("Breakpoint removed from line {}", line);
+                }
+                Ok(line) => println!("There is no breakpoint on line {}", line),
+                Err(_) => println!("Expected a line number"),
+            },
+            "breakpoints" => {
+                for line in &self.stepper.breakpoints {
+                    println!("line {}", line);
+                }
+            }
+            "print" | "p" => {
+                let mut output = String::new();
+                let result = evaluate(frame, argument, &mut output);
+
+                print!("{}", output);
+
+                match result {
+                    Ok(value) => println!("{}", value.repr()),
+                    Err(err) => println!("{}", err),
+                }
+            }
+            "vars" | "v" => {
+                for (name, value) in frame_variables(frame) {
+                    println!("{} = {}", name, value.repr());
+                }
+            }
+            "stack" | "bt" => {
+                for (index, frame) in self.stepper.frames.iter().enumerate().rev() {
+                    println!("#{} {} at line {}", index, frame.name, frame.line);
+                }
+            }
+            "list" | "l" => list(src, frame.line),
+            "help" | "h" => println!("{}", HELP),
+            "" => {}
+            command => println!("Unknown command '{}', type help for the commands", command),
+        }
+    }
+
+    fn show_location(&self, filename: &str, src: &str) {
+        if let Some(frame) = self.stepper.frames.last() {
+            let line = src.lines().nth(frame.line.saturating_sub(1)).unwrap_or("");
+            println!("Paused in {} at {}:{}", frame.name, filename, frame.line);
+            println!("{:>4} | {}", frame.line, line.trim_end());
+        }
+    }
+}
+
+// prints the lines around `current`, marking it
+fn list(src: &str, current: usize) {
+    let first = current.saturating_sub(3).max(1);
+
+    for (index, line) in src.lines().enumerate().skip(first - 1).take(7) {
+        let number = index + 1;
+        let marker = if number == current { ">" } else { " " };
+
+        println!("{} {:>4} | {}", marker, number, line.trim_end());
+    }
+}
+
+fn io_error(err: io::Error) -> GlassError {
+    GlassError::UnknownError {
+        error_message: err.to_string(),
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::GlassError;
+use crate::sandbox;
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// The hashable form of a `Value`, used to index into a `Dict`. Only immutable values can be
+/// used as keys, so lists and dictionaries have no key form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DictKey {
+    // stored as bits so that keys can be hashed, see `DictKey::from_value`
+    Num(u64),
+    Str(String),
+    Bool(bool),
+    Tuple(Vec<DictKey>),
+    Void,
+}
+
+impl DictKey {
+    pub fn from_value(value: &Value) -> Result<Self, GlassError> {
+        Ok(match value {
+            // -0 and 0 compare equal so they have to hash the same
+            Value::Num(num) if *num == 0.0 => DictKey::Num(0f64.to_bits()),
+            Value::Num(num) => DictKey::Num(num.to_bits()),
+            Value::Str(str) => DictKey::Str(str.clone()),
+            Value::Bool(bool) => DictKey::Bool(*bool),
+            Value::Tuple(items) => DictKey::Tuple(
+                items
+                    .iter()
+                    .map(DictKey::from_value)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Void => DictKey::Void,
+            value => {
+                return Err(GlassError::UnhashableType {
+                    type_name: value.get_type(),
+                })
+            }
+        })
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            DictKey::Num(bits) => Value::Num(f64::from_bits(*bits)),
+            DictKey::Str(str) => Value::Str(str.clone()),
+            DictKey::Bool(bool) => Value::Bool(*bool),
+            DictKey::Tuple(items) => Value::Tuple(items.iter().map(DictKey::to_value).collect()),
+            DictKey::Void => Value::Void,
+        }
+    }
+}
+
+/// A dictionary that remembers the order its keys were first inserted in, which is the order
+/// it is iterated and printed in.
+#[derive(Debug, Default, Clone)]
+pub struct Dict {
+    entries: Vec<(DictKey, Value)>,
+    indices: HashMap<DictKey, usize>,
+}
+
+impl Dict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>, GlassError> {
+        let key = DictKey::from_value(key)?;
+
+        Ok(self.indices.get(&key).map(|&index| &self.entries[index].1))
+    }
+
+    /// Inserts a value, keeping the original position if the key is already present.
+    pub fn insert(&mut self, key: &Value, value: Value) -> Result<Option<Value>, GlassError> {
+        let key = DictKey::from_value(key)?;
+
+        if !self.indices.contains_key(&key) {
+            sandbox::check_items(1)?;
+        }
+
+        Ok(self.insert_key(key, value))
+    }
+
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, GlassError> {
+        let key = DictKey::from_value(key)?;
+
+        let index = match self.indices.remove(&key) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let (_, value) = self.entries.remove(index);
+
+        // every entry after the removed one has moved back by one
+        for (key, _) in &self.entries[index..] {
+            if let Some(index) = self.indices.get_mut(key) {
+                *index -= 1;
+            }
+        }
+
+        Ok(Some(value))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&DictKey, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Two dictionaries have equal entries if they have the same keys, regardless of insertion
+    /// order, and `eq` holds for the values of each key.
+    pub fn entries_eq(&self, other: &Self, mut eq: impl FnMut(&Value, &Value) -> bool) -> bool {
+        self.len() == other.len()
+            && self.entries.iter().all(|(key, value)| {
+                other
+                    .indices
+                    .get(key)
+                    .is_some_and(|&index| eq(value, &other.entries[index].1))
+            })
+    }
+
+    fn insert_key(&mut self, key: DictKey, value: Value) -> Option<Value> {
+        if let Some(&index) = self.indices.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[index].1, value));
+        }
+
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+
+        None
+    }
+}
+
+impl Extend<(DictKey, Value)> for Dict {
+    fn extend<T: IntoIterator<Item = (DictKey, Value)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert_key(key, value);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::builtins;
+use crate::serialize::{GIT_REVISION, GLASS_VERSION};
+use crate::value::Value;
 use crate::Token;
-use git_version::git_version;
 use line_span::{find_line_end, find_line_start};
 use logos::Span;
 use std::cmp::min;
@@ -7,6 +9,14 @@ use std::fmt::Debug;
 use std::rc::Rc;
 use thiserror::Error;
 
+// the exit codes of glass, which follow sysexits.h for the errors that aren't the script's fault
+/// A runtime error, or a check like `glass test` that failed.
+pub const RUNTIME_ERROR_EXIT: i32 = 1;
+/// The script can't be run because it is malformed.
+pub const SYNTAX_ERROR_EXIT: i32 = 65;
+/// A bug in glass itself.
+pub const INTERNAL_ERROR_EXIT: i32 = 70;
+
 fn get_token<'a>(src: &'a str, span: &'a Span) -> &'a str {
     src[span.start..span.end].trim()
 }
@@ -14,27 +24,71 @@ fn get_token<'a>(src: &'a str, span: &'a Span) -> &'a str {
 fn get_line<'a>(src: &'a str, filename: &'a str, span: &'a Span) -> String {
     let start = find_line_start(src, span.start);
     let end = find_line_end(src, span.start);
-    let line = &src[start..end].trim();
-    let line_num = src[..span.start].lines().count();
-    let affected_range = span.start - start..span.end - start;
+    let untrimmed = &src[start..end];
+    let line = untrimmed.trim();
+    // the columns are the same ones the JSON output reports, counted from 1 in the whole line,
+    // but the line is printed without its indentation, so only the caret is shifted
+    let (line_num, column) = line_and_column(src, span.start);
+    let width = src[span.start..min(span.end, end)].chars().count();
+    let indent = untrimmed.chars().count() - untrimmed.trim_start().chars().count();
+    let caret = (column - 1).saturating_sub(indent);
 
     format!(
-        "\n\n\t{line}\n\t{}{}\n[{filename}(Ln:{line_num}, Col:{affected_range:?})]",
-        &" ".repeat(affected_range.start),
-        &"^".repeat(min(affected_range.len(), line.len() - affected_range.start)),
+        "\n\n\t{line}\n\t{}{}\n[{filename}(Ln:{line_num}, Col:{column}..{})]",
+        &" ".repeat(caret),
+        // an empty span, like the end of the file, still gets a caret where it is
+        &"^".repeat(match width {
+            0 => 1,
+            width => min(width, line.chars().count().saturating_sub(caret)),
+        }),
+        column + width,
     )
 }
 
+/// The line and column of `offset` in `src`, both counted from 1.
+pub fn line_and_column(src: &str, offset: usize) -> (usize, usize) {
+    let start = find_line_start(src, offset);
+    let line = src[..offset].matches('\n').count() + 1;
+
+    (line, src[start..offset].chars().count() + 1)
+}
+
+// an uncaught error value is described by its kind and message, anything else that was thrown
+// is shown as it is
+fn describe_thrown(value: &Value) -> String {
+    if let Value::Struct(instance) = value {
+        let instance = instance.borrow();
+
+        if let (true, [Value::Str(message), Value::Str(kind), _]) = (
+            Rc::ptr_eq(&instance.def, &builtins::error_struct()),
+            &instance.values[..],
+        ) {
+            return format!("{}: {}", kind, message);
+        }
+    }
+
+    format!("exception {}", value.repr())
+}
+
 // todo: instead of passing source to each error, just get the span and pass that to the error handler in main?
 // I didn't think about if this is possible when I wrote this, but it might be ^
 #[derive(Error, Debug)]
 pub enum GlassError {
-    #[error("Unknown error '{error_message}'. Please report this bug with the following information: Glass Version = '{}', Git Revision = '{}'", env!("CARGO_PKG_VERSION"), git_version!(fallback = "<unknown>"))]
+    #[error("Unknown error '{error_message}'. Please report this bug with the following information: Glass Version = '{}', Git Revision = '{}'", GLASS_VERSION, GIT_REVISION)]
     UnknownError { error_message: String },
 
     #[error("File '{filename}' not found")]
     FileNotFound { filename: Rc<str> },
 
+    #[error("Could not write to file '{filename}': {reason}")]
+    FileWriteError { filename: Rc<str>, reason: String },
+
+    #[error("'{filename}' is not a valid compiled glass file: {reason}")]
+    InvalidBytecode { filename: Rc<str>, reason: String },
+
+    #[error("'{filename}' was compiled by a different build of glass ({version}), recompile it from its source")]
+    IncompatibleBytecode { filename: Rc<str>, version: String },
+
     #[error(
         "Unknown token '{}' encountered at {}",
         get_token(src, span),
@@ -83,13 +137,14 @@ pub enum GlassError {
         span: Span,
     },
 
-    #[error("Unexpected end of file in source file '{filename}'")]
-    UnexpectedEndOfInput { filename: Rc<str> },
+    #[error("Unexpected end of file at {}", get_line(src, filename, span))]
+    UnexpectedEndOfInput {
+        src: Rc<str>,
+        filename: Rc<str>,
+        span: Span,
+    },
 
-    #[error(
-        "Cannot use operation '{operation}' on type '{left}' and '{right}' at {}",
-        "get_line(src, filename, span)"
-    )]
+    #[error("Cannot use operation '{operation}' on type '{left}' and '{right}'")]
     InvalidOperation {
         operation: String,
         left: String,
@@ -103,7 +158,312 @@ pub enum GlassError {
     #[error("Unary operator '{operation}' cannot be applied to type '{operand}'")]
     InvalidUnaryOperation { operation: String, operand: String },
 
+    // an error found before the script is run, while checking or optimizing it
+    #[error("{error} at {}", get_line(src, filename, span))]
+    Spanned {
+        error: Box<GlassError>,
+        src: Rc<str>,
+        filename: Rc<str>,
+        span: Span,
+    },
+
+    #[error("Type '{type_name}' cannot be used as a dictionary key or set item")]
+    UnhashableType { type_name: String },
+
+    #[error("Key {key} not found in dictionary")]
+    KeyNotFound { key: String },
+
+    #[error("Index {index} is out of bounds for length {len}")]
+    IndexOutOfBounds { index: String, len: usize },
+
+    #[error("Variable '{name}' is not defined")]
+    UndefinedVariable { name: String },
+
+    #[error("No method '{method}' on type '{type_name}'")]
+    NoSuchMethod { method: String, type_name: String },
+
+    #[error("No field '{field}' on type '{type_name}'")]
+    NoSuchField { field: String, type_name: String },
+
+    #[error("Field '{field}' of type '{type_name}' is read-only")]
+    ReadOnlyField { field: String, type_name: String },
+
+    #[error("Value of type '{type_name}' is not callable")]
+    NotCallable { type_name: String },
+
+    #[error("Function '{function}' expected {expected} argument(s) but {found} were given")]
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Invalid argument passed to '{function}': {message}")]
+    InvalidArgument { function: String, message: String },
+
+    #[error("'{function}' is not defined for {arguments}")]
+    DomainError { function: String, arguments: String },
+
+    #[error(
+        "The list was modified by the function passed to '{function}' while it was being sorted"
+    )]
+    ModifiedWhileSorting { function: String },
+
+    #[error("Cannot use operation '{operation}' with a divisor of zero")]
+    DivisionByZero { operation: String },
+
+    #[error("Expected condition to be of type 'boolean' but found '{condition_type}'")]
+    InvalidCondition { condition_type: String },
+
+    #[error("Value of type '{type_name}' is not iterable")]
+    NotIterable { type_name: String },
+
+    #[error("Expected type '{expected}' but found '{found}'")]
+    TypeMismatch { expected: String, found: String },
+
+    #[error("Unknown type '{name}'")]
+    UnknownType { name: String },
+
+    #[error("Found {errors} type error(s)")]
+    TypeCheckFailed { errors: usize },
+
+    #[error("Assertion failed{}", message.as_ref().map(|message| format!(": {}", message)).unwrap_or_default())]
+    AssertionFailed { message: Option<String> },
+
+    #[error("Assertion failed: the values are not equal (- left, + right)\n{diff}")]
+    AssertionNotEqual { diff: String },
+
+    #[error("{failed} test(s) failed")]
+    TestsFailed { failed: usize },
+
+    #[error("{files} script(s) are not formatted")]
+    Unformatted { files: usize },
+
+    // raised by exit(), which stops the script with an exit code
+    #[error("Script exited with code {code}")]
+    Exit { code: i32 },
+
+    // raised when a script goes over one of the limits it was run with, see `Limits`
+    #[error("Script took more than the limit of {limit} steps")]
+    StepLimitExceeded { limit: u64 },
+
+    #[error("Script ran for longer than the limit of {limit_ms} ms")]
+    TimeLimitExceeded { limit_ms: u128 },
+
+    #[error("Script would take up {size} bytes, more than the limit of {limit} bytes")]
+    MemoryLimitExceeded { size: usize, limit: usize },
+
+    #[error("Function calls are nested deeper than the limit of {limit}")]
+    RecursionLimitExceeded { limit: usize },
+
+    #[error("'{function}' needs the {capability} capability, which is denied")]
+    CapabilityDisabled {
+        function: String,
+        capability: String,
+    },
+
+    #[error("Uncaught {}", describe_thrown(value))]
+    Thrown { value: Value },
+
+    #[error("'{statement}' used outside of a loop")]
+    ControlFlowOutsideLoop { statement: String },
+
+    #[error("'return' used outside of a function")]
+    ReturnOutsideFunction,
+
+    // the rest are warnings, which don't stop the script from running
+    #[error("Variable '{name}' is used before it is defined")]
+    UsedBeforeDefinition { name: String },
+
+    #[error("Variable '{name}' is assigned but never used")]
+    UnusedVariable { name: String },
+
+    #[error("Variable '{name}' shadows a variable of the same name in an enclosing scope")]
+    ShadowedVariable { name: String },
+
+    #[error("This operation always fails when it is run: {error}")]
+    AlwaysFails { error: Box<GlassError> },
+
     // this error is to only be used in development as a placeholder for errors that haven't been implemented yet
+    #[allow(dead_code)]
     #[error("{message}")]
     PlaceholderError { message: String },
 }
+
+impl GlassError {
+    /// Whether a catch block can catch this error. Exiting can't be caught, so that a script
+    /// always stops when it asks to, and neither can running out of steps or time, so that it
+    /// always stops when it has to.
+    pub fn is_catchable(&self) -> bool {
+        match self {
+            GlassError::Spanned { error, .. } => error.is_catchable(),
+            GlassError::Exit { .. }
+            | GlassError::StepLimitExceeded { .. }
+            | GlassError::TimeLimitExceeded { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// The exit code glass stops with because of this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GlassError::Spanned { error, .. } => error.exit_code(),
+            GlassError::Exit { code } => *code,
+            GlassError::UnknownToken { .. }
+            | GlassError::UnclosedString { .. }
+            | GlassError::UnknownEscapeSequence { .. }
+            | GlassError::UnexpectedToken { .. }
+            | GlassError::UnexpectedEndOfInput { .. }
+            | GlassError::ControlFlowOutsideLoop { .. }
+            | GlassError::ReturnOutsideFunction => SYNTAX_ERROR_EXIT,
+            GlassError::UnknownError { .. } | GlassError::PlaceholderError { .. } => {
+                INTERNAL_ERROR_EXIT
+            }
+            _ => RUNTIME_ERROR_EXIT,
+        }
+    }
+
+    /// The source this error points at, the file it is from and the span in it, if it points
+    /// somewhere.
+    pub fn location(&self) -> Option<(&Rc<str>, &Rc<str>, &Span)> {
+        match self {
+            GlassError::Spanned {
+                src,
+                filename,
+                span,
+                ..
+            }
+            | GlassError::UnknownToken {
+                src,
+                filename,
+                span,
+            }
+            | GlassError::UnclosedString {
+                src,
+                filename,
+                span,
+            }
+            | GlassError::UnknownEscapeSequence {
+                src,
+                filename,
+                span,
+                ..
+            }
+            | GlassError::UnexpectedToken {
+                src,
+                filename,
+                span,
+                ..
+            }
+            | GlassError::UnexpectedEndOfInput {
+                src,
+                filename,
+                span,
+            } => Some((src, filename, span)),
+            _ => None,
+        }
+    }
+
+    /// The file this error happened in, which is also known for some errors that don't point
+    /// at a span.
+    pub fn filename(&self) -> Option<&Rc<str>> {
+        match self {
+            GlassError::FileNotFound { filename }
+            | GlassError::FileWriteError { filename, .. }
+            | GlassError::InvalidBytecode { filename, .. }
+            | GlassError::IncompatibleBytecode { filename, .. } => Some(filename),
+            error => error.location().map(|(_, filename, _)| filename),
+        }
+    }
+
+    /// The span of the source this error points at, if it has one.
+    pub fn span(&self) -> Option<&Span> {
+        self.location().map(|(_, _, span)| span)
+    }
+
+    /// The message of this error without the excerpt of the source it points at, for when the
+    /// location is shown some other way.
+    pub fn message(&self) -> String {
+        match self {
+            GlassError::Spanned { error, .. } => error.message(),
+            GlassError::UnknownToken { src, span, .. } => {
+                format!("Unknown token '{}'", get_token(src, span))
+            }
+            GlassError::UnclosedString { .. } => "Unclosed string literal".into(),
+            GlassError::UnknownEscapeSequence {
+                escape_sequence, ..
+            } => format!("Unknown escape sequence '{}'", escape_sequence),
+            GlassError::UnexpectedToken {
+                expected: Some(expected),
+                src,
+                span,
+                ..
+            } => format!(
+                "Expected '{}' but found '{}' instead",
+                expected.get_rep(),
+                get_token(src, span)
+            ),
+            GlassError::UnexpectedToken { src, span, .. } => {
+                format!("Unexpected token '{}'", get_token(src, span))
+            }
+            GlassError::UnexpectedEndOfInput { .. } => "Unexpected end of file".into(),
+            error => error.to_string(),
+        }
+    }
+
+    /// The name of this kind of error, which is the `kind` of the error value a script catches.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GlassError::Spanned { error, .. } => error.kind(),
+            GlassError::UnknownError { .. } => "UnknownError",
+            GlassError::FileNotFound { .. } => "FileNotFound",
+            GlassError::FileWriteError { .. } => "FileWriteError",
+            GlassError::InvalidBytecode { .. } => "InvalidBytecode",
+            GlassError::IncompatibleBytecode { .. } => "IncompatibleBytecode",
+            GlassError::UnknownToken { .. } => "UnknownToken",
+            GlassError::UnclosedString { .. } => "UnclosedString",
+            GlassError::UnknownEscapeSequence { .. } => "UnknownEscapeSequence",
+            GlassError::UnexpectedToken { .. } => "UnexpectedToken",
+            GlassError::UnexpectedEndOfInput { .. } => "UnexpectedEndOfInput",
+            GlassError::InvalidOperation { .. } => "InvalidOperation",
+            GlassError::InvalidUnaryOperation { .. } => "InvalidUnaryOperation",
+            GlassError::UnhashableType { .. } => "UnhashableType",
+            GlassError::KeyNotFound { .. } => "KeyNotFound",
+            GlassError::IndexOutOfBounds { .. } => "IndexOutOfBounds",
+            GlassError::UndefinedVariable { .. } => "UndefinedVariable",
+            GlassError::NoSuchMethod { .. } => "NoSuchMethod",
+            GlassError::NoSuchField { .. } => "NoSuchField",
+            GlassError::ReadOnlyField { .. } => "ReadOnlyField",
+            GlassError::NotCallable { .. } => "NotCallable",
+            GlassError::ArgumentCount { .. } => "ArgumentCount",
+            GlassError::InvalidArgument { .. } => "InvalidArgument",
+            GlassError::DomainError { .. } => "DomainError",
+            GlassError::ModifiedWhileSorting { .. } => "ModifiedWhileSorting",
+            GlassError::DivisionByZero { .. } => "DivisionByZero",
+            GlassError::InvalidCondition { .. } => "InvalidCondition",
+            GlassError::NotIterable { .. } => "NotIterable",
+            GlassError::TypeMismatch { .. } => "TypeMismatch",
+            GlassError::UnknownType { .. } => "UnknownType",
+            GlassError::TypeCheckFailed { .. } => "TypeCheckFailed",
+            GlassError::AssertionFailed { .. } | GlassError::AssertionNotEqual { .. } => {
+                "AssertionFailed"
+            }
+            GlassError::TestsFailed { .. } => "TestsFailed",
+            GlassError::Unformatted { .. } => "Unformatted",
+            GlassError::Exit { .. } => "Exit",
+            GlassError::StepLimitExceeded { .. } => "StepLimitExceeded",
+            GlassError::TimeLimitExceeded { .. } => "TimeLimitExceeded",
+            GlassError::MemoryLimitExceeded { .. } => "MemoryLimitExceeded",
+            GlassError::RecursionLimitExceeded { .. } => "RecursionLimitExceeded",
+            GlassError::CapabilityDisabled { .. } => "CapabilityDisabled",
+            GlassError::Thrown { .. } => "Error",
+            GlassError::ControlFlowOutsideLoop { .. } => "ControlFlowOutsideLoop",
+            GlassError::ReturnOutsideFunction => "ReturnOutsideFunction",
+            GlassError::UsedBeforeDefinition { .. } => "UsedBeforeDefinition",
+            GlassError::UnusedVariable { .. } => "UnusedVariable",
+            GlassError::ShadowedVariable { .. } => "ShadowedVariable",
+            GlassError::AlwaysFails { .. } => "AlwaysFails",
+            GlassError::PlaceholderError { .. } => "PlaceholderError",
+        }
+    }
+}
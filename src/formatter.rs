@@ -0,0 +1,355 @@
+//! `glass fmt`, which prints scripts in a canonical style.
+//!
+//! The formatter works on the token stream rather than the AST, so that comments and blank
+//! lines survive. Every statement starts on its own line and blocks always span several lines,
+//! while an expression broken over several lines is joined back into one. Parentheses, lists
+//! and dictionaries stay on one line unless the author broke a line inside of them, in which
+//! case each item goes on its own line.
+
+use crate::error::GlassError;
+use crate::lexer::{lex_with_trivia, Token, Trivia, TriviaToken};
+use crate::parser::Parser;
+use logos::Logos;
+use std::rc::Rc;
+
+pub struct FormatOptions {
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    Paren,
+    List,
+    Block,
+    Dict,
+}
+
+/// A bracket that hasn't been closed yet, and the indent of the line it was opened on.
+struct Open {
+    bracket: Bracket,
+    indent: usize,
+    // whether the author broke a line right inside of it
+    multiline: bool,
+}
+
+/// Formats a script, which has to parse. The result is checked to have exactly the same tokens
+/// as the script, so formatting can never change what a script does.
+pub fn format(src: &str, filename: Rc<str>, options: &FormatOptions) -> Result<String, GlassError> {
+    let src: Rc<str> = Rc::from(src);
+    Parser::new(
+        Token::lexer(&src).spanned().collect(),
+        Rc::clone(&src),
+        Rc::clone(&filename),
+    )
+    .parse()?;
+
+    let (tokens, trailing) = lex_with_trivia(&src);
+    let formatted = Formatter::new(&src, options).format(&tokens, &trailing);
+
+    let before: Vec<Token> = Token::lexer(&src).collect();
+    let after: Vec<Token> = Token::lexer(&formatted).collect();
+
+    if before != after {
+        return Err(GlassError::UnknownError {
+            error_message: format!("Formatting '{}' changed its tokens", filename),
+        });
+    }
+
+    Ok(formatted)
+}
+
+struct Formatter<'a> {
+    src: &'a str,
+    options: &'a FormatOptions,
+    out: String,
+    // the line being built, without its indent
+    line: String,
+    indent: usize,
+    open: Vec<Open>,
+    blank_line: bool,
+    // whether there was a line break since the previous token
+    broken: bool,
+    previous: Option<&'a Token>,
+    previous_unary: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(src: &'a str, options: &'a FormatOptions) -> Self {
+        Self {
+            src,
+            options,
+            out: String::new(),
+            line: String::new(),
+            indent: 0,
+            open: Vec::new(),
+            blank_line: false,
+            broken: false,
+            previous: None,
+            previous_unary: false,
+        }
+    }
+
+    fn format(mut self, tokens: &'a [TriviaToken], trailing: &[Trivia]) -> String {
+        let multiline = multiline_brackets(tokens);
+
+        for (token, multiline) in tokens.iter().zip(multiline) {
+            self.trivia(&token.leading);
+            self.token(&token.token, &self.src[token.span.clone()], multiline);
+        }
+
+        self.trivia(trailing);
+        self.end_line();
+
+        self.out
+    }
+
+    // the indent of the lines inside of the innermost bracket
+    fn content_indent(&self) -> usize {
+        self.open.last().map_or(0, |open| open.indent + 1)
+    }
+
+    fn end_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        let indent = " ".repeat(self.indent * self.options.indent_width);
+        self.out.push_str(&indent);
+        self.out.push_str(self.line.trim_end());
+        self.out.push('\n');
+        self.line.clear();
+    }
+
+    /// Starts a new line, after a blank line if there should be one. Blank lines are dropped at
+    /// the start of the script and right inside of brackets.
+    fn start_line(&mut self, indent: usize, closes: bool) {
+        let after_open = self.previous.is_some_and(is_open);
+
+        if self.blank_line && !self.out.is_empty() && !after_open && !closes {
+            self.out.push('\n');
+        }
+
+        self.blank_line = false;
+        self.indent = indent;
+    }
+
+    fn trivia(&mut self, trivia: &[Trivia]) {
+        for item in trivia {
+            match item {
+                Trivia::Newlines(count) => {
+                    self.broken = true;
+                    self.blank_line |= *count > 1;
+                }
+                // a comment after a token stays on its line
+                Trivia::Comment(comment) if !self.broken && !self.line.is_empty() => {
+                    self.line.push(' ');
+                    self.line.push_str(comment);
+                    self.end_line();
+                }
+                Trivia::Comment(comment) => {
+                    self.end_line();
+                    self.start_line(self.content_indent(), false);
+                    self.line.push_str(comment);
+                    self.end_line();
+                }
+            }
+        }
+    }
+
+    fn token(&mut self, token: &'a Token, text: &str, multiline: bool) {
+        if self.breaks_before(token) {
+            self.end_line();
+        } else {
+            // blank lines only separate statements
+            self.blank_line = false;
+        }
+
+        self.broken = false;
+
+        if self.line.is_empty() {
+            let closes = is_close(token);
+            let indent = match (closes, self.open.last()) {
+                (true, Some(open)) => open.indent,
+                _ => self.content_indent(),
+            };
+
+            self.start_line(indent, closes);
+        } else if let Some(previous) = self.previous {
+            if self.spaced(previous, token) {
+                self.line.push(' ');
+            }
+        }
+
+        self.line.push_str(text);
+
+        if is_close(token) {
+            self.open.pop();
+        } else if let Some(bracket) = self.bracket(token) {
+            self.open.push(Open {
+                bracket,
+                indent: self.indent,
+                multiline,
+            });
+        }
+
+        self.previous_unary =
+            matches!(token, Token::Minus | Token::Plus) && !self.previous.is_some_and(ends_value);
+        self.previous = Some(token);
+    }
+
+    /// Whether a token starts a new line, which is decided by the tokens alone and not by where
+    /// the author broke lines.
+    fn breaks_before(&self, token: &Token) -> bool {
+        let Some(previous) = self.previous else {
+            return false;
+        };
+
+        let Some(open) = self.open.last() else {
+            return starts_statement(previous, token);
+        };
+
+        // blocks that aren't empty and brackets with an item on each line
+        let spread = open.bracket == Bracket::Block || open.multiline;
+
+        if is_close(token) {
+            spread && !is_open(previous)
+        } else if is_open(previous) {
+            spread
+        } else if open.bracket == Bracket::Block {
+            starts_statement(previous, token)
+        } else {
+            open.multiline && previous == &Token::Comma
+        }
+    }
+
+    fn bracket(&self, token: &Token) -> Option<Bracket> {
+        Some(match token {
+            Token::LParen => Bracket::Paren,
+            Token::LBracket => Bracket::List,
+            // a brace right after a value or one of these keywords starts a block, anywhere
+            // else it starts a dictionary
+            Token::LBrace => match self.previous {
+                Some(Token::Else | Token::Try | Token::Catch | Token::Finally | Token::Arrow) => {
+                    Bracket::Block
+                }
+                Some(previous) if ends_value(previous) => Bracket::Block,
+                _ => Bracket::Dict,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Whether there is a space between two tokens on the same line.
+    fn spaced(&self, previous: &Token, next: &Token) -> bool {
+        if self.previous_unary
+            || matches!(
+                previous,
+                Token::LParen | Token::LBracket | Token::Dot | Token::DotDotDot | Token::Hash
+            )
+        {
+            return false;
+        }
+
+        match (previous, next) {
+            (
+                _,
+                Token::Comma
+                | Token::Semicolon
+                | Token::RParen
+                | Token::RBracket
+                | Token::Dot
+                | Token::Colon,
+            ) => false,
+            (Token::DotDot | Token::DotDotEqual, _) | (_, Token::DotDot | Token::DotDotEqual) => {
+                false
+            }
+            // blocks never share a line with their statements, and dictionaries aren't padded
+            (Token::LBrace, _) | (_, Token::RBrace) => false,
+            // calls and indexing
+            (previous, Token::LParen | Token::LBracket) => {
+                !ends_value(previous) && previous != &Token::Func
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Finds the brackets that the author broke a line or put a comment right inside of, indexed
+/// like the tokens.
+fn multiline_brackets(tokens: &[TriviaToken]) -> Vec<bool> {
+    let mut multiline = vec![false; tokens.len()];
+    let mut open = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        if let Some(&top) = open.last() {
+            multiline[top] |= !token.leading.is_empty();
+        }
+
+        if is_close(&token.token) {
+            open.pop();
+        } else if is_open(&token.token) {
+            open.push(index);
+        }
+    }
+
+    multiline
+}
+
+// whether two tokens at the top of a block are in different statements, which is the case when
+// the first one ends a statement or a value that the second one can't continue
+fn starts_statement(previous: &Token, next: &Token) -> bool {
+    matches!(previous, Token::Semicolon | Token::Break | Token::Continue)
+        || ends_value(previous)
+            && matches!(
+                next,
+                Token::Number(_)
+                    | Token::Identifier(_)
+                    | Token::UnverifiedString(_)
+                    | Token::True
+                    | Token::False
+                    | Token::Void
+                    | Token::Not
+                    | Token::If
+                    | Token::While
+                    | Token::For
+                    | Token::Return
+                    | Token::Break
+                    | Token::Continue
+                    | Token::Try
+                    | Token::Throw
+                    | Token::Import
+                    | Token::Func
+                    | Token::Struct
+            )
+}
+
+fn is_open(token: &Token) -> bool {
+    matches!(token, Token::LParen | Token::LBracket | Token::LBrace)
+}
+
+fn is_close(token: &Token) -> bool {
+    matches!(token, Token::RParen | Token::RBracket | Token::RBrace)
+}
+
+// whether a token can be the last token of a value, which decides if a `-` after it is binary
+// and if a `(` or `[` after it is a call or an index
+fn ends_value(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Number(_)
+            | Token::Identifier(_)
+            | Token::UnverifiedString(_)
+            | Token::True
+            | Token::False
+            | Token::Void
+            | Token::RParen
+            | Token::RBracket
+            | Token::RBrace
+    )
+}
@@ -0,0 +1,71 @@
+use crate::bytecode::FunctionProto;
+use crate::context::Context;
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::node::Node;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+pub type NativeFn = fn(&Interpreter, Vec<Value>) -> InterpreterResult;
+
+/// A function defined in a glass script, along with the context it was defined in so that it
+/// can read the variables around it when it is called.
+pub struct UserFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Rc<Node>,
+    // the variable of each slot a call needs, the first of which are the parameters
+    pub locals: Rc<[String]>,
+    pub closure: Rc<RefCell<Context>>,
+    // the source the body was parsed from, which its errors point into
+    pub src: Rc<str>,
+    pub filename: Rc<str>,
+}
+
+/// A function compiled to bytecode, which is run by the VM when called.
+pub struct CompiledFunction {
+    pub proto: Rc<FunctionProto>,
+    pub closure: Rc<RefCell<Context>>,
+    pub src: Rc<str>,
+    pub filename: Rc<str>,
+}
+
+/// A function implemented in Rust. An `arity` of `None` means any number of arguments is
+/// accepted.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: Option<usize>,
+    pub func: NativeFn,
+}
+
+pub enum Function {
+    User(UserFunction),
+    Compiled(CompiledFunction),
+    Native(NativeFunction),
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match self {
+            Function::User(function) => &function.name,
+            Function::Compiled(function) => &function.proto.name,
+            Function::Native(function) => function.name,
+        }
+    }
+}
+
+// the closure can (and usually will) contain the function itself, so it can't be printed
+impl Debug for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Function::User(function) => write!(f, "UserFunction({})", function.name),
+            Function::Compiled(function) => {
+                write!(f, "CompiledFunction({})", function.proto.name)
+            }
+            Function::Native(function) => write!(f, "NativeFunction({})", function.name),
+        }
+    }
+}
@@ -1,82 +1,905 @@
+use crate::builtins;
 use crate::context::Context;
-use crate::error::GlassError;
+use crate::debugger::{self, Debugger};
+use crate::dict::Dict;
+use crate::error::{line_and_column, GlassError};
+use crate::function::{Function, UserFunction};
 use crate::lexer::Token;
-use crate::node::Node;
+use crate::methods;
+use crate::node::{Node, Slot};
+use crate::profiler::Profiler;
+use crate::sandbox;
+use crate::structs::{Struct, StructDef};
 use crate::value::Value;
+use crate::vm;
+use log::debug;
+use logos::Span;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
+/// A statement that stops the normal flow of execution. It is set by the corresponding node and
+/// taken by the function call or loop that handles it, while every block in between stops
+/// executing statements as long as it is set.
+pub enum ControlFlow {
+    Return(Value),
+    Break,
+    Continue,
+}
+
 pub struct Interpreter {
     src: Rc<str>,
     filename: Rc<str>,
-    context: Context, // todo: implement context
+    context: Rc<RefCell<Context>>,
+    control_flow: RefCell<Option<ControlFlow>>,
+    // print every node as it is evaluated, see `trace`
+    tracing: bool,
+    // told about every statement before it is run
+    debugger: Option<Rc<RefCell<dyn Debugger>>>,
+    // the number of function calls this interpreter is inside of
+    depth: usize,
+    // times every function call and statement, see `Profiler`
+    profiler: Option<Rc<RefCell<Profiler>>>,
 }
 
 pub type InterpreterResult = Result<Value, GlassError>;
 
 impl Interpreter {
-    pub fn new(src: Rc<str>, filename: Rc<str>) -> Self {
+    pub fn with_context(src: Rc<str>, filename: Rc<str>, context: Rc<RefCell<Context>>) -> Self {
         Self {
             src,
             filename,
-            context: Context::new(),
+            context,
+            control_flow: RefCell::new(None),
+            tracing: false,
+            debugger: None,
+            depth: 0,
+            profiler: None,
+        }
+    }
+
+    pub fn with_debugger(mut self, debugger: Rc<RefCell<dyn Debugger>>) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    pub fn with_profiler(mut self, profiler: Rc<RefCell<Profiler>>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Writes what the script prints, which goes through the debugger when there is one.
+    pub fn write_output(&self, text: &str) -> Result<(), GlassError> {
+        match &self.debugger {
+            Some(debugger) => debugger.borrow_mut().output(text),
+            None => debugger::write_stdout(text),
+        }
+    }
+
+    /// Makes the interpreter print every node it evaluates to stderr, along with its result.
+    pub fn with_tracing(mut self, tracing: bool) -> Self {
+        self.tracing = tracing;
+        self
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracing
+    }
+
+    /// Prints a node that was just evaluated, where it is and what it evaluated to.
+    pub fn trace(&self, node: &Node, result: &InterpreterResult) {
+        let location = match node.span() {
+            Some(span) if self.src.get(span.clone()).is_some() => {
+                let (line, column) = line_and_column(&self.src, span.start);
+                format!("{}:{}:{}", self.filename, line, column)
+            }
+            _ => format!("{}", self.filename),
+        };
+
+        match result {
+            Ok(value) => eprintln!("[trace] {} {} -> {}", location, node.name(), value.repr()),
+            Err(err) => eprintln!(
+                "[trace] {} {} -> error: {}",
+                location,
+                node.name(),
+                err.kind()
+            ),
         }
     }
 
-    pub fn visit_node(&self, node: Node) -> InterpreterResult {
-        node.visit(self)
+    pub fn context(&self) -> &Rc<RefCell<Context>> {
+        &self.context
+    }
+
+    pub fn src(&self) -> &Rc<str> {
+        &self.src
+    }
+
+    pub fn filename(&self) -> &Rc<str> {
+        &self.filename
+    }
+
+    /// Points an error raised by an operation at where the operation is in the source. An error
+    /// that already points somewhere, like one raised inside of a called function, is kept as is.
+    pub fn locate(&self, error: GlassError, span: &Span) -> GlassError {
+        match error {
+            GlassError::Spanned { .. } | GlassError::Exit { .. } => error,
+            // a compiled file can be run without the source it was compiled from
+            error if self.src.get(span.clone()).is_none() => error,
+            error => GlassError::Spanned {
+                error: Box::new(error),
+                src: Rc::clone(&self.src),
+                filename: Rc::clone(&self.filename),
+                span: span.clone(),
+            },
+        }
+    }
+
+    /// Creates the error for a value thrown by a script. A thrown string becomes the message of
+    /// an error value, and an error value without a span gets the span of the throw.
+    pub fn throw(&self, value: Value, span: Option<&Span>) -> GlassError {
+        let position = match span {
+            Some(span) if self.src.get(span.clone()).is_some() => position(&self.src, span),
+            _ => Value::Void,
+        };
+
+        let value = match value {
+            Value::Str(message) => builtins::error_value(message, "Error", position),
+            Value::Struct(instance) => {
+                {
+                    let mut error = instance.borrow_mut();
+
+                    if Rc::ptr_eq(&error.def, &builtins::error_struct())
+                        && matches!(error.values[2], Value::Void)
+                    {
+                        error.values[2] = position;
+                    }
+                }
+
+                Value::Struct(instance)
+            }
+            value => value,
+        };
+
+        let error = GlassError::Thrown { value };
+
+        match span {
+            Some(span) => self.locate(error, span),
+            None => error,
+        }
+    }
+
+    /// Turns an error into the value a catch block receives. Runtime errors become error values
+    /// with their message, kind and the `(line, column)` they happened at.
+    pub fn caught_value(error: GlassError) -> Value {
+        let (error, span) = match error {
+            GlassError::Spanned {
+                error, src, span, ..
+            } => (*error, position(&src, &span)),
+            error => (error, Value::Void),
+        };
+
+        match error {
+            GlassError::Thrown { value } => value,
+            error => builtins::error_value(error.to_string(), error.kind(), span),
+        }
+    }
+
+    pub fn visit_node(&self, node: &Node) -> InterpreterResult {
+        let result = node.visit(self)?;
+
+        match self.control_flow.take() {
+            Some(ControlFlow::Break) => Err(GlassError::ControlFlowOutsideLoop {
+                statement: "break".into(),
+            }),
+            Some(ControlFlow::Continue) => Err(GlassError::ControlFlowOutsideLoop {
+                statement: "continue".into(),
+            }),
+            // a return at the top level just stops the script
+            Some(ControlFlow::Return(value)) => Ok(value),
+            None => Ok(result),
+        }
+    }
+
+    /// Reads a variable from the first of its slots that has been assigned, falling back to
+    /// looking it up by name.
+    pub fn visit_identifier_node(&self, name: &str, slots: &[Slot]) -> InterpreterResult {
+        let context = self.context.borrow();
+
+        for slot in slots {
+            if let Some(value) = context.get_slot(*slot) {
+                return Ok(value);
+            }
+        }
+
+        builtins::check_variable(name)?;
+
+        match context.get(name) {
+            Some(value) => Ok(value),
+            None => Err(GlassError::UndefinedVariable { name: name.into() }),
+        }
+    }
+
+    /// Assigns a variable in the current context, to its slot if it has one.
+    fn set_variable(&self, name: &str, slot: Option<usize>, value: Value) {
+        match slot {
+            Some(index) => self.context.borrow_mut().set_slot(index, value),
+            None => self.context.borrow_mut().set(name, value),
+        }
     }
 
     pub fn visit_bin_op_node(
         &self,
         op: &Token,
-        left: &Box<Node>,
-        right: &Box<Node>,
+        left: &Node,
+        right: &Node,
+        span: &Span,
     ) -> InterpreterResult {
+        let left = left.visit(self)?;
+
+        // and/or short circuit, but still need a boolean on the right if they don't
+        match (op, &left) {
+            (Token::And, Value::Bool(false)) => return Ok(Value::Bool(false)),
+            (Token::Or, Value::Bool(true)) => return Ok(Value::Bool(true)),
+            _ => {}
+        }
+
+        let right = right.visit(self)?;
+
+        Self::apply_bin_op(op, left, right).map_err(|err| self.locate(err, span))
+    }
+
+    fn apply_bin_op(op: &Token, left: Value, right: Value) -> InterpreterResult {
         match op {
-            Token::Plus => left.visit(self)?.add(right.visit(self)?),
-            Token::Minus => left.visit(self)?.sub(right.visit(self)?),
-            Token::Star => left.visit(self)?.mul(right.visit(self)?),
-            Token::Slash => left.visit(self)?.div(right.visit(self)?),
-            Token::Percent => left.visit(self)?.rem(right.visit(self)?),
-            Token::StarStar => left.visit(self)?.pow(right.visit(self)?),
-            Token::EqualEqual => left.visit(self)?.eq(right.visit(self)?),
-            Token::ExclamationEqual => left.visit(self)?.ne(right.visit(self)?),
-            Token::LessThan => left.visit(self)?.lt(right.visit(self)?),
-            Token::GreaterThan => left.visit(self)?.gt(right.visit(self)?),
-            Token::LessThanEqual => left.visit(self)?.le(right.visit(self)?),
-            Token::GreaterThanEqual => left.visit(self)?.ge(right.visit(self)?),
-            Token::And => left.visit(self)?.and(right.visit(self)?),
-            Token::Or => left.visit(self)?.or(right.visit(self)?),
+            Token::Plus => left.add(right),
+            Token::Minus => left.sub(right),
+            Token::Star => left.mul(right),
+            Token::Slash => left.div(right),
+            Token::Percent => left.rem(right),
+            Token::StarStar => left.pow(right),
+            Token::EqualEqual => left.eq(right),
+            Token::ExclamationEqual => left.ne(right),
+            Token::LessThan => left.lt(right),
+            Token::GreaterThan => left.gt(right),
+            Token::LessThanEqual => left.le(right),
+            Token::GreaterThanEqual => left.ge(right),
+            Token::And => left.and(right),
+            Token::Or => left.or(right),
             _ => Err(GlassError::UnknownError {
                 error_message: "Parsed invalid binary operation expression".into(),
             }),
         }
     }
 
-    pub fn visit_unary_op_node(&self, op: &Token, right: &Box<Node>) -> InterpreterResult {
+    pub fn visit_unary_op_node(&self, op: &Token, right: &Node, span: &Span) -> InterpreterResult {
+        let right = right.visit(self)?;
+
         match op {
-            Token::Minus => right.visit(self)?.neg(),
-            Token::Not => right.visit(self)?.not(),
-            Token::Plus => right.visit(self),
+            Token::Minus => right.neg(),
+            Token::Not => right.not(),
+            Token::Plus => Ok(right),
             _ => Err(GlassError::UnknownError {
                 error_message: "Parsed invalid unary expression".to_string(),
             }),
         }
+        .map_err(|err| self.locate(err, span))
     }
 
-    pub fn visit_block_node(&self, statements: &Vec<Node>) -> InterpreterResult {
-        for statement in statements {
-            statement.visit(self)?;
+    pub fn visit_assignment_node(
+        &self,
+        op: &Token,
+        left: &Node,
+        right: &Node,
+    ) -> InterpreterResult {
+        // compound assignments apply the operator they start with
+        let bin_op = match op {
+            Token::Equal => None,
+            Token::PlusEqual => Some(Token::Plus),
+            Token::MinusEqual => Some(Token::Minus),
+            Token::StarEqual => Some(Token::Star),
+            Token::SlashEqual => Some(Token::Slash),
+            Token::PercentEqual => Some(Token::Percent),
+            Token::StarStarEqual => Some(Token::StarStar),
+            _ => {
+                return Err(GlassError::UnknownError {
+                    error_message: "Parsed invalid assignment operator".into(),
+                })
+            }
+        };
+
+        match left {
+            Node::Identifier { name, slots, .. } => {
+                let value = match bin_op {
+                    Some(bin_op) => Self::apply_bin_op(
+                        &bin_op,
+                        self.visit_identifier_node(name, slots)?,
+                        right.visit(self)?,
+                    )?,
+                    None => right.visit(self)?,
+                };
+
+                // assignments are always local, so a slot in the current call is the only one
+                // that can be assigned to
+                let slot = slots
+                    .first()
+                    .filter(|slot| slot.depth == 0)
+                    .map(|slot| slot.index);
+
+                self.set_variable(name, slot, value);
+            }
+            Node::Index { target, index, .. } => {
+                let target = target.visit(self)?;
+                let index = index.visit(self)?;
+
+                let value = match bin_op {
+                    Some(bin_op) => Self::apply_bin_op(
+                        &bin_op,
+                        target.clone().index(index.clone())?,
+                        right.visit(self)?,
+                    )?,
+                    None => right.visit(self)?,
+                };
+
+                target.set_index(index, value)?;
+            }
+            Node::FieldAccess { target, field, .. } => {
+                let target = target.visit(self)?;
+
+                let value = match bin_op {
+                    Some(bin_op) => {
+                        Self::apply_bin_op(&bin_op, target.get_field(field)?, right.visit(self)?)?
+                    }
+                    None => right.visit(self)?,
+                };
+
+                target.set_field(field, value)?;
+            }
+            _ => {
+                return Err(GlassError::UnknownError {
+                    error_message: "Parsed invalid assignment target".into(),
+                })
+            }
         }
 
         Ok(Value::Void)
     }
 
-    pub fn new_child_context(&self, name: &str) -> Self {
-        Self {
+    pub fn visit_list_node(&self, items: &[Node]) -> InterpreterResult {
+        let mut list = Vec::with_capacity(items.len());
+
+        for item in items {
+            list.push(item.visit(self)?);
+        }
+
+        Value::checked_list(list)
+    }
+
+    pub fn visit_tuple_node(&self, items: &[Node]) -> InterpreterResult {
+        let mut tuple = Vec::with_capacity(items.len());
+
+        for item in items {
+            tuple.push(item.visit(self)?);
+        }
+
+        sandbox::check_items(tuple.len())?;
+        Ok(Value::Tuple(tuple))
+    }
+
+    pub fn visit_dict_node(&self, entries: &[(Node, Node)]) -> InterpreterResult {
+        let mut dict = Dict::new();
+
+        for (key, value) in entries {
+            let key = key.visit(self)?;
+            dict.insert(&key, value.visit(self)?)?;
+        }
+
+        Ok(Value::dict(dict))
+    }
+
+    pub fn visit_index_node(&self, target: &Node, index: &Node, span: &Span) -> InterpreterResult {
+        let target = target.visit(self)?;
+        let index = index.visit(self)?;
+
+        target.index(index).map_err(|err| self.locate(err, span))
+    }
+
+    pub fn visit_function_definition_node(
+        &self,
+        name: &str,
+        signature: &[String],
+        body: &Rc<Node>,
+        locals: &Rc<[String]>,
+    ) -> InterpreterResult {
+        Ok(Value::Func(Rc::new(Function::User(UserFunction {
+            name: name.into(),
+            params: signature.to_vec(),
+            body: Rc::clone(body),
+            locals: Rc::clone(locals),
+            closure: Rc::clone(&self.context),
             src: Rc::clone(&self.src),
             filename: Rc::clone(&self.filename),
-            context: self.context.new_child(name),
+        }))))
+    }
+
+    pub fn visit_function_call_node(
+        &self,
+        function: &Node,
+        args: &[Node],
+        span: &Span,
+    ) -> InterpreterResult {
+        let function = function.visit(self)?;
+        let mut values = Vec::with_capacity(args.len());
+
+        for arg in args {
+            values.push(arg.visit(self)?);
+        }
+
+        self.call_function(&function, values)
+            .map_err(|err| self.locate(err, span))
+    }
+
+    pub fn call_function(&self, function: &Value, args: Vec<Value>) -> InterpreterResult {
+        let function = match function {
+            Value::Func(function) => function,
+            Value::StructDef(def) => return Self::construct_struct(def, args),
+            value => {
+                return Err(GlassError::NotCallable {
+                    type_name: value.get_type(),
+                })
+            }
+        };
+
+        let Some(profiler) = &self.profiler else {
+            return self.call(function, args);
+        };
+
+        profiler.borrow_mut().enter_function(function.name());
+        let result = self.call(function, args);
+        profiler.borrow_mut().exit_function();
+
+        result
+    }
+
+    fn call(&self, function: &Function, args: Vec<Value>) -> InterpreterResult {
+        match function {
+            Function::Native(native) => {
+                if let Some(arity) = native.arity {
+                    if args.len() != arity {
+                        return Err(GlassError::ArgumentCount {
+                            function: native.name.into(),
+                            expected: arity,
+                            found: args.len(),
+                        });
+                    }
+                }
+
+                (native.func)(self, args)
+            }
+            Function::User(user) => {
+                sandbox::check_depth(self.depth + 1)?;
+
+                if args.len() != user.params.len() {
+                    return Err(GlassError::ArgumentCount {
+                        function: user.name.clone(),
+                        expected: user.params.len(),
+                        found: args.len(),
+                    });
+                }
+
+                let child = self.new_call_context(
+                    &user.name,
+                    Rc::clone(&user.closure),
+                    &user.locals,
+                    &user.src,
+                    &user.filename,
+                );
+
+                // the parameters are the first slots
+                for (index, arg) in args.into_iter().enumerate() {
+                    child.context.borrow_mut().set_slot(index, arg);
+                }
+
+                debug!("Calling {}", child.context.borrow().stack_trace());
+
+                user.body.visit(&child)?;
+
+                match child.control_flow.take() {
+                    Some(ControlFlow::Return(value)) => Ok(value),
+                    Some(ControlFlow::Break) => Err(GlassError::ControlFlowOutsideLoop {
+                        statement: "break".into(),
+                    }),
+                    Some(ControlFlow::Continue) => Err(GlassError::ControlFlowOutsideLoop {
+                        statement: "continue".into(),
+                    }),
+                    None => Ok(Value::Void),
+                }
+            }
+            Function::Compiled(compiled) => {
+                let proto = &compiled.proto;
+                sandbox::check_depth(self.depth + 1)?;
+
+                if args.len() != proto.params.len() {
+                    return Err(GlassError::ArgumentCount {
+                        function: proto.name.clone(),
+                        expected: proto.params.len(),
+                        found: args.len(),
+                    });
+                }
+
+                let child = self.new_call_context(
+                    &proto.name,
+                    Rc::clone(&compiled.closure),
+                    &proto.locals,
+                    &compiled.src,
+                    &compiled.filename,
+                );
+
+                // the parameters are the first slots
+                for (index, arg) in args.into_iter().enumerate() {
+                    child.context.borrow_mut().set_slot(index, arg);
+                }
+
+                debug!("Calling {}", child.context.borrow().stack_trace());
+
+                vm::run(&child, &proto.chunk)
+            }
+        }
+    }
+
+    pub fn visit_field_access_node(
+        &self,
+        target: &Node,
+        field: &str,
+        span: &Span,
+    ) -> InterpreterResult {
+        target
+            .visit(self)?
+            .get_field(field)
+            .map_err(|err| self.locate(err, span))
+    }
+
+    pub fn visit_struct_definition_node(
+        &self,
+        name: &str,
+        slot: Option<usize>,
+        fields: &[String],
+        methods: &[(String, Node)],
+    ) -> InterpreterResult {
+        let mut method_values = HashMap::with_capacity(methods.len());
+
+        for (method, function) in methods {
+            method_values.insert(method.clone(), function.visit(self)?);
+        }
+
+        let def = StructDef {
+            name: name.into(),
+            fields: fields.to_vec(),
+            methods: method_values,
+            read_only: false,
+        };
+
+        self.set_variable(name, slot, Value::StructDef(Rc::new(def)));
+
+        Ok(Value::Void)
+    }
+
+    pub fn visit_method_call_node(
+        &self,
+        target: &Node,
+        method: &str,
+        args: &[Node],
+        span: &Span,
+    ) -> InterpreterResult {
+        let target = target.visit(self)?;
+        let mut values = Vec::with_capacity(args.len());
+
+        for arg in args {
+            values.push(arg.visit(self)?);
+        }
+
+        self.call_method(target, method, values)
+            .map_err(|err| self.locate(err, span))
+    }
+
+    fn construct_struct(def: &Rc<StructDef>, args: Vec<Value>) -> InterpreterResult {
+        if args.len() != def.fields.len() {
+            return Err(GlassError::ArgumentCount {
+                function: def.name.clone(),
+                expected: def.fields.len(),
+                found: args.len(),
+            });
+        }
+
+        Ok(Value::Struct(Rc::new(RefCell::new(Struct {
+            def: Rc::clone(def),
+            values: args,
+        }))))
+    }
+
+    pub fn call_method(&self, target: Value, method: &str, args: Vec<Value>) -> InterpreterResult {
+        match &target {
+            Value::Struct(instance) => {
+                let def = Rc::clone(&instance.borrow().def);
+
+                // methods get the instance they were called on as their first argument, self
+                if let Some(function) = def.methods.get(method) {
+                    let mut method_args = Vec::with_capacity(args.len() + 1);
+                    method_args.push(target.clone());
+                    method_args.extend(args);
+
+                    return self.call_function(function, method_args);
+                }
+
+                // a field holding a function can be called like a method, but without self
+                let field = instance.borrow().get_field(method);
+
+                if let Some(field) = field {
+                    return self.call_function(&field, args);
+                }
+            }
+            Value::StructDef(def) => {
+                if let Some(function) = def.methods.get(method) {
+                    return self.call_function(function, args);
+                }
+            }
+            _ => {}
+        }
+
+        let found = match methods::find_method(&target, method) {
+            Some(found) => found,
+            None => {
+                return Err(GlassError::NoSuchMethod {
+                    method: method.into(),
+                    type_name: target.get_type(),
+                })
+            }
+        };
+
+        if let Some(arity) = found.arity {
+            if args.len() != arity {
+                return Err(GlassError::ArgumentCount {
+                    function: format!("{}.{}", target.get_type(), method),
+                    expected: arity,
+                    found: args.len(),
+                });
+            }
+        }
+
+        (found.func)(self, target, args)
+    }
+
+    pub fn visit_return_node(&self, value: &Node) -> InterpreterResult {
+        let value = value.visit(self)?;
+        self.control_flow.replace(Some(ControlFlow::Return(value)));
+
+        Ok(Value::Void)
+    }
+
+    pub fn visit_break_node(&self) -> InterpreterResult {
+        self.control_flow.replace(Some(ControlFlow::Break));
+        Ok(Value::Void)
+    }
+
+    pub fn visit_continue_node(&self) -> InterpreterResult {
+        self.control_flow.replace(Some(ControlFlow::Continue));
+        Ok(Value::Void)
+    }
+
+    pub fn visit_throw_node(&self, value: &Node, span: &Span) -> InterpreterResult {
+        let value = value.visit(self)?;
+
+        Err(self.throw(value, Some(span)))
+    }
+
+    /// Runs the body, handing any error it raises to the catch block. The finally block runs
+    /// however the others finish, and a return, break or continue in it replaces whatever they
+    /// were doing, including raising an error.
+    pub fn visit_try_node(
+        &self,
+        body: &Node,
+        variable: Option<&str>,
+        slot: Option<usize>,
+        catch_body: Option<&Node>,
+        finally_body: Option<&Node>,
+    ) -> InterpreterResult {
+        let result = match (body.visit(self), catch_body) {
+            (Err(error), Some(catch_body)) if error.is_catchable() => {
+                if let Some(variable) = variable {
+                    self.set_variable(variable, slot, Self::caught_value(error));
+                }
+
+                catch_body.visit(self)
+            }
+            (result, _) => result,
+        };
+
+        if let Some(finally_body) = finally_body {
+            let control_flow = self.control_flow.take();
+            finally_body.visit(self)?;
+
+            if self.control_flow.borrow().is_some() {
+                return Ok(Value::Void);
+            }
+
+            self.control_flow.replace(control_flow);
         }
+
+        result.map(|_| Value::Void)
+    }
+
+    fn visit_condition(&self, condition: &Node) -> Result<bool, GlassError> {
+        match condition.visit(self)? {
+            Value::Bool(value) => Ok(value),
+            value => Err(GlassError::InvalidCondition {
+                condition_type: value.get_type(),
+            }),
+        }
+    }
+
+    pub fn visit_if_node(
+        &self,
+        condition: &Node,
+        body: &Node,
+        else_body: Option<&Node>,
+    ) -> InterpreterResult {
+        if self.visit_condition(condition)? {
+            body.visit(self)?;
+        } else if let Some(else_body) = else_body {
+            else_body.visit(self)?;
+        }
+
+        Ok(Value::Void)
+    }
+
+    /// Handles the control flow set by the body of a loop, returning whether the loop should
+    /// stop. A return is left in place so that it keeps unwinding to the function call.
+    fn finish_iteration(&self) -> bool {
+        let mut control_flow = self.control_flow.borrow_mut();
+
+        match control_flow.take() {
+            Some(ControlFlow::Break) => true,
+            Some(ControlFlow::Continue) | None => false,
+            Some(ControlFlow::Return(value)) => {
+                *control_flow = Some(ControlFlow::Return(value));
+                true
+            }
+        }
+    }
+
+    pub fn visit_while_node(&self, condition: &Node, body: &Node) -> InterpreterResult {
+        while self.visit_condition(condition)? {
+            body.visit(self)?;
+
+            if self.finish_iteration() {
+                break;
+            }
+        }
+
+        Ok(Value::Void)
     }
+
+    pub fn visit_for_node(
+        &self,
+        variable: &str,
+        slot: Option<usize>,
+        start: &Node,
+        end: &Node,
+        inclusive: bool,
+        body: &Node,
+    ) -> InterpreterResult {
+        let (start, end) = match (start.visit(self)?, end.visit(self)?) {
+            (Value::Num(start), Value::Num(end)) => (start, end),
+            (start, end) => {
+                return Err(GlassError::InvalidOperation {
+                    operation: if inclusive { "..=" } else { ".." }.into(),
+                    left: start.get_type(),
+                    right: end.get_type(),
+                })
+            }
+        };
+
+        let mut i = start;
+
+        while i < end || (inclusive && i == end) {
+            self.set_variable(variable, slot, Value::Num(i));
+            body.visit(self)?;
+
+            if self.finish_iteration() {
+                break;
+            }
+
+            i += 1.0;
+        }
+
+        Ok(Value::Void)
+    }
+
+    pub fn visit_for_each_node(
+        &self,
+        variable: &str,
+        slot: Option<usize>,
+        iterable: &Node,
+        body: &Node,
+    ) -> InterpreterResult {
+        let items = iterable.visit(self)?.into_items()?;
+
+        for item in items {
+            self.set_variable(variable, slot, item);
+            body.visit(self)?;
+
+            if self.finish_iteration() {
+                break;
+            }
+        }
+
+        Ok(Value::Void)
+    }
+
+    /// Runs each statement in order, stopping early if one of them changes the control flow.
+    /// The value of the last statement is the value of the block, which is what the REPL prints.
+    pub fn visit_block_node(&self, statements: &[Node]) -> InterpreterResult {
+        let mut result = Value::Void;
+
+        for statement in statements {
+            if let Some(debugger) = &self.debugger {
+                debugger.borrow_mut().before_statement(self, statement)?;
+            }
+
+            result = match &self.profiler {
+                Some(profiler) => {
+                    profiler
+                        .borrow_mut()
+                        .enter_statement(&self.src, &self.filename, statement);
+                    let result = statement.visit(self);
+                    profiler.borrow_mut().exit_statement();
+
+                    result?
+                }
+                None => statement.visit(self)?,
+            };
+
+            if self.control_flow.borrow().is_some() {
+                return Ok(Value::Void);
+            }
+        }
+
+        Ok(result)
+    }
+
+    // a call runs in the source its function was defined in, so that its errors point there
+    fn new_call_context(
+        &self,
+        name: &str,
+        parent: Rc<RefCell<Context>>,
+        slots: &Rc<[String]>,
+        src: &Rc<str>,
+        filename: &Rc<str>,
+    ) -> Self {
+        Self {
+            debugger: self.debugger.clone(),
+            depth: self.depth + 1,
+            profiler: self.profiler.clone(),
+            ..Self::with_context(
+                Rc::clone(src),
+                Rc::clone(filename),
+                Rc::new(RefCell::new(Context::new_child(
+                    parent,
+                    name,
+                    Rc::clone(slots),
+                ))),
+            )
+            .with_tracing(self.tracing)
+        }
+    }
+}
+
+// the (line, column) of a span, as the span field of an error value
+fn position(src: &str, span: &Span) -> Value {
+    let (line, column) = line_and_column(src, span.start);
+
+    Value::Tuple(vec![Value::Num(line as f64), Value::Num(column as f64)])
 }
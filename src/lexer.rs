@@ -1,4 +1,4 @@
-use logos::{Lexer, Logos};
+use logos::{Lexer, Logos, Span};
 use snailquote::unescape;
 
 fn lex_string(lex: &mut Lexer<Token>) -> Result<String, String> {
@@ -120,6 +120,18 @@ pub enum Token {
     #[token("continue")]
     Continue,
 
+    #[token("try")]
+    Try,
+
+    #[token("catch")]
+    Catch,
+
+    #[token("finally")]
+    Finally,
+
+    #[token("throw")]
+    Throw,
+
     #[token("import")]
     Import,
 
@@ -138,9 +150,15 @@ pub enum Token {
     #[token("func")]
     Func,
 
+    #[token("struct")]
+    Struct,
+
     #[token("=>")]
     Arrow,
 
+    #[token("->")] // return type
+    ThinArrow,
+
     #[token("(")]
     LParen,
 
@@ -165,6 +183,9 @@ pub enum Token {
     #[token(",")]
     Comma,
 
+    #[token(":")]
+    Colon,
+
     #[token(".")]
     Dot,
 
@@ -188,6 +209,92 @@ pub enum Token {
     Error,
 }
 
+/// Something between two tokens that the parser skips, but the formatter has to keep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    Comment(String),
+    // the number of line breaks in a row, so a blank line is 2
+    Newlines(usize),
+}
+
+/// A token along with the trivia in front of it.
+#[derive(Debug)]
+pub struct TriviaToken {
+    pub token: Token,
+    pub span: Span,
+    pub leading: Vec<Trivia>,
+}
+
+/// Lexes a script without losing its comments and line breaks. The trivia after the last token
+/// is returned separately. Only spaces and tabs are lost, which is everything the formatter
+/// decides for itself.
+pub fn lex_with_trivia(src: &str) -> (Vec<TriviaToken>, Vec<Trivia>) {
+    let mut tokens = Vec::new();
+    let mut end = 0;
+
+    for (token, span) in Token::lexer(src).spanned() {
+        tokens.push(TriviaToken {
+            token,
+            leading: trivia(&src[end..span.start]),
+            span: span.clone(),
+        });
+        end = span.end;
+    }
+
+    (tokens, trivia(&src[end..]))
+}
+
+// the text between two tokens only ever holds whitespace and comments
+fn trivia(gap: &str) -> Vec<Trivia> {
+    let mut trivia = Vec::new();
+    let mut rest = gap;
+
+    while let Some(char) = rest.chars().next() {
+        if rest.starts_with("//") {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            trivia.push(Trivia::Comment(rest[..end].trim_end().into()));
+            rest = &rest[end..];
+            continue;
+        }
+
+        if char == '\n' {
+            match trivia.last_mut() {
+                Some(Trivia::Newlines(count)) => *count += 1,
+                _ => trivia.push(Trivia::Newlines(1)),
+            }
+        }
+
+        rest = &rest[char.len_utf8()..];
+    }
+
+    trivia
+}
+
+/// Every keyword that can be used, for completion. `import` and `match` are reserved but not
+/// implemented yet, so they aren't included.
+pub const KEYWORDS: &[Token] = &[
+    Token::Not,
+    Token::And,
+    Token::Or,
+    Token::If,
+    Token::Else,
+    Token::While,
+    Token::For,
+    Token::In,
+    Token::Return,
+    Token::Break,
+    Token::Continue,
+    Token::Try,
+    Token::Catch,
+    Token::Finally,
+    Token::Throw,
+    Token::True,
+    Token::False,
+    Token::Void,
+    Token::Func,
+    Token::Struct,
+];
+
 // todo: there has to be a better way to do this
 impl Token {
     pub fn get_rep(&self) -> &str {
@@ -225,13 +332,19 @@ impl Token {
             Token::Return => "return",
             Token::Break => "break",
             Token::Continue => "continue",
+            Token::Try => "try",
+            Token::Catch => "catch",
+            Token::Finally => "finally",
+            Token::Throw => "throw",
             Token::Import => "import",
             Token::Match => "match",
             Token::True => "true",
             Token::False => "false",
             Token::Void => "void",
             Token::Func => "func",
+            Token::Struct => "struct",
             Token::Arrow => "=>",
+            Token::ThinArrow => "->",
             Token::LParen => "(",
             Token::RParen => ")",
             Token::LBrace => "{",
@@ -240,6 +353,7 @@ impl Token {
             Token::RBracket => "]",
             Token::Semicolon => ";",
             Token::Comma => ",",
+            Token::Colon => ":",
             Token::Dot => ".",
             Token::DotDot => "..",
             Token::DotDotDot => "...",
@@ -0,0 +1,570 @@
+//! `glass lsp`, a language server that editors talk to over stdin and stdout.
+//!
+//! Every time a document changes it is parsed, resolved and type checked like `glass check`
+//! would, and requests about it are answered from what was found then. Documents are always
+//! sent whole, and positions are counted in UTF-16 code units like the protocol expects.
+
+use crate::analyze_source;
+use crate::builtins;
+use crate::checker::Checker;
+use crate::context::Context;
+use crate::error::GlassError;
+use crate::function::Function;
+use crate::lexer::{Token, KEYWORDS};
+use crate::node::Node;
+use crate::value::Value;
+use line_span::find_line_start;
+use logos::Span;
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const COMPLETION_STRUCT: u8 = 22;
+
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+const SYMBOL_STRUCT: u8 = 23;
+
+/// Serves requests until the client asks the server to exit or closes stdin.
+pub fn run() -> Result<(), GlassError> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match serde_json::from_str(&body) {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": err.to_string() },
+            })],
+        };
+
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+
+        if server.exited {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the body of the next message, or `None` once the input is closed. The debug adapter
+/// frames its messages the same way.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<String>, GlassError> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header).map_err(io_error)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| GlassError::UnknownError {
+        error_message: "Message without a Content-Length header".into(),
+    })?;
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(io_error)?;
+
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> Result<(), GlassError> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body).map_err(io_error)?;
+    output.flush().map_err(io_error)
+}
+
+fn io_error(err: io::Error) -> GlassError {
+    GlassError::UnknownError {
+        error_message: err.to_string(),
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    exited: bool,
+}
+
+impl Server {
+    /// Handles a message, returning the response to it (if it's a request) and any
+    /// notifications to send along with it.
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "glass", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => Json::Null,
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                return vec![self.update(uri, text)];
+            }
+            "textDocument/didChange" => {
+                // the whole document is sent on every change, so only the last one matters
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                    .unwrap_or_default();
+
+                return vec![self.update(uri, text)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+            "textDocument/hover" => self.with_offset(uri, params, Document::hover),
+            "textDocument/definition" => self.with_offset(uri, params, Document::definition),
+            "textDocument/documentSymbol" => match self.documents.get(uri) {
+                Some(document) => document.symbols(),
+                None => Json::Null,
+            },
+            "textDocument/completion" => match self.documents.get(uri) {
+                Some(document) => document.completions(),
+                None => Json::Null,
+            },
+            // any other notification can be ignored
+            _ if message.get("id").is_none() => return Vec::new(),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("Unknown method '{}'", method),
+                    },
+                })]
+            }
+        };
+
+        match message.get("id") {
+            Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            None => Vec::new(),
+        }
+    }
+
+    fn update(&mut self, uri: &str, text: &str) -> Json {
+        let previous = self.documents.remove(uri);
+        let document = Document::analyze(uri, text, previous);
+        let diagnostics = document.diagnostics.clone();
+
+        self.documents.insert(uri.into(), document);
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    fn with_offset(
+        &self,
+        uri: &str,
+        params: &Json,
+        answer: impl Fn(&Document, usize) -> Json,
+    ) -> Json {
+        self.documents
+            .get(uri)
+            .and_then(|document| {
+                let offset = offset(&document.src, &params["position"])?;
+                Some(answer(document, offset))
+            })
+            .unwrap_or(Json::Null)
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// An open document, and everything found out about it the last time it changed.
+struct Document {
+    uri: String,
+    // the source everything below is about. while the document can't be parsed, this is the
+    // last version that could be, so that hovering and the rest keep working while it is edited
+    src: Rc<str>,
+    // the diagnostics of the latest version, which are sent as soon as it is analyzed
+    diagnostics: Vec<Json>,
+    // the type of every variable where it is named, see `Analysis::types`
+    types: Vec<(Span, String)>,
+    definitions: Definitions,
+    // the variables and structs defined at the top level, in order
+    top_level: Vec<(String, Span, u8)>,
+}
+
+impl Document {
+    /// Analyzes the latest version of a document, falling back to what was found out about the
+    /// `previous` version if it can't be parsed.
+    fn analyze(uri: &str, text: &str, previous: Option<Document>) -> Self {
+        let src: Rc<str> = Rc::from(text);
+        let filename: Rc<str> = Rc::from(uri);
+        let context = Rc::new(RefCell::new(Context::new()));
+
+        let mut document = Document {
+            uri: uri.into(),
+            src: Rc::clone(&src),
+            diagnostics: Vec::new(),
+            types: Vec::new(),
+            definitions: Definitions::new(),
+            top_level: Vec::new(),
+        };
+
+        let (ast, resolved) = match analyze_source(Rc::clone(&src), Rc::clone(&filename), &context)
+        {
+            Ok(analyzed) => analyzed,
+            Err(error) => {
+                document.diagnose(&error, SEVERITY_ERROR);
+
+                return match previous {
+                    Some(previous) => Document {
+                        diagnostics: document.diagnostics,
+                        ..previous
+                    },
+                    None => document,
+                };
+            }
+        };
+
+        // a script that can't be resolved can still be checked, since every error is found
+        for error in &resolved.errors {
+            document.diagnose(error, SEVERITY_ERROR);
+        }
+
+        for warning in &resolved.warnings {
+            document.diagnose(warning, SEVERITY_WARNING);
+        }
+
+        let analysis = Checker::new(src, filename).analyze(&ast, &context.borrow());
+
+        for error in &analysis.errors {
+            document.diagnose(error, SEVERITY_ERROR);
+        }
+
+        document.types = analysis.types;
+        document.definitions.collect(&ast);
+        document.top_level = top_level(&ast);
+
+        document
+    }
+
+    fn diagnose(&mut self, error: &GlassError, severity: u8) {
+        // errors without a span, like reaching the end of the script too early, are put at
+        // the end
+        let span = error
+            .span()
+            .cloned()
+            .unwrap_or(self.src.len()..self.src.len());
+
+        self.diagnostics.push(json!({
+            "range": range(&self.src, &span),
+            "severity": severity,
+            "code": error.kind(),
+            "source": "glass",
+            "message": error.message(),
+        }));
+    }
+
+    fn hover(&self, offset: usize) -> Json {
+        // the innermost span is the one the cursor is on
+        let found = self
+            .types
+            .iter()
+            .filter(|(span, _)| contains(span, offset))
+            .min_by_key(|(span, _)| span.len());
+
+        match found {
+            Some((span, ty)) => json!({
+                "contents": {
+                    "kind": "plaintext",
+                    "value": format!("{}: {}", &self.src[span.clone()], ty),
+                },
+                "range": range(&self.src, span),
+            }),
+            None => Json::Null,
+        }
+    }
+
+    fn definition(&self, offset: usize) -> Json {
+        match self.definitions.find(offset) {
+            Some(span) => json!({
+                "uri": self.uri,
+                "range": range(&self.src, span),
+            }),
+            None => Json::Null,
+        }
+    }
+
+    fn symbols(&self) -> Json {
+        self.top_level
+            .iter()
+            .map(|(name, span, kind)| {
+                json!({
+                    "name": name,
+                    "kind": kind,
+                    "range": range(&self.src, span),
+                    "selectionRange": range(&self.src, span),
+                })
+            })
+            .collect()
+    }
+
+    fn completions(&self) -> Json {
+        let mut items = Vec::new();
+
+        let mut builtins: Vec<_> = builtins::get_builtins().into_iter().collect();
+        builtins.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (name, value) in builtins {
+            let kind = match value {
+                Value::Func(function) => match function.as_ref() {
+                    Function::Native(_) => COMPLETION_FUNCTION,
+                    _ => COMPLETION_VARIABLE,
+                },
+                Value::StructDef(_) => COMPLETION_STRUCT,
+                _ => COMPLETION_VARIABLE,
+            };
+
+            items.push(json!({ "label": name, "kind": kind, "detail": "builtin" }));
+        }
+
+        for keyword in KEYWORDS {
+            items.push(json!({ "label": keyword.get_rep(), "kind": COMPLETION_KEYWORD }));
+        }
+
+        for (name, _, kind) in &self.top_level {
+            let kind = match *kind {
+                SYMBOL_FUNCTION => COMPLETION_FUNCTION,
+                SYMBOL_STRUCT => COMPLETION_STRUCT,
+                _ => COMPLETION_VARIABLE,
+            };
+
+            items.push(json!({ "label": name, "kind": kind }));
+        }
+
+        Json::Array(items)
+    }
+}
+
+/// Where every variable is defined and used. Like in the resolver, every function has its own
+/// scope, a variable is defined by the first assignment to it in a scope, and a variable that is
+/// used is looked for in the scopes around it, innermost first.
+struct Definitions {
+    // the first definition of every variable, by scope
+    scopes: Vec<HashMap<String, Span>>,
+    // the scopes being collected, innermost last
+    stack: Vec<usize>,
+    // every name of a variable, with the scopes it could be defined in
+    uses: Vec<(Span, String, Vec<usize>)>,
+}
+
+impl Definitions {
+    fn new() -> Self {
+        // the top level of the script is scope 0
+        Self {
+            scopes: vec![HashMap::new()],
+            stack: vec![0],
+            uses: Vec::new(),
+        }
+    }
+
+    fn collect(&mut self, node: &Node) {
+        match node {
+            Node::Identifier { name, span, .. } => {
+                let scopes = self.stack.iter().rev().copied().collect();
+                self.uses.push((span.clone(), name.clone(), scopes));
+            }
+            Node::Assignment {
+                op: Token::Equal,
+                left,
+                ..
+            } => {
+                if let Node::Identifier { name, span, .. } = &**left {
+                    self.define(name, span);
+                }
+            }
+            Node::FunctionDefinition {
+                signature,
+                param_spans,
+                body,
+                ..
+            } => {
+                self.stack.push(self.scopes.len());
+                self.scopes.push(HashMap::new());
+
+                for (param, span) in signature.iter().zip(param_spans) {
+                    self.define(param, span);
+                }
+
+                self.collect(body);
+                self.stack.pop();
+
+                return;
+            }
+            Node::StructDefinition { name, span, .. } => self.define(name, span),
+            Node::For {
+                variable,
+                variable_span,
+                ..
+            }
+            | Node::ForEach {
+                variable,
+                variable_span,
+                ..
+            } => self.define(variable, variable_span),
+            Node::Try {
+                variable: Some(variable),
+                variable_span: Some(variable_span),
+                ..
+            } => self.define(variable, variable_span),
+            _ => {}
+        }
+
+        for child in node.children() {
+            self.collect(child);
+        }
+    }
+
+    fn define(&mut self, name: &str, span: &Span) {
+        let scope = *self.stack.last().expect("Definitions has no scope");
+
+        self.scopes[scope]
+            .entry(name.into())
+            .or_insert_with(|| span.clone());
+    }
+
+    /// The definition of the variable named at `offset`.
+    fn find(&self, offset: usize) -> Option<&Span> {
+        let (_, name, scopes) = self.uses.iter().find(|(span, ..)| contains(span, offset))?;
+
+        scopes
+            .iter()
+            .find_map(|&scope| self.scopes[scope].get(name))
+    }
+}
+
+/// The variables and structs defined at the top level of a script, with their kind of symbol.
+fn top_level(ast: &Node) -> Vec<(String, Span, u8)> {
+    let statements = match ast {
+        Node::Block { statements } => statements.iter().collect(),
+        node => vec![node],
+    };
+
+    let mut symbols: Vec<(String, Span, u8)> = Vec::new();
+
+    for statement in statements {
+        let (name, span, kind) = match statement {
+            Node::Assignment {
+                op: Token::Equal,
+                left,
+                right,
+                ..
+            } => match &**left {
+                Node::Identifier { name, span, .. } => {
+                    let kind = match **right {
+                        Node::FunctionDefinition { .. } => SYMBOL_FUNCTION,
+                        _ => SYMBOL_VARIABLE,
+                    };
+
+                    (name, span, kind)
+                }
+                _ => continue,
+            },
+            Node::StructDefinition { name, span, .. } => (name, span, SYMBOL_STRUCT),
+            _ => continue,
+        };
+
+        if !symbols.iter().any(|(defined, ..)| defined == name) {
+            symbols.push((name.clone(), span.clone(), kind));
+        }
+    }
+
+    symbols
+}
+
+// the end of a span counts as in it, so that a name can be found with the cursor right after it
+fn contains(span: &Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+fn range(src: &str, span: &Span) -> Json {
+    json!({ "start": position(src, span.start), "end": position(src, span.end) })
+}
+
+fn position(src: &str, offset: usize) -> Json {
+    let start = find_line_start(src, offset);
+    let line = src[..offset].matches('\n').count();
+    let character: usize = src[start..offset].chars().map(char::len_utf16).sum();
+
+    json!({ "line": line, "character": character })
+}
+
+/// The byte offset of an LSP position, which is clamped to the end of its line.
+fn offset(src: &str, position: &Json) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+
+    let start = match line {
+        0 => 0,
+        line => src.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let mut units = 0;
+
+    for (index, char) in src[start..].char_indices() {
+        if units >= character || char == '\n' {
+            return Some(start + index);
+        }
+
+        units += char.len_utf16();
+    }
+
+    Some(src.len())
+}
@@ -1,79 +1,778 @@
+mod builtins;
+mod bytecode;
+mod checker;
+mod compiler;
 mod context;
+mod dap;
+mod debugger;
+mod dict;
 mod error;
+mod formatter;
+mod function;
 mod interpreter;
 mod lexer;
+mod lsp;
+mod math;
+mod methods;
 mod node;
+mod optimizer;
 mod parser;
+mod profiler;
+mod report;
+mod resolver;
+mod sandbox;
+mod serialize;
+mod structs;
+mod template;
+mod test_runner;
 mod value;
+mod vm;
 
-use crate::error::GlassError;
+use crate::bytecode::Chunk;
+use crate::checker::Checker;
+use crate::compiler::Compiler;
+use crate::context::Context;
+use crate::error::{line_and_column, GlassError, INTERNAL_ERROR_EXIT};
+use crate::formatter::FormatOptions;
 use crate::interpreter::Interpreter;
 use crate::lexer::Token;
+use crate::node::Node;
+use crate::optimizer::Optimizer;
 use crate::parser::Parser;
-use clap::Parser as ClapParser;
-use log::{debug, log_enabled, Level, LevelFilter};
+use crate::profiler::Profiler;
+use crate::report::ErrorFormat;
+use crate::resolver::{Resolved, Resolver};
+use crate::sandbox::{Capability, Limits};
+use crate::serialize::Header;
+use crate::value::Value;
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
+use log::{debug, info, log_enabled, Level, LevelFilter};
 use logos::{Logos, Span};
 use simplelog::SimpleLogger;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::RefCell;
 use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::{fs, panic};
+use std::time::Duration;
+use std::{fs, io, panic, process, thread};
+
+// the stack is only reserved up front, and isn't used until a script recurses that deep
+const MAIN_STACK_SIZE: usize = 256 * 1024 * 1024;
 
 #[derive(ClapParser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    #[clap(help = "The script file to run", index = 1)]
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(
+        help = "The script file to run, a compiled .glassc file, or - to read the script from stdin",
+        index = 1
+    )]
     file: Option<PathBuf>,
 
-    #[clap(short = 'd', long = "debug", help = "Enable debug mode")]
+    #[clap(
+        help = "Arguments passed to the script, which it reads from the args list. Put them after -- when the first one is also the name of a command",
+        index = 2,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+
+    #[clap(
+        short = 'e',
+        long = "eval",
+        help = "Run the given code instead of a script file"
+    )]
+    eval: Option<String>,
+
+    #[clap(short = 'd', long = "debug", global = true, help = "Enable debug mode")]
     debug: bool,
 
-    #[clap(short = 'v', long = "verbose", help = "Enable verbose mode")]
+    #[clap(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        help = "Log which files are read and compiled"
+    )]
     verbose: bool,
+
+    #[clap(
+        long = "trace",
+        conflicts_with = "vm",
+        help = "Print every node of the script as it is evaluated, with its span and value"
+    )]
+    trace: bool,
+
+    #[clap(
+        long = "vm",
+        help = "Compile the script to bytecode and run it on the VM instead of walking the AST"
+    )]
+    vm: bool,
+
+    #[clap(
+        long = "profile",
+        conflicts_with = "vm",
+        help = "Time every function and line of the script, printing the slowest to stderr when it finishes"
+    )]
+    profile: bool,
+
+    #[clap(
+        long = "profile-output",
+        requires = "profile",
+        help = "Also write the profile to this file as collapsed stacks for flamegraph tools"
+    )]
+    profile_output: Option<PathBuf>,
+
+    #[clap(
+        long = "error-format",
+        value_enum,
+        global = true,
+        default_value_t = ErrorFormat::Text,
+        help = "How to print errors and warnings, json prints an object per line for tools to read"
+    )]
+    error_format: ErrorFormat,
+
+    #[clap(
+        long = "max-steps",
+        global = true,
+        help = "Stop the script after it has evaluated this many nodes, or run this many instructions on the VM"
+    )]
+    max_steps: Option<u64>,
+
+    #[clap(
+        long = "timeout",
+        value_name = "MS",
+        global = true,
+        help = "Stop the script after it has run for this many milliseconds"
+    )]
+    timeout: Option<u64>,
+
+    #[clap(
+        long = "max-memory",
+        value_name = "BYTES",
+        global = true,
+        help = "The most bytes the strings, lists and dictionaries a script makes can take up in total"
+    )]
+    max_memory: Option<usize>,
+
+    #[clap(
+        long = "max-depth",
+        global = true,
+        help = "The most function calls that can be nested inside of each other [default: 1000]"
+    )]
+    max_depth: Option<usize>,
+
+    #[clap(
+        long = "deny",
+        value_enum,
+        value_delimiter = ',',
+        global = true,
+        help = "Make the builtins that need these capabilities fail"
+    )]
+    deny: Vec<Capability>,
+
+    #[clap(
+        long = "emit",
+        value_enum,
+        help = "Print the output of a phase of the script instead of running it"
+    )]
+    emit: Option<Emit>,
+
+    #[clap(
+        long = "dump-ast",
+        conflicts_with = "emit",
+        help = "Print the optimized AST of the script instead of running it, like --emit ast"
+    )]
+    dump_ast: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Emit {
+    /// The tokens the script is made of, with their line and column
+    Tokens,
+    /// The optimized and resolved AST
+    Ast,
+    /// The optimized and resolved AST as JSON
+    AstJson,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[clap(about = "Compile a script to bytecode without running it")]
+    Compile {
+        #[clap(help = "The script file to compile")]
+        file: PathBuf,
+
+        #[clap(
+            short = 'o',
+            long = "output",
+            help = "Where to write the compiled file, defaults to the script with a .glassc extension"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[clap(about = "Check a script for type errors without running it")]
+    Check {
+        #[clap(help = "The script file to check")]
+        file: PathBuf,
+    },
+
+    #[clap(about = "Start a debug adapter that communicates over stdin and stdout")]
+    Dap,
+
+    #[clap(about = "Run a script in a debugger that pauses it at breakpoints or step by step")]
+    Debug {
+        #[clap(help = "The script file to debug")]
+        file: PathBuf,
+
+        #[clap(
+            help = "Arguments passed to the script",
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        args: Vec<String>,
+    },
+
+    #[clap(about = "Format scripts in the canonical style")]
+    Fmt {
+        #[clap(
+            help = "The scripts to format, or directories to search for .glass files",
+            default_value = "."
+        )]
+        paths: Vec<PathBuf>,
+
+        #[clap(
+            long = "check",
+            help = "Only list the scripts that aren't formatted, without changing them"
+        )]
+        check: bool,
+
+        #[clap(
+            long = "indent",
+            default_value_t = 4,
+            help = "The number of spaces to indent by"
+        )]
+        indent: usize,
+    },
+
+    #[clap(about = "Start a language server that communicates over stdin and stdout")]
+    Lsp,
+
+    #[clap(about = "Run the test_* functions in scripts")]
+    Test {
+        #[clap(
+            help = "The scripts to test, or directories to search for .glass files",
+            default_value = "."
+        )]
+        paths: Vec<PathBuf>,
+    },
 }
 
 fn main() {
-    panic::set_hook(Box::new(|err| {
-        eprintln!(
-            "{}",
-            GlassError::UnknownError {
-                error_message: err.to_string(),
-            }
-        );
+    let args = Args::parse();
+
+    panic::set_hook(Box::new(|info| {
+        report::fatal(&GlassError::UnknownError {
+            error_message: info.to_string(),
+        });
+
+        // only captured when RUST_BACKTRACE is set
+        let backtrace = Backtrace::capture();
+
+        if backtrace.status() == BacktraceStatus::Captured {
+            eprintln!("{}", backtrace);
+        }
+
+        process::exit(INTERNAL_ERROR_EXIT);
     }));
 
-    if let Err(err) = try_main() {
-        eprintln!("Fatal exception during execution -> {}", err);
+    // calls recurse on the native stack, so the script runs on a thread with a stack that is
+    // big enough for the default recursion limit, even in a debug build
+    let main = thread::Builder::new()
+        .stack_size(MAIN_STACK_SIZE)
+        .spawn(move || run(args))
+        .expect("Failed to start the main thread");
+
+    // a panic has already been reported and exited by the hook
+    let _ = main.join();
+}
+
+// the formats and limits are per thread, so they are set on the thread the script runs on
+fn run(args: Args) {
+    report::set_format(args.error_format);
+    sandbox::set_limits(Limits {
+        max_steps: args.max_steps,
+        timeout: args.timeout.map(Duration::from_millis),
+        max_memory: args.max_memory,
+        max_depth: args.max_depth,
+        denied: args.deny.clone(),
+    });
+
+    if let Err(err) = try_main(args) {
+        match err {
+            // these have already been reported, but whatever ran the command needs to know
+            GlassError::TestsFailed { .. }
+            | GlassError::Unformatted { .. }
+            | GlassError::Exit { .. } => {}
+            ref err => report::fatal(err),
+        }
+
+        let _ = io::stdout().flush();
+        process::exit(err.exit_code());
     }
 }
 
-fn try_main() -> Result<(), GlassError> {
-    let args = Args::parse();
-    setup_logger(args.debug)?;
+fn try_main(args: Args) -> Result<(), GlassError> {
+    // the logger writes to stdout, which the language server and debug adapter use to talk to
+    // the editor
+    if !matches!(args.command, Some(Command::Lsp | Command::Dap)) {
+        setup_logger(args.debug, args.verbose)?;
+    }
 
-    match args.file {
-        Some(file) => run_script(file),
-        None => Err(GlassError::PlaceholderError {
-            message: "REPL not implemented yet".into(),
-        }),
+    let emit = args.emit.or(args.dump_ast.then_some(Emit::Ast));
+
+    match (args.command, args.file) {
+        (Some(Command::Compile { file, output }), _) => {
+            let output = output.unwrap_or_else(|| file.with_extension("glassc"));
+            compile_script(&file, &output).map(|_| ())
+        }
+        (Some(Command::Check { file }), _) => check_script(&file),
+        (Some(Command::Dap), _) => dap::run(),
+        (Some(Command::Debug { file, args }), _) => debugger::run_debugger(&file, args),
+        (
+            Some(Command::Fmt {
+                paths,
+                check,
+                indent,
+            }),
+            _,
+        ) => format_scripts(
+            &paths,
+            check,
+            &FormatOptions {
+                indent_width: indent,
+            },
+        ),
+        (Some(Command::Lsp), _) => lsp::run(),
+        (Some(Command::Test { paths }), _) => test_runner::run_tests(&paths),
+        (None, None) if args.eval.is_none() => run_repl(),
+        (None, file) => {
+            // a compiled file has no source to emit, so it can only be run
+            if let (Some(file), None, None) = (&file, &args.eval, emit) {
+                if file.extension().is_some_and(|ext| ext == "glassc") {
+                    return run_compiled(file, args.args);
+                }
+            }
+
+            let script = resolve_source(args.eval, file, args.args)?;
+
+            match emit {
+                Some(emit) => emit_phase(script.src, script.filename, emit),
+                None => {
+                    let options = RunOptions {
+                        use_vm: args.vm,
+                        trace: args.trace,
+                        profile: args.profile,
+                        profile_output: args.profile_output,
+                    };
+
+                    run_source(script.src, script.filename, script.args, &options)
+                }
+            }
+        }
+    }
+}
+
+fn run_repl() -> Result<(), GlassError> {
+    let filename: Rc<str> = Rc::from("<stdin>");
+    let stdin = io::stdin();
+    // shared between lines so that variables are kept around
+    let context = Rc::new(RefCell::new(Context::new()));
+
+    loop {
+        print!("> ");
+
+        if let Err(err) = io::stdout().flush() {
+            return Err(GlassError::UnknownError {
+                error_message: err.to_string(),
+            });
+        }
+
+        let mut line = String::new();
+
+        match stdin.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                return Err(GlassError::UnknownError {
+                    error_message: err.to_string(),
+                })
+            }
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match eval_source(
+            Rc::from(line),
+            Rc::clone(&filename),
+            Rc::clone(&context),
+            &RunOptions::default(),
+        ) {
+            Ok(Value::Void) => {}
+            Ok(result) => println!("{}", result.repr()),
+            Err(err @ GlassError::Exit { .. }) => return Err(err),
+            Err(err) => eprintln!("{}", err),
+        }
     }
+
+    Ok(())
+}
+
+/// How to run a script, from the options it was given on the command line.
+#[derive(Default)]
+struct RunOptions {
+    use_vm: bool,
+    trace: bool,
+    profile: bool,
+    // where to write the collapsed stacks to when profiling, if anywhere
+    profile_output: Option<PathBuf>,
+}
+
+/// The code of a script given on the command line, with the arguments passed to it.
+struct Script {
+    src: Rc<str>,
+    filename: Rc<str>,
+    args: Vec<String>,
 }
 
-fn run_script(file: PathBuf) -> Result<(), GlassError> {
+/// Reads the code from `-e`, stdin when the file is `-`, or the file. With `-e` there's no
+/// script file, so every argument is passed to the code.
+fn resolve_source(
+    eval: Option<String>,
+    file: Option<PathBuf>,
+    args: Vec<String>,
+) -> Result<Script, GlassError> {
+    if let Some(eval) = eval {
+        return Ok(Script {
+            src: Rc::from(eval),
+            filename: Rc::from("<eval>"),
+            args: file
+                .map(|file| file.to_string_lossy().to_string())
+                .into_iter()
+                .chain(args)
+                .collect(),
+        });
+    }
+
+    let file = file.unwrap_or_default();
+
+    if file.as_os_str() == "-" {
+        let mut src = String::new();
+
+        if let Err(err) = io::stdin().read_to_string(&mut src) {
+            return Err(GlassError::UnknownError {
+                error_message: err.to_string(),
+            });
+        }
+
+        return Ok(Script {
+            src: Rc::from(src),
+            filename: Rc::from("<stdin>"),
+            args,
+        });
+    }
+
     // todo: stop using Rc!!!
     let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+    let src = read_source(&file, &filename)?;
+
+    Ok(Script {
+        src,
+        filename,
+        args,
+    })
+}
+
+fn run_source(
+    src: Rc<str>,
+    filename: Rc<str>,
+    script_args: Vec<String>,
+    options: &RunOptions,
+) -> Result<(), GlassError> {
+    let context = script_context(script_args);
+    let result = eval_source(src, filename, context, options)?;
+
+    debug!("Result > {}", result.repr());
+
+    Ok(())
+}
+
+/// A context for running a script, where `args` holds the arguments it was given.
+fn script_context(script_args: Vec<String>) -> Rc<RefCell<Context>> {
+    let mut context = Context::new();
+    let script_args = script_args.into_iter().map(Value::Str).collect();
+    context.set("args", Value::list(script_args));
+
+    Rc::new(RefCell::new(context))
+}
+
+/// Prints what a phase of the pipeline makes of a script, without running it.
+fn emit_phase(src: Rc<str>, filename: Rc<str>, emit: Emit) -> Result<(), GlassError> {
+    if let Emit::Tokens = emit {
+        for (token, span) in Token::lexer(&src).spanned() {
+            let (line, column) = line_and_column(&src, span.start);
+            println!("{}:{} {:?}", line, column, token);
+        }
+
+        return Ok(());
+    }
+
+    let context = Rc::new(RefCell::new(Context::new()));
+    let ast = parse_source(src, filename, &context)?;
+
+    match emit {
+        Emit::AstJson => println!("{:#}", ast.to_json()),
+        _ => println!("{:#?}", ast),
+    }
+
+    Ok(())
+}
+
+/// Reports every type error in a script without running it.
+fn check_script(file: &Path) -> Result<(), GlassError> {
+    let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+    let src = read_source(file, &filename)?;
+
+    let context = Rc::new(RefCell::new(Context::new()));
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+    let errors = Checker::new(src, filename).check(&ast, &context.borrow());
+
+    for error in &errors {
+        report::error(error);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(GlassError::TypeCheckFailed {
+            errors: errors.len(),
+        })
+    }
+}
+
+/// Formats scripts in place, or with `check` only lists the ones that would change.
+fn format_scripts(
+    paths: &[PathBuf],
+    check: bool,
+    options: &FormatOptions,
+) -> Result<(), GlassError> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        collect_scripts(path, &mut files)?;
+    }
+
+    let mut unformatted = 0;
+
+    for file in &files {
+        let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+        let src = read_source(file, &filename)?;
+        let formatted = formatter::format(&src, Rc::clone(&filename), options)?;
+
+        if formatted == *src {
+            continue;
+        }
+
+        unformatted += 1;
+
+        if check {
+            println!("Would reformat {}", filename);
+        } else if let Err(err) = fs::write(file, formatted) {
+            return Err(GlassError::FileWriteError {
+                filename,
+                reason: err.to_string(),
+            });
+        } else {
+            println!("Formatted {}", filename);
+        }
+    }
+
+    if check && unformatted > 0 {
+        Err(GlassError::Unformatted { files: unformatted })
+    } else {
+        Ok(())
+    }
+}
+
+/// Runs a compiled file on the VM. If the source it was compiled from has changed since, or it
+/// was compiled by a different build of glass, it is recompiled from the source first.
+fn run_compiled(file: &Path, script_args: Vec<String>) -> Result<(), GlassError> {
+    let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+
+    let bytes = match fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(GlassError::FileNotFound { filename }),
+    };
+
+    let invalid = |reason| GlassError::InvalidBytecode {
+        filename: Rc::clone(&filename),
+        reason,
+    };
+
+    let header = serialize::read_header(&bytes).map_err(invalid)?;
+    let source_path = Path::new(&header.source_path);
+    let src: Option<Rc<str>> = fs::read_to_string(source_path).ok().map(Rc::from);
+
+    let chunk = match &src {
+        Some(src) if !header.is_current() || serialize::hash_source(src) != header.source_hash => {
+            info!(
+                "'{}' is out of date, recompiling it from '{}'",
+                file.display(),
+                source_path.display()
+            );
+
+            compile_script(source_path, file)?
+        }
+        _ if !header.is_current() => {
+            return Err(GlassError::IncompatibleBytecode {
+                filename,
+                version: format!("{} {}", header.glass_version, header.git_revision),
+            })
+        }
+        _ => serialize::deserialize(&bytes).map_err(invalid)?.1,
+    };
+
+    debug!("Bytecode >\n{}", chunk);
+
+    let interpreter = Interpreter::with_context(
+        src.unwrap_or_else(|| Rc::from("")),
+        Rc::from(header.source_path),
+        script_context(script_args),
+    );
+    // the VM only knows the source the chunk was compiled from, not the file it was loaded from
+    let result = vm::run(&interpreter, &chunk).map_err(|err| match err {
+        GlassError::InvalidBytecode { reason, .. } => invalid(reason),
+        err => err,
+    })?;
+
+    debug!("Result > {}", result.repr());
+
+    Ok(())
+}
+
+/// Compiles a script and writes it to `output`, returning the compiled chunk.
+fn compile_script(file: &Path, output: &Path) -> Result<Chunk, GlassError> {
+    let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+    let src = read_source(file, &filename)?;
+
+    let context = Rc::new(RefCell::new(Context::new()));
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+    let chunk = Compiler::compile(&ast)?;
 
-    let src: Rc<str> = Rc::from(match fs::read_to_string(&file) {
+    // the absolute path is stored so that the source can be found from wherever the output is run
+    let source_path = match fs::canonicalize(file) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => filename.to_string(),
+    };
+    let bytes = serialize::serialize(&Header::new(source_path, &src), &chunk);
+
+    if let Err(err) = fs::write(output, bytes) {
+        return Err(GlassError::FileWriteError {
+            filename: Rc::from(output.to_string_lossy().to_string()),
+            reason: err.to_string(),
+        });
+    }
+
+    info!("Compiled '{}' to '{}'", file.display(), output.display());
+
+    Ok(chunk)
+}
+
+/// Finds the scripts in a directory and the directories inside of it, in order. A file that is
+/// named directly is always included, whatever its extension.
+fn collect_scripts(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), GlassError> {
+    if !path.is_dir() {
+        if !path.exists() {
+            return Err(GlassError::FileNotFound {
+                filename: Rc::from(path.to_string_lossy().to_string()),
+            });
+        }
+
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            return Err(GlassError::FileNotFound {
+                filename: Rc::from(path.to_string_lossy().to_string()),
+            })
+        }
+    };
+
+    let mut entries: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_scripts(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "glass") {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_source(file: &Path, filename: &Rc<str>) -> Result<Rc<str>, GlassError> {
+    let src: Rc<str> = Rc::from(match fs::read_to_string(file) {
         Ok(src) => src,
         Err(_) => {
             return Err(GlassError::FileNotFound {
-                filename: Rc::clone(&filename),
+                filename: Rc::clone(filename),
             })
         }
     });
 
-    debug!("Read {} bytes from '{}'", &src.len(), &file.display());
+    info!("Read {} bytes from '{}'", &src.len(), &file.display());
 
+    Ok(src)
+}
+
+/// Parses, optimizes and resolves a script that is going to be run in `context`.
+fn parse_source(
+    src: Rc<str>,
+    filename: Rc<str>,
+    context: &Rc<RefCell<Context>>,
+) -> Result<Node, GlassError> {
+    let (ast, resolved) = analyze_source(src, filename, context)?;
+
+    if let Some(error) = resolved.errors.into_iter().next() {
+        return Err(error);
+    }
+
+    for warning in &resolved.warnings {
+        report::warning(warning);
+    }
+
+    Ok(ast)
+}
+
+/// Like `parse_source`, but returns every error found after the script was parsed and the
+/// warnings instead of stopping at the first error and printing the warnings.
+fn analyze_source(
+    src: Rc<str>,
+    filename: Rc<str>,
+    context: &Rc<RefCell<Context>>,
+) -> Result<(Node, Resolved), GlassError> {
     let tokens: VecDeque<(Token, Span)> = Token::lexer(&src).spanned().collect();
 
     if log_enabled!(Level::Debug) {
@@ -87,19 +786,67 @@ fn run_script(file: PathBuf) -> Result<(), GlassError> {
 
     debug!("AST > {:#?}", ast);
 
-    let interpreter = Interpreter::new(Rc::clone(&src), Rc::clone(&filename));
-    let result = interpreter.visit_node(ast)?;
+    let (mut ast, warnings) =
+        Optimizer::new(Rc::clone(&src), Rc::clone(&filename)).optimize(ast)?;
+    let mut resolved = Resolver::new(src, filename).resolve(&mut ast, context.borrow().names());
+    // the optimizer runs first, so its warnings come first
+    resolved.warnings.splice(0..0, warnings);
 
-    debug!("Result > {:?}", result);
+    debug!("Optimized AST > {:#?}", ast);
 
-    Ok(())
+    Ok((ast, resolved))
 }
 
-fn setup_logger(debug: bool) -> Result<(), GlassError> {
+fn eval_source(
+    src: Rc<str>,
+    filename: Rc<str>,
+    context: Rc<RefCell<Context>>,
+    options: &RunOptions,
+) -> Result<Value, GlassError> {
+    let ast = parse_source(Rc::clone(&src), Rc::clone(&filename), &context)?;
+    let interpreter = Interpreter::with_context(src, filename, context).with_tracing(options.trace);
+
+    if options.use_vm {
+        let chunk = Compiler::compile(&ast)?;
+
+        debug!("Bytecode >\n{}", chunk);
+
+        return vm::run(&interpreter, &chunk);
+    }
+
+    if !options.profile {
+        return interpreter.visit_node(&ast);
+    }
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    let result = interpreter
+        .with_profiler(Rc::clone(&profiler))
+        .visit_node(&ast);
+
+    // a script that fails is profiled up to where it failed
+    let mut profiler = profiler.borrow_mut();
+    profiler.finish();
+    eprint!("{}", profiler.report());
+
+    if let Some(output) = &options.profile_output {
+        if let Err(err) = fs::write(output, profiler.collapsed_stacks()) {
+            return Err(GlassError::FileWriteError {
+                filename: Rc::from(output.to_string_lossy().to_string()),
+                reason: err.to_string(),
+            });
+        }
+    }
+
+    result
+}
+
+fn setup_logger(debug: bool, verbose: bool) -> Result<(), GlassError> {
     let level = if debug {
         LevelFilter::Debug
-    } else {
+    } else if verbose {
         LevelFilter::Info
+    } else {
+        LevelFilter::Warn
     };
 
     match SimpleLogger::init(level, simplelog::Config::default()) {
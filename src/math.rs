@@ -0,0 +1,379 @@
+//! The `math` module, a builtin struct instance whose fields are the math functions and
+//! constants, so that they are used like `math.sqrt(2)` and `math.pi`. Its fields are read-only,
+//! so a script can't replace them for the code that uses them after it.
+//!
+//! A function that isn't defined for its arguments, like `math.sqrt(-1)`, raises a `DomainError`
+//! instead of returning NaN.
+
+use crate::error::GlassError;
+use crate::function::{Function, NativeFunction};
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::structs::{Struct, StructDef};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts;
+use std::rc::Rc;
+
+macro_rules! functions {
+    ($($name:literal => $func:ident($arity:expr)),* $(,)?) => {
+        &[$(NativeFunction { name: concat!("math.", $name), arity: $arity, func: $func }),*]
+    };
+}
+
+const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", consts::PI),
+    ("e", consts::E),
+    ("tau", consts::TAU),
+    ("inf", f64::INFINITY),
+];
+
+const FUNCTIONS: &[NativeFunction] = functions![
+    "sqrt" => sqrt(Some(1)),
+    "cbrt" => cbrt(Some(1)),
+    "pow" => pow(Some(2)),
+    "exp" => exp(Some(1)),
+    "log" => log(None),
+    "log2" => log2(Some(1)),
+    "log10" => log10(Some(1)),
+    "sin" => sin(Some(1)),
+    "cos" => cos(Some(1)),
+    "tan" => tan(Some(1)),
+    "asin" => asin(Some(1)),
+    "acos" => acos(Some(1)),
+    "atan" => atan(Some(1)),
+    "atan2" => atan2(Some(2)),
+    "hypot" => hypot(Some(2)),
+    "floor" => floor(Some(1)),
+    "ceil" => ceil(Some(1)),
+    "round" => round(Some(1)),
+    "trunc" => trunc(Some(1)),
+    "abs" => abs(Some(1)),
+    "sign" => sign(Some(1)),
+    "min" => min(None),
+    "max" => max(None),
+    "clamp" => clamp(Some(3)),
+    "gcd" => gcd(Some(2)),
+    "lcm" => lcm(Some(2)),
+];
+
+thread_local! {
+    // shared by every `math` module, so that it can be told apart from a script's own structs
+    static MODULE_DEF: Rc<StructDef> = Rc::new(StructDef {
+        name: "math".into(),
+        fields: CONSTANTS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain(FUNCTIONS.iter().map(|function| function.name["math.".len()..].to_string()))
+            .collect(),
+        methods: HashMap::new(),
+        read_only: true,
+    });
+}
+
+/// The struct the `math` module is an instance of.
+pub fn module_def() -> Rc<StructDef> {
+    MODULE_DEF.with(Rc::clone)
+}
+
+/// Creates the `math` module, with its constants followed by its functions.
+pub fn module() -> Value {
+    let values = CONSTANTS
+        .iter()
+        .map(|(_, value)| Value::Num(*value))
+        .chain(
+            FUNCTIONS
+                .iter()
+                .map(|function| Value::Func(Rc::new(Function::Native(function.clone())))),
+        )
+        .collect();
+
+    Value::Struct(Rc::new(RefCell::new(Struct {
+        def: module_def(),
+        values,
+    })))
+}
+
+fn expect_num(function: &str, value: &Value) -> Result<f64, GlassError> {
+    match value {
+        Value::Num(num) => Ok(*num),
+        value => Err(GlassError::InvalidArgument {
+            function: function.into(),
+            message: format!("expected 'number' but found '{}'", value.get_type()),
+        }),
+    }
+}
+
+fn expect_integer(function: &str, value: &Value) -> Result<f64, GlassError> {
+    let num = expect_num(function, value)?;
+
+    if num.fract() != 0.0 {
+        return Err(GlassError::InvalidArgument {
+            function: function.into(),
+            message: format!("expected a whole number but found {}", num),
+        });
+    }
+
+    Ok(num)
+}
+
+fn domain_error(function: &str, args: &[f64]) -> GlassError {
+    GlassError::DomainError {
+        function: function.into(),
+        arguments: args
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<_>>()
+            .join(" and "),
+    }
+}
+
+// the result of a function that only returns NaN outside of its domain, since NaN is never a
+// result scripts are meant to see
+fn checked(function: &str, args: &[f64], result: f64) -> InterpreterResult {
+    if result.is_nan() && !args.iter().any(|arg| arg.is_nan()) {
+        return Err(domain_error(function, args));
+    }
+
+    Ok(Value::Num(result))
+}
+
+fn unary(function: &str, args: &[Value], op: fn(f64) -> f64) -> InterpreterResult {
+    let x = expect_num(function, &args[0])?;
+    checked(function, &[x], op(x))
+}
+
+fn binary(function: &str, args: &[Value], op: fn(f64, f64) -> f64) -> InterpreterResult {
+    let x = expect_num(function, &args[0])?;
+    let y = expect_num(function, &args[1])?;
+    checked(function, &[x, y], op(x, y))
+}
+
+// logarithms go to negative infinity at 0 instead of returning NaN, but 0 isn't in their domain
+// either
+fn logarithm(function: &str, x: f64, op: impl Fn(f64) -> f64) -> InterpreterResult {
+    if x <= 0.0 {
+        return Err(domain_error(function, &[x]));
+    }
+
+    checked(function, &[x], op(x))
+}
+
+fn sqrt(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let x = expect_num("math.sqrt", &args[0])?;
+
+    if x < 0.0 {
+        return Err(domain_error("math.sqrt", &[x]));
+    }
+
+    checked("math.sqrt", &[x], x.sqrt())
+}
+
+fn cbrt(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.cbrt", &args, f64::cbrt)
+}
+
+// like the logarithms, 0 to a negative power goes to infinity instead of returning NaN, and a
+// negative number only has a real power if the exponent is whole
+fn pow(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let x = expect_num("math.pow", &args[0])?;
+    let y = expect_num("math.pow", &args[1])?;
+
+    if (x == 0.0 && y < 0.0) || (x < 0.0 && y.fract() != 0.0 && y.is_finite()) {
+        return Err(domain_error("math.pow", &[x, y]));
+    }
+
+    checked("math.pow", &[x, y], x.powf(y))
+}
+
+fn exp(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.exp", &args, f64::exp)
+}
+
+/// `math.log(x)` is the natural logarithm, `math.log(x, base)` is the logarithm in that base.
+fn log(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match &args[..] {
+        [x] => logarithm("math.log", expect_num("math.log", x)?, f64::ln),
+        [x, base] => {
+            let x = expect_num("math.log", x)?;
+            let base = expect_num("math.log", base)?;
+
+            if base <= 0.0 || base == 1.0 {
+                return Err(domain_error("math.log", &[x, base]));
+            }
+
+            logarithm("math.log", x, |x| x.log(base))
+        }
+        _ => Err(GlassError::InvalidArgument {
+            function: "math.log".into(),
+            message: format!(
+                "expected a number and an optional base but {} argument(s) were given",
+                args.len()
+            ),
+        }),
+    }
+}
+
+fn log2(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    logarithm("math.log2", expect_num("math.log2", &args[0])?, f64::log2)
+}
+
+fn log10(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    logarithm(
+        "math.log10",
+        expect_num("math.log10", &args[0])?,
+        f64::log10,
+    )
+}
+
+fn sin(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.sin", &args, f64::sin)
+}
+
+fn cos(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.cos", &args, f64::cos)
+}
+
+fn tan(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.tan", &args, f64::tan)
+}
+
+fn asin(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.asin", &args, f64::asin)
+}
+
+fn acos(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.acos", &args, f64::acos)
+}
+
+fn atan(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.atan", &args, f64::atan)
+}
+
+/// `math.atan2(y, x)`, the angle of the point (x, y).
+fn atan2(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    binary("math.atan2", &args, f64::atan2)
+}
+
+fn hypot(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    binary("math.hypot", &args, f64::hypot)
+}
+
+fn floor(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.floor", &args, f64::floor)
+}
+
+fn ceil(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.ceil", &args, f64::ceil)
+}
+
+/// Rounds half way values away from zero, like `number.round()`.
+fn round(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.round", &args, f64::round)
+}
+
+fn trunc(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.trunc", &args, f64::trunc)
+}
+
+fn abs(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary("math.abs", &args, f64::abs)
+}
+
+/// -1, 0 or 1, depending on whether the number is negative, zero or positive.
+fn sign(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    unary(
+        "math.sign",
+        &args,
+        |x| if x == 0.0 { 0.0 } else { x.signum() },
+    )
+}
+
+// the numbers of `math.min` and `math.max`, which are either the arguments or a single list
+fn numbers(function: &str, args: &[Value]) -> Result<Vec<f64>, GlassError> {
+    let numbers = match args {
+        [Value::List(list)] => list
+            .borrow()
+            .iter()
+            .map(|value| expect_num(function, value))
+            .collect::<Result<Vec<_>, _>>()?,
+        args => args
+            .iter()
+            .map(|value| expect_num(function, value))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    if numbers.is_empty() {
+        return Err(GlassError::InvalidArgument {
+            function: function.into(),
+            message: "expected at least one number".into(),
+        });
+    }
+
+    Ok(numbers)
+}
+
+/// `math.min(a, b, ...)` or `math.min(list)`
+fn min(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let numbers = numbers("math.min", &args)?;
+    Ok(Value::Num(
+        numbers.into_iter().fold(f64::INFINITY, f64::min),
+    ))
+}
+
+/// `math.max(a, b, ...)` or `math.max(list)`
+fn max(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let numbers = numbers("math.max", &args)?;
+    Ok(Value::Num(
+        numbers.into_iter().fold(f64::NEG_INFINITY, f64::max),
+    ))
+}
+
+/// `math.clamp(x, low, high)`, which is `x` moved into the range from `low` to `high`.
+fn clamp(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let x = expect_num("math.clamp", &args[0])?;
+    let low = expect_num("math.clamp", &args[1])?;
+    let high = expect_num("math.clamp", &args[2])?;
+
+    if low > high {
+        return Err(GlassError::InvalidArgument {
+            function: "math.clamp".into(),
+            message: format!(
+                "the low bound {} is greater than the high bound {}",
+                low, high
+            ),
+        });
+    }
+
+    Ok(Value::Num(x.clamp(low, high)))
+}
+
+fn gcd_of(mut a: f64, mut b: f64) -> f64 {
+    (a, b) = (a.abs(), b.abs());
+
+    while b != 0.0 {
+        (a, b) = (b, a % b);
+    }
+
+    a
+}
+
+/// The greatest common divisor of two whole numbers, which is never negative.
+fn gcd(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let a = expect_integer("math.gcd", &args[0])?;
+    let b = expect_integer("math.gcd", &args[1])?;
+
+    Ok(Value::Num(gcd_of(a, b)))
+}
+
+/// The least common multiple of two whole numbers, which is 0 if either of them is.
+fn lcm(_: &Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let a = expect_integer("math.lcm", &args[0])?;
+    let b = expect_integer("math.lcm", &args[1])?;
+
+    if a == 0.0 || b == 0.0 {
+        return Ok(Value::Num(0.0));
+    }
+
+    Ok(Value::Num((a / gcd_of(a, b) * b).abs()))
+}
@@ -0,0 +1,979 @@
+use crate::dict::Dict;
+use crate::error::GlassError;
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::sandbox;
+use crate::template;
+use crate::value::Value;
+use std::cmp::Ordering;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A method receives the value it was called on followed by the call's arguments.
+pub type MethodFn = fn(&Interpreter, Value, Vec<Value>) -> InterpreterResult;
+
+pub struct Method {
+    pub name: &'static str,
+    pub arity: Option<usize>,
+    pub func: MethodFn,
+}
+
+macro_rules! methods {
+    ($($name:literal => $func:ident($arity:expr)),* $(,)?) => {
+        &[$(Method { name: $name, arity: $arity, func: $func }),*]
+    };
+}
+
+const NUM_METHODS: &[Method] = methods![
+    "abs" => num_abs(Some(0)),
+    "floor" => num_floor(Some(0)),
+    "ceil" => num_ceil(Some(0)),
+    "round" => num_round(Some(0)),
+    "is_integer" => num_is_integer(Some(0)),
+];
+
+const STR_METHODS: &[Method] = methods![
+    "len" => str_len(Some(0)),
+    "upper" => str_upper(Some(0)),
+    "lower" => str_lower(Some(0)),
+    "trim" => str_trim(Some(0)),
+    "split" => str_split(Some(1)),
+    "join" => str_join(Some(1)),
+    "replace" => str_replace(Some(2)),
+    "find" => str_find(Some(1)),
+    "contains" => str_contains(Some(1)),
+    "starts_with" => str_starts_with(Some(1)),
+    "ends_with" => str_ends_with(Some(1)),
+    "chars" => str_chars(Some(0)),
+    "graphemes" => str_graphemes(Some(0)),
+    "pad_start" => str_pad_start(None),
+    "pad_end" => str_pad_end(None),
+    "format" => str_format(None),
+];
+
+const LIST_METHODS: &[Method] = methods![
+    "len" => list_len(Some(0)),
+    "push" => list_push(Some(1)),
+    "pop" => list_pop(Some(0)),
+    "insert" => list_insert(Some(2)),
+    "remove" => list_remove(Some(1)),
+    "extend" => list_extend(Some(1)),
+    "clear" => list_clear(Some(0)),
+    "find" => list_find(Some(1)),
+    "contains" => list_contains(Some(1)),
+    "join" => list_join(Some(1)),
+    "map" => list_map(Some(1)),
+    "filter" => list_filter(Some(1)),
+    "reduce" => list_reduce(None),
+    "any" => list_any(None),
+    "all" => list_all(None),
+    "sort" => list_sort(None),
+    "sort_by" => list_sort_by(Some(1)),
+    "reverse" => list_reverse(Some(0)),
+    "zip" => list_zip(None),
+    "enumerate" => list_enumerate(Some(0)),
+    "flatten" => list_flatten(Some(0)),
+    "unique" => list_unique(Some(0)),
+];
+
+const DICT_METHODS: &[Method] = methods![
+    "len" => dict_len(Some(0)),
+    "keys" => dict_keys(Some(0)),
+    "values" => dict_values(Some(0)),
+    "items" => dict_items(Some(0)),
+    "get" => dict_get(Some(2)),
+    "insert" => dict_insert(Some(2)),
+    "remove" => dict_remove(Some(1)),
+    "contains" => dict_contains(Some(1)),
+    "clear" => dict_clear(Some(0)),
+];
+
+const SET_METHODS: &[Method] = methods![
+    "len" => set_len(Some(0)),
+    "add" => set_add(Some(1)),
+    "remove" => set_remove(Some(1)),
+    "contains" => set_contains(Some(1)),
+    "clear" => set_clear(Some(0)),
+    "union" => set_union(Some(1)),
+    "intersection" => set_intersection(Some(1)),
+    "difference" => set_difference(Some(1)),
+    "is_subset" => set_is_subset(Some(1)),
+    "to_list" => set_to_list(Some(0)),
+];
+
+pub fn find_method(value: &Value, name: &str) -> Option<&'static Method> {
+    let methods = match value {
+        Value::Num(_) => NUM_METHODS,
+        Value::Str(_) => STR_METHODS,
+        Value::List(_) => LIST_METHODS,
+        Value::Dict(_) => DICT_METHODS,
+        Value::Set(_) => SET_METHODS,
+        _ => return None,
+    };
+
+    methods.iter().find(|method| method.name == name)
+}
+
+fn expect_num(method: &str, value: &Value) -> Result<f64, GlassError> {
+    match value {
+        Value::Num(num) => Ok(*num),
+        value => Err(GlassError::InvalidArgument {
+            function: method.into(),
+            message: format!("expected 'number' but found '{}'", value.get_type()),
+        }),
+    }
+}
+
+fn expect_str<'a>(method: &str, value: &'a Value) -> Result<&'a str, GlassError> {
+    match value {
+        Value::Str(str) => Ok(str),
+        value => Err(GlassError::InvalidArgument {
+            function: method.into(),
+            message: format!("expected 'string' but found '{}'", value.get_type()),
+        }),
+    }
+}
+
+/// Converts an index argument into a position in a sequence of length `len`. `len` itself is
+/// allowed when `allow_end` is set, which is used to insert at the end of a list.
+fn expect_position(
+    method: &str,
+    value: &Value,
+    len: usize,
+    allow_end: bool,
+) -> Result<usize, GlassError> {
+    let index = expect_num(method, value)?;
+    let max = if allow_end { len + 1 } else { len };
+
+    if index.fract() != 0.0 || index < 0.0 || index >= max as f64 {
+        return Err(GlassError::IndexOutOfBounds {
+            index: value.to_string(),
+            len,
+        });
+    }
+
+    Ok(index as usize)
+}
+
+// every method table only contains methods for that type, so the receiver always matches
+macro_rules! receiver {
+    ($value:expr, $variant:ident) => {
+        match $value {
+            Value::$variant(inner) => inner,
+            _ => unreachable!(),
+        }
+    };
+}
+
+fn num_abs(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Num).abs()))
+}
+
+fn num_floor(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Num).floor()))
+}
+
+fn num_ceil(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Num).ceil()))
+}
+
+fn num_round(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Num).round()))
+}
+
+fn num_is_integer(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let num = receiver!(this, Num);
+    Ok(Value::Bool(num.is_finite() && num.fract() == 0.0))
+}
+
+fn str_len(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Str).chars().count() as f64))
+}
+
+fn str_upper(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Str(receiver!(this, Str).to_uppercase()))
+}
+
+fn str_lower(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Str(receiver!(this, Str).to_lowercase()))
+}
+
+fn str_trim(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Str(receiver!(this, Str).trim().into()))
+}
+
+fn str_split(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+    let separator = expect_str("split", &args[0])?;
+
+    // the parts are never longer than the string
+    sandbox::check_str(this.len())?;
+
+    // splitting on an empty string splits a string into its characters
+    let parts = if separator.is_empty() {
+        this.chars().map(|char| Value::Str(char.into())).collect()
+    } else {
+        this.split(separator)
+            .map(|part| Value::Str(part.into()))
+            .collect()
+    };
+
+    Value::checked_list(parts)
+}
+
+fn str_join(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+
+    let items = match &args[0] {
+        Value::List(list) => list.borrow().clone(),
+        Value::Tuple(items) => items.clone(),
+        value => {
+            return Err(GlassError::InvalidArgument {
+                function: "join".into(),
+                message: format!("expected 'list' but found '{}'", value.get_type()),
+            })
+        }
+    };
+
+    join(&items, &this)
+}
+
+fn str_replace(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+    let from = expect_str("replace", &args[0])?;
+    let to = expect_str("replace", &args[1])?;
+
+    let count = this.matches(from).count();
+    sandbox::check_str(this.len() - count * from.len() + count * to.len())?;
+
+    Ok(Value::Str(this.replace(from, to)))
+}
+
+// indices are counted in characters, not bytes
+fn str_find(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+    let needle = expect_str("find", &args[0])?;
+
+    Ok(Value::Num(match this.find(needle) {
+        Some(byte_index) => this[..byte_index].chars().count() as f64,
+        None => -1.0,
+    }))
+}
+
+fn str_contains(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let needle = expect_str("contains", &args[0])?;
+    Ok(Value::Bool(receiver!(this, Str).contains(needle)))
+}
+
+fn str_starts_with(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let prefix = expect_str("starts_with", &args[0])?;
+    Ok(Value::Bool(receiver!(this, Str).starts_with(prefix)))
+}
+
+fn str_ends_with(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let suffix = expect_str("ends_with", &args[0])?;
+    Ok(Value::Bool(receiver!(this, Str).ends_with(suffix)))
+}
+
+/// The characters of the string, which are unicode code points like `for` iterates over.
+fn str_chars(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+    sandbox::check_items(this.chars().count())?;
+
+    Ok(Value::list(
+        this.chars().map(|char| Value::Str(char.into())).collect(),
+    ))
+}
+
+/// The characters of the string as they are displayed, where an accented letter or an emoji
+/// made of several code points is a single string.
+fn str_graphemes(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let this = receiver!(this, Str);
+    sandbox::check_items(this.graphemes(true).count())?;
+
+    Ok(Value::list(
+        this.graphemes(true)
+            .map(|grapheme| Value::Str(grapheme.into()))
+            .collect(),
+    ))
+}
+
+// `pad_start(width)` or `pad_start(width, fill)`, where the fill is a single character and a
+// space if it isn't given
+fn pad(method: &str, this: Value, args: Vec<Value>, align: char) -> InterpreterResult {
+    let (width, fill) = match &args[..] {
+        [width] => (width, " "),
+        [width, fill] => (width, expect_str(method, fill)?),
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: method.into(),
+                message: format!(
+                    "expected a width and an optional fill but {} argument(s) were given",
+                    args.len()
+                ),
+            })
+        }
+    };
+
+    let width = expect_num(method, width)?;
+    let mut fill_chars = fill.chars();
+
+    let fill = match (fill_chars.next(), fill_chars.next()) {
+        (Some(fill), None) => fill,
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: method.into(),
+                message: format!(
+                    "expected a single character to fill with but found {:?}",
+                    fill
+                ),
+            })
+        }
+    };
+
+    if width.fract() != 0.0 || width < 0.0 {
+        return Err(GlassError::InvalidArgument {
+            function: method.into(),
+            message: format!("{} is not a valid width", width),
+        });
+    }
+
+    let padded = template::pad(&receiver!(this, Str), width as usize, fill, align)?;
+    sandbox::check_str(padded.len())?;
+
+    Ok(Value::Str(padded))
+}
+
+fn str_pad_start(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    pad("pad_start", this, args, '>')
+}
+
+fn str_pad_end(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    pad("pad_end", this, args, '<')
+}
+
+fn str_format(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Str(template::format(&receiver!(this, Str), &args)?))
+}
+
+fn list_len(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, List).borrow().len() as f64))
+}
+
+fn list_push(_: &Interpreter, this: Value, mut args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+    sandbox::check_items(1)?;
+
+    list.borrow_mut().push(args.remove(0));
+    Ok(Value::Void)
+}
+
+fn list_pop(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    match receiver!(this, List).borrow_mut().pop() {
+        Some(value) => Ok(value),
+        None => Err(GlassError::IndexOutOfBounds {
+            index: "-1".into(),
+            len: 0,
+        }),
+    }
+}
+
+fn list_insert(_: &Interpreter, this: Value, mut args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+    let position = expect_position("insert", &args[0], list.borrow().len(), true)?;
+    sandbox::check_items(1)?;
+
+    list.borrow_mut().insert(position, args.remove(1));
+    Ok(Value::Void)
+}
+
+fn list_remove(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+    let position = expect_position("remove", &args[0], list.borrow().len(), false)?;
+
+    let removed = list.borrow_mut().remove(position);
+    Ok(removed)
+}
+
+fn list_extend(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+
+    // cloned first, since extending a list with itself would borrow it twice
+    let items = match &args[0] {
+        Value::List(other) => other.borrow().clone(),
+        Value::Tuple(items) => items.clone(),
+        value => {
+            return Err(GlassError::InvalidArgument {
+                function: "extend".into(),
+                message: format!("expected 'list' but found '{}'", value.get_type()),
+            })
+        }
+    };
+
+    sandbox::check_items(items.len())?;
+
+    list.borrow_mut().extend(items);
+    Ok(Value::Void)
+}
+
+fn list_clear(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    receiver!(this, List).borrow_mut().clear();
+    Ok(Value::Void)
+}
+
+fn list_find(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+    let position = list.borrow().iter().position(|item| *item == args[0]);
+
+    Ok(Value::Num(
+        position.map_or(-1.0, |position| position as f64),
+    ))
+}
+
+fn list_contains(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let list = receiver!(this, List);
+    let contains = list.borrow().iter().any(|item| *item == args[0]);
+
+    Ok(Value::Bool(contains))
+}
+
+fn list_join(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let separator = expect_str("join", &args[0])?;
+    let list = receiver!(this, List);
+    let items = list.borrow();
+
+    join(&items, separator)
+}
+
+// the size is checked before joining, since joining many long strings can make a huge one
+fn join(items: &[Value], separator: &str) -> InterpreterResult {
+    let items = items
+        .iter()
+        .map(|item| item.format(false))
+        .collect::<Result<Vec<_>, _>>()?;
+    let separators = separator.len() * items.len().saturating_sub(1);
+
+    sandbox::check_str(items.iter().map(String::len).sum::<usize>() + separators)?;
+
+    Ok(Value::Str(items.join(separator)))
+}
+
+// calls a function that has to return a boolean, like the function given to `filter`
+fn call_predicate(
+    interpreter: &Interpreter,
+    method: &str,
+    function: &Value,
+    item: Value,
+) -> Result<bool, GlassError> {
+    match interpreter.call_function(function, vec![item])? {
+        Value::Bool(bool) => Ok(bool),
+        value => Err(GlassError::InvalidArgument {
+            function: method.into(),
+            message: format!(
+                "expected the function to return 'boolean' but it returned '{}'",
+                value.get_type()
+            ),
+        }),
+    }
+}
+
+// the functions given to the list methods can change the list, so they are called on a copy
+fn list_items(this: Value) -> Vec<Value> {
+    receiver!(this, List).borrow().clone()
+}
+
+fn list_map(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let items = list_items(this)
+        .into_iter()
+        .map(|item| interpreter.call_function(&args[0], vec![item]))
+        .collect::<Result<_, _>>()?;
+
+    Value::checked_list(items)
+}
+
+fn list_filter(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let mut items = Vec::new();
+
+    for item in list_items(this) {
+        if call_predicate(interpreter, "filter", &args[0], item.clone())? {
+            items.push(item);
+        }
+    }
+
+    Value::checked_list(items)
+}
+
+/// `reduce(function)` or `reduce(function, initial)`, which combines the items from first to
+/// last with `function(total, item)`. Without an initial value, the first item is used and the
+/// list can't be empty.
+fn list_reduce(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let mut items = list_items(this).into_iter();
+
+    let (function, initial) = match &args[..] {
+        [function] => (function, items.next()),
+        [function, initial] => (function, Some(initial.clone())),
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: "reduce".into(),
+                message: format!(
+                "expected a function and an optional initial value but {} argument(s) were given",
+                args.len()
+            ),
+            })
+        }
+    };
+
+    let Some(mut total) = initial else {
+        return Err(GlassError::InvalidArgument {
+            function: "reduce".into(),
+            message: "an empty list can't be reduced without an initial value".into(),
+        });
+    };
+
+    for item in items {
+        total = interpreter.call_function(function, vec![total, item])?;
+    }
+
+    Ok(total)
+}
+
+// `any()` and `all()` check a list of booleans, `any(function)` and `all(function)` check what
+// the function returns for each item. Both stop at the first item that decides the result.
+fn test_items(
+    interpreter: &Interpreter,
+    method: &str,
+    this: Value,
+    args: Vec<Value>,
+    stop_at: bool,
+) -> InterpreterResult {
+    let function = match &args[..] {
+        [] => None,
+        [function] => Some(function),
+        _ => {
+            return Err(GlassError::InvalidArgument {
+                function: method.into(),
+                message: format!(
+                    "expected an optional function but {} arguments were given",
+                    args.len()
+                ),
+            })
+        }
+    };
+
+    for item in list_items(this) {
+        let result = match (function, item) {
+            (Some(function), item) => call_predicate(interpreter, method, function, item)?,
+            (None, Value::Bool(bool)) => bool,
+            (None, item) => {
+                return Err(GlassError::InvalidArgument {
+                    function: method.into(),
+                    message: format!("expected 'boolean' but found '{}'", item.get_type()),
+                })
+            }
+        };
+
+        if result == stop_at {
+            return Ok(Value::Bool(stop_at));
+        }
+    }
+
+    Ok(Value::Bool(!stop_at))
+}
+
+fn list_any(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    test_items(interpreter, "any", this, args, true)
+}
+
+fn list_all(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    test_items(interpreter, "all", this, args, false)
+}
+
+// the order `sort` puts values in. Numbers, strings and booleans can only be compared to their
+// own type, and tuples are compared item by item.
+fn compare(a: &Value, b: &Value) -> Result<Ordering, GlassError> {
+    Ok(match (a, b) {
+        (Value::Num(a), Value::Num(b)) => a.total_cmp(b),
+        (Value::Str(a), Value::Str(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Tuple(a), Value::Tuple(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match compare(a, b)? {
+                    Ordering::Equal => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+
+            a.len().cmp(&b.len())
+        }
+        (a, b) => {
+            return Err(GlassError::InvalidOperation {
+                operation: "<".into(),
+                left: a.get_type(),
+                right: b.get_type(),
+            })
+        }
+    })
+}
+
+/// A stable merge sort. Unlike the sorts of the standard library, the comparison can fail, and
+/// a script's comparison that isn't consistent can't make it panic.
+fn merge_sort<T>(
+    mut items: Vec<T>,
+    compare: &mut impl FnMut(&T, &T) -> Result<Ordering, GlassError>,
+) -> Result<Vec<T>, GlassError> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // taking from the left when they are equal keeps the sort stable
+        let next = match compare(a, b)? {
+            Ordering::Greater => right.next(),
+            _ => left.next(),
+        };
+
+        merged.extend(next);
+    }
+
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// sorts a list in place with `sort`, which may call back into the script. Like in Python, the
+// list is empty while it is sorted, so that a function that modifies it can be caught instead
+// of its changes being overwritten, and the list is left as it was if anything goes wrong
+fn sort_in_place(
+    this: Value,
+    function: &str,
+    sort: impl FnOnce(Vec<Value>) -> Result<Vec<Value>, GlassError>,
+) -> InterpreterResult {
+    let list = receiver!(this, List);
+    let items = std::mem::take(&mut *list.borrow_mut());
+    let sorted = sort(items.clone());
+
+    let mut current = list.borrow_mut();
+
+    // an empty Vec doesn't allocate until something is added to it, so this also catches items
+    // that were added and then removed again
+    if current.capacity() != 0 {
+        *current = items;
+        return Err(GlassError::ModifiedWhileSorting {
+            function: function.into(),
+        });
+    }
+
+    match sorted {
+        Ok(sorted) => *current = sorted,
+        Err(err) => {
+            *current = items;
+            return Err(err);
+        }
+    }
+
+    Ok(Value::Void)
+}
+
+/// `sort()` or `sort(key)`, which sorts the list in place by its items or by what `key`
+/// returns for each of them.
+fn list_sort(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    if args.len() > 1 {
+        return Err(GlassError::InvalidArgument {
+            function: "sort".into(),
+            message: format!(
+                "expected an optional key function but {} arguments were given",
+                args.len()
+            ),
+        });
+    }
+
+    sort_in_place(this, "sort", |items| match args.first() {
+        None => merge_sort(items, &mut compare),
+        Some(key) => {
+            let keyed = items
+                .into_iter()
+                .map(|item| Ok((interpreter.call_function(key, vec![item.clone()])?, item)))
+                .collect::<Result<_, GlassError>>()?;
+
+            Ok(merge_sort(keyed, &mut |(a, _), (b, _)| compare(a, b))?
+                .into_iter()
+                .map(|(_, item)| item)
+                .collect())
+        }
+    })
+}
+
+/// `sort_by(compare)`, which sorts the list in place with `compare(a, b)` returning a negative
+/// number if `a` goes first, a positive one if `b` does and 0 if either can.
+fn list_sort_by(interpreter: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    sort_in_place(this, "sort_by", |items| {
+        merge_sort(items, &mut |a, b| match interpreter
+            .call_function(&args[0], vec![a.clone(), b.clone()])?
+        {
+            Value::Num(order) if order < 0.0 => Ok(Ordering::Less),
+            Value::Num(order) if order > 0.0 => Ok(Ordering::Greater),
+            Value::Num(_) => Ok(Ordering::Equal),
+            value => Err(GlassError::InvalidArgument {
+                function: "sort_by".into(),
+                message: format!(
+                    "expected the function to return 'number' but it returned '{}'",
+                    value.get_type()
+                ),
+            }),
+        })
+    })
+}
+
+fn list_reverse(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    receiver!(this, List).borrow_mut().reverse();
+    Ok(Value::Void)
+}
+
+/// `zip(others...)`, a list of tuples of the items at each index of this list and the others,
+/// which is as long as the shortest of them.
+fn list_zip(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    if args.is_empty() {
+        return Err(GlassError::InvalidArgument {
+            function: "zip".into(),
+            message: "expected at least one list to zip with".into(),
+        });
+    }
+
+    let mut columns = vec![list_items(this).into_iter()];
+
+    for arg in args {
+        columns.push(arg.into_items()?.into_iter());
+    }
+
+    let mut rows = Vec::new();
+
+    // stops at the first column that runs out
+    while let Some(row) = columns
+        .iter_mut()
+        .map(Iterator::next)
+        .collect::<Option<Vec<_>>>()
+    {
+        sandbox::check_items(row.len())?;
+        rows.push(Value::Tuple(row));
+    }
+
+    Value::checked_list(rows)
+}
+
+fn list_enumerate(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let items = list_items(this)
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| Value::Tuple(vec![Value::Num(index as f64), item]))
+        .collect::<Vec<_>>();
+
+    // and the index and the item of each tuple
+    sandbox::check_items(items.len() * 2)?;
+    Value::checked_list(items)
+}
+
+/// Flattens lists and tuples in the list by one level, keeping every other item as it is.
+fn list_flatten(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let mut items = Vec::new();
+
+    for item in list_items(this) {
+        match item {
+            Value::List(list) => items.extend(list.borrow().iter().cloned()),
+            Value::Tuple(tuple) => items.extend(tuple),
+            item => items.push(item),
+        }
+    }
+
+    Value::checked_list(items)
+}
+
+/// The items of the list without the ones equal to an earlier item.
+fn list_unique(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let mut seen = Dict::new();
+    let mut items: Vec<Value> = Vec::new();
+
+    for item in list_items(this) {
+        // lists and other items that can't be hashed are compared to every item kept so far
+        let new = match seen.insert(&item, Value::Void) {
+            Ok(previous) => previous.is_none(),
+            Err(_) => !items.contains(&item),
+        };
+
+        if new {
+            items.push(item);
+        }
+    }
+
+    Value::checked_list(items)
+}
+
+fn dict_len(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Dict).borrow().len() as f64))
+}
+
+fn dict_keys(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let keys = receiver!(this, Dict)
+        .borrow()
+        .iter()
+        .map(|(key, _)| key.to_value())
+        .collect();
+
+    Value::checked_list(keys)
+}
+
+fn dict_values(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let values = receiver!(this, Dict)
+        .borrow()
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    Value::checked_list(values)
+}
+
+fn dict_items(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    let items = receiver!(this, Dict)
+        .borrow()
+        .iter()
+        .map(|(key, value)| Value::Tuple(vec![key.to_value(), value.clone()]))
+        .collect::<Vec<_>>();
+
+    // and the key and the value of each tuple
+    sandbox::check_items(items.len() * 2)?;
+    Value::checked_list(items)
+}
+
+fn dict_get(_: &Interpreter, this: Value, mut args: Vec<Value>) -> InterpreterResult {
+    let default = args.remove(1);
+
+    match receiver!(this, Dict).borrow().get(&args[0])? {
+        Some(value) => Ok(value.clone()),
+        None => Ok(default),
+    }
+}
+
+fn dict_insert(_: &Interpreter, this: Value, mut args: Vec<Value>) -> InterpreterResult {
+    let value = args.remove(1);
+
+    match receiver!(this, Dict).borrow_mut().insert(&args[0], value)? {
+        Some(previous) => Ok(previous),
+        None => Ok(Value::Void),
+    }
+}
+
+fn dict_remove(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    match receiver!(this, Dict).borrow_mut().remove(&args[0])? {
+        Some(value) => Ok(value),
+        None => Err(GlassError::KeyNotFound {
+            key: args[0].repr(),
+        }),
+    }
+}
+
+fn dict_contains(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let contains = receiver!(this, Dict).borrow().get(&args[0])?.is_some();
+    Ok(Value::Bool(contains))
+}
+
+fn dict_clear(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    receiver!(this, Dict).borrow_mut().clear();
+    Ok(Value::Void)
+}
+
+fn set_len(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Num(receiver!(this, Set).borrow().len() as f64))
+}
+
+fn set_add(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    receiver!(this, Set)
+        .borrow_mut()
+        .insert(&args[0], Value::Void)?;
+
+    Ok(Value::Void)
+}
+
+fn set_remove(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    match receiver!(this, Set).borrow_mut().remove(&args[0])? {
+        Some(_) => Ok(Value::Void),
+        None => Err(GlassError::InvalidArgument {
+            function: "remove".into(),
+            message: format!("{} is not in the set", args[0].repr()),
+        }),
+    }
+}
+
+fn set_contains(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let contains = receiver!(this, Set).borrow().get(&args[0])?.is_some();
+    Ok(Value::Bool(contains))
+}
+
+fn set_clear(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    receiver!(this, Set).borrow_mut().clear();
+    Ok(Value::Void)
+}
+
+// the items of a set and of the argument of a set method, which can be anything a `for` loop
+// goes through
+fn set_and_items(this: Value, other: &Value) -> Result<(Vec<Value>, Dict), GlassError> {
+    let mut others = Dict::new();
+
+    for item in other.clone().into_items()? {
+        others.insert(&item, Value::Void)?;
+    }
+
+    Ok((this.into_items()?, others))
+}
+
+/// The items of the set followed by the items of the other one that it doesn't have.
+fn set_union(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let mut items = this.into_items()?;
+    items.extend(args[0].clone().into_items()?);
+
+    Value::set(items)
+}
+
+fn set_intersection(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let (items, others) = set_and_items(this, &args[0])?;
+    let mut kept = Vec::new();
+
+    for item in items {
+        if others.get(&item)?.is_some() {
+            kept.push(item);
+        }
+    }
+
+    Value::set(kept)
+}
+
+fn set_difference(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let (items, others) = set_and_items(this, &args[0])?;
+    let mut kept = Vec::new();
+
+    for item in items {
+        if others.get(&item)?.is_none() {
+            kept.push(item);
+        }
+    }
+
+    Value::set(kept)
+}
+
+/// Whether every item of the set is also in the other one.
+fn set_is_subset(_: &Interpreter, this: Value, args: Vec<Value>) -> InterpreterResult {
+    let (items, others) = set_and_items(this, &args[0])?;
+
+    for item in items {
+        if others.get(&item)?.is_none() {
+            return Ok(Value::Bool(false));
+        }
+    }
+
+    Ok(Value::Bool(true))
+}
+
+fn set_to_list(_: &Interpreter, this: Value, _: Vec<Value>) -> InterpreterResult {
+    Value::checked_list(this.into_items()?)
+}
@@ -1,6 +1,26 @@
 use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::sandbox;
 use crate::value::Value;
 use crate::Token;
+use logos::Span;
+use serde_json::{json, Value as Json};
+use std::rc::Rc;
+
+/// Where a variable is stored in the context of an enclosing function call, `depth` calls up
+/// from the one it is used in. Variables outside of any function are looked up by name instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// A type written in a script, like `number` in `x: number = 3`. Annotations are only used by
+/// the type checker, and are ignored when the script is run.
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation {
+    pub name: String,
+    pub span: Span,
+}
 
 #[derive(Debug)]
 pub enum Node {
@@ -10,34 +30,107 @@ pub enum Node {
     Number {
         value: f64,
     },
+    Bool {
+        value: bool,
+    },
+    Void,
     Identifier {
         name: String,
+        span: Span,
+        // filled in by the resolver
+        slots: Vec<Slot>,
+    },
+    List {
+        items: Vec<Node>,
+    },
+    Tuple {
+        items: Vec<Node>,
+    },
+    Dict {
+        entries: Vec<(Node, Node)>,
+    },
+    Index {
+        target: Box<Node>,
+        index: Box<Node>,
+        span: Span,
     },
     BinaryOp {
         op: Token,
         left: Box<Node>,
         right: Box<Node>,
+        // the span of the operator, used to report errors found while optimizing
+        span: Span,
     },
     Assignment {
         op: Token,
         left: Box<Node>,
         right: Box<Node>,
+        annotation: Option<TypeAnnotation>,
     },
     UnaryOp {
         op: Token,
         expr: Box<Node>,
+        span: Span,
     },
     FunctionCall {
-        name: String,
+        function: Box<Node>,
         args: Vec<Node>,
+        span: Span,
+    },
+    FieldAccess {
+        target: Box<Node>,
+        field: String,
+        span: Span,
+    },
+    MethodCall {
+        target: Box<Node>,
+        method: String,
+        args: Vec<Node>,
+        span: Span,
     },
     FunctionDefinition {
         name: String,
         signature: Vec<String>,
-        body: Box<Node>,
+        // where each parameter is named
+        param_spans: Vec<Span>,
+        param_types: Vec<Option<TypeAnnotation>>,
+        return_type: Option<TypeAnnotation>,
+        // shared with every function value created from this definition
+        body: Rc<Node>,
+        // the variable of each slot a call needs, starting with the parameters, filled in by the
+        // resolver
+        locals: Rc<[String]>,
+    },
+    StructDefinition {
+        name: String,
+        // the span of the name
+        span: Span,
+        slot: Option<usize>,
+        fields: Vec<String>,
+        methods: Vec<(String, Node)>,
     },
     Return {
         value: Box<Node>,
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+    Throw {
+        value: Box<Node>,
+        span: Span,
+    },
+    Try {
+        body: Box<Node>,
+        // the variable the error is assigned to, if the catch block names one
+        variable: Option<String>,
+        variable_span: Option<Span>,
+        slot: Option<usize>,
+        catch_body: Option<Box<Node>>,
+        finally_body: Option<Box<Node>>,
     },
     If {
         condition: Box<Node>,
@@ -50,9 +143,18 @@ pub enum Node {
     },
     For {
         variable: String,
+        variable_span: Span,
+        slot: Option<usize>,
         start: Box<Node>,
         end: Box<Node>,
-        step: Option<Box<Node>>,
+        inclusive: bool,
+        body: Box<Node>,
+    },
+    ForEach {
+        variable: String,
+        variable_span: Span,
+        slot: Option<usize>,
+        iterable: Box<Node>,
         body: Box<Node>,
     },
     Block {
@@ -62,20 +164,495 @@ pub enum Node {
 
 impl Node {
     pub fn visit(&self, interpreter: &Interpreter) -> InterpreterResult {
+        sandbox::step()?;
+
+        let result = self.evaluate(interpreter);
+
+        if interpreter.is_tracing() {
+            interpreter.trace(self, &result);
+        }
+
+        result
+    }
+
+    fn evaluate(&self, interpreter: &Interpreter) -> InterpreterResult {
         match self {
             Node::String { value } => Ok(Value::Str(value.to_owned())), // todo: don't clone
             Node::Number { value } => Ok(Value::Num(*value)),
-            Node::Identifier { .. } => todo!("identifier"),
-            Node::BinaryOp { op, left, right } => interpreter.visit_bin_op_node(op, left, right),
-            Node::Assignment { .. } => todo!("assignment"),
-            Node::UnaryOp { op, expr } => interpreter.visit_unary_op_node(op, expr),
-            Node::FunctionCall { .. } => todo!("function call"),
-            Node::FunctionDefinition { .. } => todo!("function definition"),
-            Node::Return { .. } => todo!("return"),
-            Node::If { .. } => todo!("if"),
-            Node::While { .. } => todo!("while"),
-            Node::For { .. } => todo!("for"),
+            Node::Bool { value } => Ok(Value::Bool(*value)),
+            Node::Void => Ok(Value::Void),
+            Node::Identifier { name, slots, span } => interpreter
+                .visit_identifier_node(name, slots)
+                .map_err(|err| interpreter.locate(err, span)),
+            Node::List { items } => interpreter.visit_list_node(items),
+            Node::Tuple { items } => interpreter.visit_tuple_node(items),
+            Node::Dict { entries } => interpreter.visit_dict_node(entries),
+            Node::Index {
+                target,
+                index,
+                span,
+            } => interpreter.visit_index_node(target, index, span),
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => interpreter.visit_bin_op_node(op, left, right, span),
+            Node::Assignment {
+                op, left, right, ..
+            } => interpreter.visit_assignment_node(op, left, right),
+            Node::UnaryOp { op, expr, span } => interpreter.visit_unary_op_node(op, expr, span),
+            Node::FunctionCall {
+                function,
+                args,
+                span,
+            } => interpreter.visit_function_call_node(function, args, span),
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => interpreter.visit_field_access_node(target, field, span),
+            Node::MethodCall {
+                target,
+                method,
+                args,
+                span,
+            } => interpreter.visit_method_call_node(target, method, args, span),
+            Node::FunctionDefinition {
+                name,
+                signature,
+                body,
+                locals,
+                ..
+            } => interpreter.visit_function_definition_node(name, signature, body, locals),
+            Node::StructDefinition {
+                name,
+                slot,
+                fields,
+                methods,
+                ..
+            } => interpreter.visit_struct_definition_node(name, *slot, fields, methods),
+            Node::Return { value, .. } => interpreter.visit_return_node(value),
+            Node::Break { .. } => interpreter.visit_break_node(),
+            Node::Continue { .. } => interpreter.visit_continue_node(),
+            Node::Throw { value, span } => interpreter.visit_throw_node(value, span),
+            Node::Try {
+                body,
+                variable,
+                slot,
+                catch_body,
+                finally_body,
+                ..
+            } => interpreter.visit_try_node(
+                body,
+                variable.as_deref(),
+                *slot,
+                catch_body.as_deref(),
+                finally_body.as_deref(),
+            ),
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => interpreter.visit_if_node(condition, body, else_body.as_deref()),
+            Node::While { condition, body } => interpreter.visit_while_node(condition, body),
+            Node::For {
+                variable,
+                slot,
+                start,
+                end,
+                inclusive,
+                body,
+                ..
+            } => interpreter.visit_for_node(variable, *slot, start, end, *inclusive, body),
+            Node::ForEach {
+                variable,
+                slot,
+                iterable,
+                body,
+                ..
+            } => interpreter.visit_for_each_node(variable, *slot, iterable, body),
             Node::Block { statements } => interpreter.visit_block_node(statements),
         }
     }
+
+    /// The nodes directly inside of this one, in the order they are evaluated. A function body
+    /// is included, even though it is only evaluated once the function is called.
+    pub fn children(&self) -> Vec<&Node> {
+        match self {
+            Node::List { items } | Node::Tuple { items } => items.iter().collect(),
+            Node::Dict { entries } => entries
+                .iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+            Node::Index { target, index, .. } => vec![target, index],
+            Node::BinaryOp { left, right, .. } => vec![left, right],
+            Node::Assignment { left, right, .. } => vec![left, right],
+            Node::UnaryOp { expr, .. } => vec![expr],
+            Node::FunctionCall { function, args, .. } => {
+                std::iter::once(&**function).chain(args).collect()
+            }
+            Node::FieldAccess { target, .. } => vec![target],
+            Node::MethodCall { target, args, .. } => {
+                std::iter::once(&**target).chain(args).collect()
+            }
+            Node::FunctionDefinition { body, .. } => vec![body],
+            Node::StructDefinition { methods, .. } => {
+                methods.iter().map(|(_, method)| method).collect()
+            }
+            Node::Return { value, .. } | Node::Throw { value, .. } => vec![value],
+            Node::Try {
+                body,
+                catch_body,
+                finally_body,
+                ..
+            } => std::iter::once(body)
+                .chain(catch_body)
+                .chain(finally_body)
+                .map(|node| &**node)
+                .collect(),
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => [condition, body]
+                .into_iter()
+                .chain(else_body)
+                .map(|node| &**node)
+                .collect(),
+            Node::While { condition, body } => vec![condition, body],
+            Node::For {
+                start, end, body, ..
+            } => vec![start, end, body],
+            Node::ForEach { iterable, body, .. } => vec![iterable, body],
+            Node::Block { statements } => statements.iter().collect(),
+            Node::String { .. }
+            | Node::Number { .. }
+            | Node::Bool { .. }
+            | Node::Void
+            | Node::Identifier { .. }
+            | Node::Break { .. }
+            | Node::Continue { .. } => Vec::new(),
+        }
+    }
+
+    /// The name of the kind of node, which is the name of its variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Node::String { .. } => "String",
+            Node::Number { .. } => "Number",
+            Node::Bool { .. } => "Bool",
+            Node::Void => "Void",
+            Node::Identifier { .. } => "Identifier",
+            Node::List { .. } => "List",
+            Node::Tuple { .. } => "Tuple",
+            Node::Dict { .. } => "Dict",
+            Node::Index { .. } => "Index",
+            Node::BinaryOp { .. } => "BinaryOp",
+            Node::Assignment { .. } => "Assignment",
+            Node::UnaryOp { .. } => "UnaryOp",
+            Node::FunctionCall { .. } => "FunctionCall",
+            Node::FieldAccess { .. } => "FieldAccess",
+            Node::MethodCall { .. } => "MethodCall",
+            Node::FunctionDefinition { .. } => "FunctionDefinition",
+            Node::StructDefinition { .. } => "StructDefinition",
+            Node::Return { .. } => "Return",
+            Node::Break { .. } => "Break",
+            Node::Continue { .. } => "Continue",
+            Node::Throw { .. } => "Throw",
+            Node::Try { .. } => "Try",
+            Node::If { .. } => "If",
+            Node::While { .. } => "While",
+            Node::For { .. } => "For",
+            Node::ForEach { .. } => "ForEach",
+            Node::Block { .. } => "Block",
+        }
+    }
+
+    /// The span to point at for this node, which is the span of its first descendant that has
+    /// one for nodes without their own.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::Identifier { span, .. }
+            | Node::Index { span, .. }
+            | Node::BinaryOp { span, .. }
+            | Node::UnaryOp { span, .. }
+            | Node::FunctionCall { span, .. }
+            | Node::FieldAccess { span, .. }
+            | Node::MethodCall { span, .. }
+            | Node::Return { span, .. }
+            | Node::Break { span }
+            | Node::Continue { span }
+            | Node::Throw { span, .. } => Some(span.clone()),
+            node => node.children().into_iter().find_map(Node::span),
+        }
+    }
+
+    /// Where the node starts in the source. Its `span` can be that of a child further in, like
+    /// the body of a loop.
+    pub fn start(&self) -> Option<usize> {
+        match self {
+            Node::For { variable_span, .. } | Node::ForEach { variable_span, .. } => {
+                Some(variable_span.start)
+            }
+            node => node
+                .children()
+                .into_iter()
+                .filter_map(Node::span)
+                .chain(node.span())
+                .map(|span| span.start)
+                .min(),
+        }
+    }
+
+    /// The node as JSON, for tools that read the AST. Every node is an object with its kind as
+    /// `type`, and spans are byte offsets into the source.
+    pub fn to_json(&self) -> Json {
+        let nodes = |nodes: &[Node]| nodes.iter().map(Node::to_json).collect::<Vec<_>>();
+        let optional = |node: &Option<Box<Node>>| node.as_ref().map(|node| node.to_json());
+        let annotation = |annotation: &Option<TypeAnnotation>| {
+            annotation
+                .as_ref()
+                .map(|annotation| annotation.name.clone())
+        };
+
+        let mut json = match self {
+            Node::String { value } => json!({ "value": value }),
+            Node::Number { value } => json!({ "value": value }),
+            Node::Bool { value } => json!({ "value": value }),
+            Node::Void => json!({}),
+            Node::Identifier { name, span, .. } => json!({ "name": name, "span": span_json(span) }),
+            Node::List { items } | Node::Tuple { items } => json!({ "items": nodes(items) }),
+            Node::Dict { entries } => json!({
+                "entries": entries
+                    .iter()
+                    .map(|(key, value)| json!([key.to_json(), value.to_json()]))
+                    .collect::<Vec<_>>(),
+            }),
+            Node::Index {
+                target,
+                index,
+                span,
+            } => json!({
+                "target": target.to_json(),
+                "index": index.to_json(),
+                "span": span_json(span),
+            }),
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => json!({
+                "op": op.get_rep(),
+                "left": left.to_json(),
+                "right": right.to_json(),
+                "span": span_json(span),
+            }),
+            Node::Assignment {
+                op,
+                left,
+                right,
+                annotation: type_annotation,
+            } => json!({
+                "op": op.get_rep(),
+                "left": left.to_json(),
+                "right": right.to_json(),
+                "annotation": annotation(type_annotation),
+            }),
+            Node::UnaryOp { op, expr, span } => json!({
+                "op": op.get_rep(),
+                "expr": expr.to_json(),
+                "span": span_json(span),
+            }),
+            Node::FunctionCall {
+                function,
+                args,
+                span,
+            } => json!({
+                "function": function.to_json(),
+                "args": nodes(args),
+                "span": span_json(span),
+            }),
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => json!({
+                "target": target.to_json(),
+                "field": field,
+                "span": span_json(span),
+            }),
+            Node::MethodCall {
+                target,
+                method,
+                args,
+                span,
+            } => json!({
+                "target": target.to_json(),
+                "method": method,
+                "args": nodes(args),
+                "span": span_json(span),
+            }),
+            Node::FunctionDefinition {
+                name,
+                signature,
+                param_spans,
+                param_types,
+                return_type,
+                body,
+                ..
+            } => json!({
+                "name": name,
+                "params": signature
+                    .iter()
+                    .zip(param_spans)
+                    .zip(param_types)
+                    .map(|((param, span), param_type)| json!({
+                        "name": param,
+                        "type": annotation(param_type),
+                        "span": span_json(span),
+                    }))
+                    .collect::<Vec<_>>(),
+                "return_type": annotation(return_type),
+                "body": body.to_json(),
+            }),
+            Node::StructDefinition {
+                name,
+                span,
+                fields,
+                methods,
+                ..
+            } => json!({
+                "name": name,
+                "fields": fields,
+                "methods": methods
+                    .iter()
+                    .map(|(name, method)| json!({ "name": name, "function": method.to_json() }))
+                    .collect::<Vec<_>>(),
+                "span": span_json(span),
+            }),
+            Node::Return { value, span } | Node::Throw { value, span } => {
+                json!({ "value": value.to_json(), "span": span_json(span) })
+            }
+            Node::Break { span } | Node::Continue { span } => json!({ "span": span_json(span) }),
+            Node::Try {
+                body,
+                variable,
+                catch_body,
+                finally_body,
+                ..
+            } => json!({
+                "body": body.to_json(),
+                "variable": variable,
+                "catch_body": optional(catch_body),
+                "finally_body": optional(finally_body),
+            }),
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => json!({
+                "condition": condition.to_json(),
+                "body": body.to_json(),
+                "else_body": optional(else_body),
+            }),
+            Node::While { condition, body } => json!({
+                "condition": condition.to_json(),
+                "body": body.to_json(),
+            }),
+            Node::For {
+                variable,
+                start,
+                end,
+                inclusive,
+                body,
+                ..
+            } => json!({
+                "variable": variable,
+                "start": start.to_json(),
+                "end": end.to_json(),
+                "inclusive": inclusive,
+                "body": body.to_json(),
+            }),
+            Node::ForEach {
+                variable,
+                iterable,
+                body,
+                ..
+            } => json!({
+                "variable": variable,
+                "iterable": iterable.to_json(),
+                "body": body.to_json(),
+            }),
+            Node::Block { statements } => json!({ "statements": nodes(statements) }),
+        };
+
+        json["type"] = json!(self.name());
+        json
+    }
+
+    /// Like `children`, but mutable. A function body is skipped once it is shared by a function
+    /// value, since it can't be changed anymore.
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            Node::List { items } | Node::Tuple { items } => items.iter_mut().collect(),
+            Node::Dict { entries } => entries
+                .iter_mut()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+            Node::Index { target, index, .. } => vec![target, index],
+            Node::BinaryOp { left, right, .. } => vec![left, right],
+            Node::Assignment { left, right, .. } => vec![left, right],
+            Node::UnaryOp { expr, .. } => vec![expr],
+            Node::FunctionCall { function, args, .. } => {
+                std::iter::once(&mut **function).chain(args).collect()
+            }
+            Node::FieldAccess { target, .. } => vec![target],
+            Node::MethodCall { target, args, .. } => {
+                std::iter::once(&mut **target).chain(args).collect()
+            }
+            Node::FunctionDefinition { body, .. } => Rc::get_mut(body).into_iter().collect(),
+            Node::StructDefinition { methods, .. } => {
+                methods.iter_mut().map(|(_, method)| method).collect()
+            }
+            Node::Return { value, .. } | Node::Throw { value, .. } => vec![value],
+            Node::Try {
+                body,
+                catch_body,
+                finally_body,
+                ..
+            } => std::iter::once(body)
+                .chain(catch_body)
+                .chain(finally_body)
+                .map(|node| &mut **node)
+                .collect(),
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => [condition, body]
+                .into_iter()
+                .chain(else_body)
+                .map(|node| &mut **node)
+                .collect(),
+            Node::While { condition, body } => vec![condition, body],
+            Node::For {
+                start, end, body, ..
+            } => vec![start, end, body],
+            Node::ForEach { iterable, body, .. } => vec![iterable, body],
+            Node::Block { statements } => statements.iter_mut().collect(),
+            Node::String { .. }
+            | Node::Number { .. }
+            | Node::Bool { .. }
+            | Node::Void
+            | Node::Identifier { .. }
+            | Node::Break { .. }
+            | Node::Continue { .. } => Vec::new(),
+        }
+    }
+}
+
+fn span_json(span: &Span) -> Json {
+    json!({ "start": span.start, "end": span.end })
 }
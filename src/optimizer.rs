@@ -0,0 +1,425 @@
+//! Passes over the AST that run after parsing, before it is interpreted or compiled.
+
+use crate::bytecode::BinOp;
+use crate::error::GlassError;
+use crate::lexer::Token;
+use crate::node::Node;
+use crate::value::Value;
+use log::debug;
+use logos::Span;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type OptimizeResult = Result<Node, GlassError>;
+type Pass = fn(&Optimizer, Node) -> OptimizeResult;
+
+// run in order, each over the whole tree
+const PASSES: &[(&str, Pass)] = &[
+    ("constant folding", Optimizer::fold_constants),
+    (
+        "dead branch elimination",
+        Optimizer::eliminate_dead_branches,
+    ),
+];
+
+// folding something like `"a" * 1000000` would only make the script bigger, so longer strings
+// are left to be built at runtime
+const MAX_FOLDED_STRING_LEN: usize = 4096;
+
+pub struct Optimizer {
+    src: Rc<str>,
+    filename: Rc<str>,
+    // the passes only borrow the optimizer, since they are plain functions over the tree
+    warnings: RefCell<Vec<GlassError>>,
+}
+
+impl Optimizer {
+    pub fn new(src: Rc<str>, filename: Rc<str>) -> Self {
+        Self {
+            src,
+            filename,
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Runs every pass over `node`, returning the warnings found along the way.
+    pub fn optimize(self, mut node: Node) -> Result<(Node, Vec<GlassError>), GlassError> {
+        for (name, pass) in PASSES {
+            debug!("Running {} pass", name);
+            node = pass(&self, node)?;
+        }
+
+        Ok((node, self.warnings.into_inner()))
+    }
+
+    /// Evaluates operators whose operands are all literals, replacing them with their result.
+    fn fold_constants(&self, node: Node) -> OptimizeResult {
+        match self.map_children(node, Self::fold_constants)? {
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                // and/or short circuit, so the right side doesn't need to be constant
+                match (&op, literal_value(&left)) {
+                    (Token::And, Some(Value::Bool(false))) => return Ok(*left),
+                    (Token::Or, Some(Value::Bool(true))) => return Ok(*left),
+                    _ => {}
+                }
+
+                let result = match (
+                    BinOp::from_token(&op),
+                    literal_value(&left),
+                    literal_value(&right),
+                ) {
+                    (Some(bin_op), Some(a), Some(b))
+                        if folded_len(bin_op, &a, &b) <= MAX_FOLDED_STRING_LEN =>
+                    {
+                        bin_op.apply(a, b)
+                    }
+                    _ => {
+                        return Ok(Node::BinaryOp {
+                            op,
+                            left,
+                            right,
+                            span,
+                        })
+                    }
+                };
+
+                match result.map(literal_node) {
+                    Ok(Some(folded)) => Ok(folded),
+                    Ok(None) => Ok(Node::BinaryOp {
+                        op,
+                        left,
+                        right,
+                        span,
+                    }),
+                    Err(err) => {
+                        self.keep_failing(err, span.clone());
+                        Ok(Node::BinaryOp {
+                            op,
+                            left,
+                            right,
+                            span,
+                        })
+                    }
+                }
+            }
+            Node::UnaryOp { op, expr, span } => {
+                let result = match (&op, literal_value(&expr)) {
+                    (Token::Minus, Some(value)) => value.neg(),
+                    (Token::Not, Some(value)) => value.not(),
+                    (Token::Plus, Some(value)) => Ok(value),
+                    _ => return Ok(Node::UnaryOp { op, expr, span }),
+                };
+
+                match result.map(literal_node) {
+                    Ok(Some(folded)) => Ok(folded),
+                    Ok(None) => Ok(Node::UnaryOp { op, expr, span }),
+                    Err(err) => {
+                        self.keep_failing(err, span.clone());
+                        Ok(Node::UnaryOp { op, expr, span })
+                    }
+                }
+            }
+            node => Ok(node),
+        }
+    }
+
+    /// Removes the branches of ifs and whiles with a constant condition that can never run.
+    fn eliminate_dead_branches(&self, node: Node) -> OptimizeResult {
+        match self.map_children(node, Self::eliminate_dead_branches)? {
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => match (*condition, else_body) {
+                // an if is always void, so a trailing void keeps it that way when it is the last
+                // statement of a block
+                (Node::Bool { value: true }, _) => Ok(Node::Block {
+                    statements: vec![*body, Node::Void],
+                }),
+                (Node::Bool { value: false }, Some(else_body)) => Ok(Node::Block {
+                    statements: vec![*else_body, Node::Void],
+                }),
+                (Node::Bool { value: false }, None) => Ok(Node::Void),
+                (condition, else_body) => Ok(Node::If {
+                    condition: Box::new(condition),
+                    body,
+                    else_body,
+                }),
+            },
+            Node::While { condition, .. } if matches!(*condition, Node::Bool { value: false }) => {
+                Ok(Node::Void)
+            }
+            Node::Block { statements } => {
+                let last = statements.len().saturating_sub(1);
+
+                // a removed statement leaves a void behind, which only matters if it is the
+                // value of the block
+                Ok(Node::Block {
+                    statements: statements
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, statement)| *i == last || !matches!(statement, Node::Void))
+                        .map(|(_, statement)| statement)
+                        .collect(),
+                })
+            }
+            node => Ok(node),
+        }
+    }
+
+    /// Rebuilds a node with `pass` applied to each of its children.
+    fn map_children(&self, node: Node, pass: Pass) -> OptimizeResult {
+        let boxed = |node: Box<Node>| pass(self, *node).map(Box::new);
+        let all = |nodes: Vec<Node>| {
+            nodes
+                .into_iter()
+                .map(|node| pass(self, node))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match node {
+            Node::List { items } => Node::List { items: all(items)? },
+            Node::Tuple { items } => Node::Tuple { items: all(items)? },
+            Node::Dict { entries } => Node::Dict {
+                entries: entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((pass(self, key)?, pass(self, value)?)))
+                    .collect::<Result<_, GlassError>>()?,
+            },
+            Node::Index {
+                target,
+                index,
+                span,
+            } => Node::Index {
+                target: boxed(target)?,
+                index: boxed(index)?,
+                span,
+            },
+            Node::BinaryOp {
+                op,
+                left,
+                right,
+                span,
+            } => Node::BinaryOp {
+                op,
+                left: boxed(left)?,
+                right: boxed(right)?,
+                span,
+            },
+            Node::Assignment {
+                op,
+                left,
+                right,
+                annotation,
+            } => Node::Assignment {
+                op,
+                left: boxed(left)?,
+                right: boxed(right)?,
+                annotation,
+            },
+            Node::UnaryOp { op, expr, span } => Node::UnaryOp {
+                op,
+                expr: boxed(expr)?,
+                span,
+            },
+            Node::FunctionCall {
+                function,
+                args,
+                span,
+            } => Node::FunctionCall {
+                function: boxed(function)?,
+                args: all(args)?,
+                span,
+            },
+            Node::FieldAccess {
+                target,
+                field,
+                span,
+            } => Node::FieldAccess {
+                target: boxed(target)?,
+                field,
+                span,
+            },
+            Node::MethodCall {
+                target,
+                method,
+                args,
+                span,
+            } => Node::MethodCall {
+                target: boxed(target)?,
+                method,
+                args: all(args)?,
+                span,
+            },
+            Node::FunctionDefinition {
+                name,
+                signature,
+                param_spans,
+                param_types,
+                return_type,
+                body,
+                locals,
+            } => Node::FunctionDefinition {
+                name,
+                signature,
+                param_spans,
+                param_types,
+                return_type,
+                locals,
+                // the body is only shared once the function has been evaluated
+                body: match Rc::try_unwrap(body) {
+                    Ok(body) => Rc::new(pass(self, body)?),
+                    Err(body) => body,
+                },
+            },
+            Node::StructDefinition {
+                name,
+                span,
+                slot,
+                fields,
+                methods,
+            } => Node::StructDefinition {
+                name,
+                span,
+                slot,
+                fields,
+                methods: methods
+                    .into_iter()
+                    .map(|(name, method)| Ok((name, pass(self, method)?)))
+                    .collect::<Result<_, GlassError>>()?,
+            },
+            Node::Return { value, span } => Node::Return {
+                value: boxed(value)?,
+                span,
+            },
+            Node::Throw { value, span } => Node::Throw {
+                value: boxed(value)?,
+                span,
+            },
+            Node::Try {
+                body,
+                variable,
+                variable_span,
+                slot,
+                catch_body,
+                finally_body,
+            } => Node::Try {
+                body: boxed(body)?,
+                variable,
+                variable_span,
+                slot,
+                catch_body: catch_body.map(boxed).transpose()?,
+                finally_body: finally_body.map(boxed).transpose()?,
+            },
+            Node::If {
+                condition,
+                body,
+                else_body,
+            } => Node::If {
+                condition: boxed(condition)?,
+                body: boxed(body)?,
+                else_body: else_body.map(boxed).transpose()?,
+            },
+            Node::While { condition, body } => Node::While {
+                condition: boxed(condition)?,
+                body: boxed(body)?,
+            },
+            Node::For {
+                variable,
+                variable_span,
+                slot,
+                start,
+                end,
+                inclusive,
+                body,
+            } => Node::For {
+                variable,
+                variable_span,
+                slot,
+                start: boxed(start)?,
+                end: boxed(end)?,
+                inclusive,
+                body: boxed(body)?,
+            },
+            Node::ForEach {
+                variable,
+                variable_span,
+                slot,
+                iterable,
+                body,
+            } => Node::ForEach {
+                variable,
+                variable_span,
+                slot,
+                iterable: boxed(iterable)?,
+                body: boxed(body)?,
+            },
+            Node::Block { statements } => Node::Block {
+                statements: all(statements)?,
+            },
+            node @ (Node::String { .. }
+            | Node::Number { .. }
+            | Node::Bool { .. }
+            | Node::Void
+            | Node::Identifier { .. }
+            | Node::Break { .. }
+            | Node::Continue { .. }) => node,
+        })
+    }
+
+    // an operation on constants that fails is left in place, so that it fails when it is run
+    // and can be caught, or never fails at all if it is in code that doesn't run, but it is
+    // almost certainly a mistake so it is warned about
+    fn keep_failing(&self, err: GlassError, span: Span) {
+        self.warnings.borrow_mut().push(GlassError::Spanned {
+            error: Box::new(GlassError::AlwaysFails {
+                error: Box::new(err),
+            }),
+            src: Rc::clone(&self.src),
+            filename: Rc::clone(&self.filename),
+            span,
+        });
+    }
+}
+
+fn literal_value(node: &Node) -> Option<Value> {
+    match node {
+        Node::String { value } => Some(Value::Str(value.clone())),
+        Node::Number { value } => Some(Value::Num(*value)),
+        Node::Bool { value } => Some(Value::Bool(*value)),
+        Node::Void => Some(Value::Void),
+        _ => None,
+    }
+}
+
+// the most bytes the result of a folded operation can take up, which is checked before applying
+// it so that something like `"x" * 1e9` is never built at compile time
+fn folded_len(bin_op: BinOp, left: &Value, right: &Value) -> usize {
+    match (bin_op, left, right) {
+        (BinOp::Add, Value::Str(a), Value::Str(b)) => a.len().saturating_add(b.len()),
+        (BinOp::Add, Value::Str(str), Value::Num(num))
+        | (BinOp::Add, Value::Num(num), Value::Str(str)) => {
+            str.len().saturating_add(num.to_string().len())
+        }
+        (BinOp::Mul, Value::Str(str), Value::Num(count))
+        | (BinOp::Mul, Value::Num(count), Value::Str(str)) => {
+            str.len().saturating_mul(count.max(0.0) as usize)
+        }
+        _ => 0,
+    }
+}
+
+fn literal_node(value: Value) -> Option<Node> {
+    match value {
+        Value::Str(value) if value.len() <= MAX_FOLDED_STRING_LEN => Some(Node::String { value }),
+        Value::Num(value) => Some(Node::Number { value }),
+        Value::Bool(value) => Some(Node::Bool { value }),
+        Value::Void => Some(Node::Void),
+        _ => None,
+    }
+}
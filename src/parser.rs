@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use std::rc::Rc;
 
 use crate::error::GlassError;
-use crate::node::Node;
+use crate::node::{Node, TypeAnnotation};
 use logos::Span;
 
 pub struct Parser {
@@ -13,6 +13,7 @@ pub struct Parser {
 }
 
 type ParseResult = Result<Node, GlassError>;
+type ParseFn = Box<dyn FnMut(&mut Parser) -> ParseResult>;
 
 macro_rules! token_matches {
     ($token:expr, $($pattern:pat_param)|+) => {
@@ -33,33 +34,355 @@ impl Parser {
     }
 
     pub fn parse(&mut self) -> ParseResult {
-        let result = self.parse_expression()?;
+        let mut statements = Vec::new();
 
-        if let Some((_, span)) = self.next()? {
-            Err(GlassError::UnexpectedToken {
+        while self.peek()?.is_some() {
+            statements.push(self.parse_statement()?);
+        }
+
+        Ok(Node::Block { statements })
+    }
+
+    fn parse_statement(&mut self) -> ParseResult {
+        let statement = match self.peek()? {
+            Some((Token::If, _)) => self.parse_if()?,
+            Some((Token::While, _)) => self.parse_while()?,
+            Some((Token::For, _)) => self.parse_for()?,
+            Some((Token::Struct, _)) => self.parse_struct()?,
+            Some((Token::Try, _)) => self.parse_try()?,
+            Some((Token::Throw, span)) => {
+                self.next()?;
+
+                Node::Throw {
+                    value: Box::new(self.parse_expression()?),
+                    span,
+                }
+            }
+            Some((Token::Return, span)) => {
+                self.next()?;
+
+                let value = match self.peek()? {
+                    Some((Token::Semicolon | Token::RBrace, _)) | None => Node::Void,
+                    Some(_) => self.parse_expression()?,
+                };
+
+                Node::Return {
+                    value: Box::new(value),
+                    span,
+                }
+            }
+            Some((Token::Break, span)) => {
+                self.next()?;
+                Node::Break { span }
+            }
+            Some((Token::Continue, span)) => {
+                self.next()?;
+                Node::Continue { span }
+            }
+            _ => self.parse_assignment()?,
+        };
+
+        // semicolons between statements are optional
+        if let Some((Token::Semicolon, _)) = self.peek()? {
+            self.next()?;
+        }
+
+        Ok(statement)
+    }
+
+    fn parse_block(&mut self) -> ParseResult {
+        self.expect(Token::LBrace)?;
+
+        let mut statements = Vec::new();
+
+        loop {
+            match self.peek()? {
+                Some((Token::RBrace, _)) => break,
+                Some(_) => statements.push(self.parse_statement()?),
+                None => return Err(self.end_of_input()),
+            }
+        }
+
+        self.expect(Token::RBrace)?;
+
+        Ok(Node::Block { statements })
+    }
+
+    fn parse_if(&mut self) -> ParseResult {
+        self.expect(Token::If)?;
+
+        let condition = self.parse_expression()?;
+        let body = self.parse_block()?;
+
+        let else_body = if let Some((Token::Else, _)) = self.peek()? {
+            self.next()?;
+
+            // else if is just an if statement as the else body
+            if let Some((Token::If, _)) = self.peek()? {
+                Some(Box::new(self.parse_if()?))
+            } else {
+                Some(Box::new(self.parse_block()?))
+            }
+        } else {
+            None
+        };
+
+        Ok(Node::If {
+            condition: Box::new(condition),
+            body: Box::new(body),
+            else_body,
+        })
+    }
+
+    fn parse_while(&mut self) -> ParseResult {
+        self.expect(Token::While)?;
+
+        let condition = self.parse_expression()?;
+        let body = self.parse_block()?;
+
+        Ok(Node::While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        })
+    }
+
+    fn parse_for(&mut self) -> ParseResult {
+        self.expect(Token::For)?;
+
+        let (variable, variable_span) = self.expect_identifier_spanned()?;
+        self.expect(Token::In)?;
+        let iterable = self.parse_expression()?;
+
+        // a range is handled by the loop itself instead of creating a list to iterate over
+        if let Some((token @ (Token::DotDot | Token::DotDotEqual), _)) = self.peek()? {
+            self.next()?;
+
+            let end = self.parse_expression()?;
+            let body = self.parse_block()?;
+
+            return Ok(Node::For {
+                variable,
+                variable_span,
+                slot: None,
+                start: Box::new(iterable),
+                end: Box::new(end),
+                inclusive: token == Token::DotDotEqual,
+                body: Box::new(body),
+            });
+        }
+
+        let body = self.parse_block()?;
+
+        Ok(Node::ForEach {
+            variable,
+            variable_span,
+            slot: None,
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+        })
+    }
+
+    // try { ... } catch err { ... } finally { ... }, where either the catch or the finally block
+    // can be left out, and so can the name of the error
+    fn parse_try(&mut self) -> ParseResult {
+        self.expect(Token::Try)?;
+
+        let body = self.parse_block()?;
+
+        let (variable, catch_body) = if let Some((Token::Catch, _)) = self.peek()? {
+            self.next()?;
+
+            let variable = match self.peek()? {
+                Some((Token::Identifier(_), _)) => Some(self.expect_identifier_spanned()?),
+                _ => None,
+            };
+
+            (variable, Some(Box::new(self.parse_block()?)))
+        } else {
+            (None, None)
+        };
+        let (variable, variable_span) = variable.unzip();
+
+        let finally_body = if let Some((Token::Finally, _)) = self.peek()? {
+            self.next()?;
+            Some(Box::new(self.parse_block()?))
+        } else {
+            // a try without either is an error, since it would just be a block
+            if catch_body.is_none() {
+                self.expect(Token::Catch)?;
+            }
+
+            None
+        };
+
+        Ok(Node::Try {
+            body: Box::new(body),
+            variable,
+            variable_span,
+            slot: None,
+            catch_body,
+            finally_body,
+        })
+    }
+
+    // struct Point {
+    //     x, y
+    //     length = func(self) => (self.x ** 2 + self.y ** 2) ** 0.5
+    // }
+    fn parse_struct(&mut self) -> ParseResult {
+        self.expect(Token::Struct)?;
+
+        let (name, span) = self.expect_identifier_spanned()?;
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+
+        self.expect(Token::LBrace)?;
+
+        loop {
+            match self.next()? {
+                Some((Token::RBrace, _)) => break,
+                Some((Token::Comma | Token::Semicolon, _)) => continue,
+                Some((Token::Identifier(member), _)) => {
+                    if let Some((Token::Equal, _)) = self.peek()? {
+                        self.next()?;
+
+                        let mut function = match self.next()? {
+                            Some((Token::Func, _)) => self.parse_function()?,
+                            Some((_, span)) => {
+                                return Err(GlassError::UnexpectedToken {
+                                    expected: Some(Token::Func),
+                                    src: Rc::clone(&self.src),
+                                    filename: Rc::clone(&self.filename),
+                                    span,
+                                })
+                            }
+                            None => return Err(self.end_of_input()),
+                        };
+
+                        if let Node::FunctionDefinition {
+                            name: function_name,
+                            ..
+                        } = &mut function
+                        {
+                            *function_name = format!("{}.{}", name, member);
+                        }
+
+                        methods.push((member, function));
+                    } else {
+                        fields.push(member);
+                    }
+                }
+                Some((_, span)) => {
+                    return Err(GlassError::UnexpectedToken {
+                        expected: Some(Token::RBrace),
+                        src: Rc::clone(&self.src),
+                        filename: Rc::clone(&self.filename),
+                        span,
+                    })
+                }
+                None => return Err(self.end_of_input()),
+            }
+        }
+
+        Ok(Node::StructDefinition {
+            name,
+            span,
+            slot: None,
+            fields,
+            methods,
+        })
+    }
+
+    fn parse_assignment(&mut self) -> ParseResult {
+        let left = self.parse_expression()?;
+
+        // x: number = 3
+        let annotation = match (&left, self.peek()?) {
+            (Node::Identifier { .. }, Some((Token::Colon, _))) => {
+                self.next()?;
+                let annotation = self.parse_type()?;
+
+                // only a plain assignment can have a type, and it's parsed as one below
+                if !token_matches!(self.peek()?, Some((Token::Equal, _))) {
+                    self.expect(Token::Equal)?;
+                }
+
+                Some(annotation)
+            }
+            _ => None,
+        };
+
+        let (op, span) = match self.peek()? {
+            Some((
+                op @ (Token::Equal
+                | Token::PlusEqual
+                | Token::MinusEqual
+                | Token::StarEqual
+                | Token::SlashEqual
+                | Token::PercentEqual
+                | Token::StarStarEqual),
+                span,
+            )) => (op, span),
+            _ => return Ok(left),
+        };
+
+        if !token_matches!(
+            left,
+            Node::Identifier { .. } | Node::Index { .. } | Node::FieldAccess { .. }
+        ) {
+            return Err(GlassError::UnexpectedToken {
                 expected: None,
                 src: Rc::clone(&self.src),
                 filename: Rc::clone(&self.filename),
                 span,
-            })
-        } else {
-            Ok(result)
+            });
         }
+
+        self.next()?;
+        let mut right = self.parse_expression()?;
+
+        // functions are values, so they get their name from the variable they are assigned to
+        if let (
+            Token::Equal,
+            Node::Identifier { name, .. },
+            Node::FunctionDefinition {
+                name: function_name,
+                ..
+            },
+        ) = (&op, &left, &mut right)
+        {
+            *function_name = name.clone();
+        }
+
+        Ok(Node::Assignment {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+            annotation,
+        })
     }
 
     fn parse_expression(&mut self) -> ParseResult {
-        return self.parse_equality();
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> ParseResult {
+        self.parse_math_expression(Box::new(Self::parse_and), None, vec![Token::Or])
+    }
+
+    fn parse_and(&mut self) -> ParseResult {
+        self.parse_math_expression(Box::new(Self::parse_equality), None, vec![Token::And])
     }
 
     fn parse_math_expression(
         &mut self,
-        mut a: Box<dyn FnMut(&mut Self) -> ParseResult>,
-        mut b: Option<Box<dyn FnMut(&mut Self) -> ParseResult>>,
+        mut a: ParseFn,
+        mut b: Option<ParseFn>,
         types: Vec<Token>,
     ) -> ParseResult {
         let mut left = a(self)?;
 
-        while let Some((token, _)) = self.peek()? {
+        while let Some((token, span)) = self.peek()? {
             if types.contains(&token) {
                 self.next()?;
 
@@ -72,6 +395,7 @@ impl Parser {
                     left: Box::new(left),
                     op: token,
                     right: Box::new(right),
+                    span,
                 };
 
                 continue;
@@ -126,22 +450,21 @@ impl Parser {
     }
 
     fn parse_unary(&mut self) -> ParseResult {
-        if let Some((token, _)) = self.peek()? {
+        if let Some((token, span)) = self.peek()? {
             if token_matches!(token, Token::Minus | Token::Plus | Token::Not) {
                 self.next()?;
 
                 return Ok(Node::UnaryOp {
                     op: token,
                     expr: Box::new(self.parse_unary()?),
+                    span,
                 });
             }
 
-            return self.parse_atom();
+            return self.parse_postfix();
         }
 
-        Err(GlassError::UnexpectedEndOfInput {
-            filename: Rc::clone(&self.filename),
-        })
+        Err(self.end_of_input())
     }
 
     fn parse_atom(&mut self) -> ParseResult {
@@ -150,21 +473,244 @@ impl Parser {
         match token {
             Some((Token::Number(num), _)) => Ok(Node::Number { value: num }),
             Some((Token::String(str), _)) => Ok(Node::String { value: str }),
-            Some((Token::Identifier(ident), _)) => Ok(Node::Identifier { name: ident }),
-            Some((Token::LParen, _)) => {
-                let node = self.parse_expression()?;
-                self.expect(Token::RParen)?;
-                Ok(node)
-            }
+            Some((Token::True, _)) => Ok(Node::Bool { value: true }),
+            Some((Token::False, _)) => Ok(Node::Bool { value: false }),
+            Some((Token::Void, _)) => Ok(Node::Void),
+            Some((Token::Identifier(ident), span)) => Ok(Node::Identifier {
+                name: ident,
+                span,
+                slots: Vec::new(),
+            }),
+            Some((Token::LParen, _)) => self.parse_parenthesized(),
+            Some((Token::LBracket, _)) => self.parse_list(),
+            Some((Token::LBrace, _)) => self.parse_dict(),
+            Some((Token::Func, _)) => self.parse_function(),
             Some((_, span)) => Err(GlassError::UnexpectedToken {
                 expected: None,
                 src: Rc::clone(&self.src),
                 filename: Rc::clone(&self.filename),
                 span,
             }),
-            None => Err(GlassError::UnexpectedEndOfInput {
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> ParseResult {
+        let mut node = self.parse_atom()?;
+
+        loop {
+            match self.peek()? {
+                Some((Token::LBracket, span)) => {
+                    self.next()?;
+                    let index = self.parse_expression()?;
+                    self.expect(Token::RBracket)?;
+
+                    node = Node::Index {
+                        target: Box::new(node),
+                        index: Box::new(index),
+                        span,
+                    };
+                }
+                Some((Token::Dot, _)) => {
+                    self.next()?;
+
+                    let (name, span) = self.expect_identifier_spanned()?;
+
+                    node = if let Some((Token::LParen, _)) = self.peek()? {
+                        self.next()?;
+
+                        Node::MethodCall {
+                            target: Box::new(node),
+                            method: name,
+                            args: self.parse_sequence(Token::RParen)?,
+                            span,
+                        }
+                    } else {
+                        Node::FieldAccess {
+                            target: Box::new(node),
+                            field: name,
+                            span,
+                        }
+                    };
+                }
+                Some((Token::LParen, span)) => {
+                    self.next()?;
+
+                    node = Node::FunctionCall {
+                        function: Box::new(node),
+                        args: self.parse_sequence(Token::RParen)?,
+                        span,
+                    };
+                }
+                _ => break,
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn parse_function(&mut self) -> ParseResult {
+        self.expect(Token::LParen)?;
+
+        let mut signature = Vec::new();
+        let mut param_spans = Vec::new();
+        let mut param_types = Vec::new();
+
+        while let Some((token, _)) = self.peek()? {
+            if token == Token::RParen {
+                break;
+            }
+
+            let (param, span) = self.expect_identifier_spanned()?;
+            signature.push(param);
+            param_spans.push(span);
+
+            param_types.push(if let Some((Token::Colon, _)) = self.peek()? {
+                self.next()?;
+                Some(self.parse_type()?)
+            } else {
+                None
+            });
+
+            if let Some((Token::Comma, _)) = self.peek()? {
+                self.next()?;
+            } else {
+                break;
+            }
+        }
+
+        self.expect(Token::RParen)?;
+
+        let return_type = if let Some((Token::ThinArrow, _)) = self.peek()? {
+            self.next()?;
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        self.expect(Token::Arrow)?;
+
+        // func(x) => x * x is short for func(x) => { return x * x; }
+        let body = match self.peek()? {
+            Some((Token::LBrace, _)) => self.parse_block()?,
+            Some((_, span)) => Node::Block {
+                statements: vec![Node::Return {
+                    value: Box::new(self.parse_expression()?),
+                    span,
+                }],
+            },
+            None => return Err(self.end_of_input()),
+        };
+
+        Ok(Node::FunctionDefinition {
+            name: "anonymous".into(),
+            signature,
+            param_spans,
+            param_types,
+            return_type,
+            body: Rc::new(body),
+            locals: Rc::from([]),
+        })
+    }
+
+    // either a parenthesized expression or a tuple, which is told apart by a comma
+    fn parse_parenthesized(&mut self) -> ParseResult {
+        if let Some((Token::RParen, _)) = self.peek()? {
+            self.next()?;
+            return Ok(Node::Tuple { items: Vec::new() });
+        }
+
+        let node = self.parse_expression()?;
+
+        if let Some((Token::Comma, _)) = self.peek()? {
+            self.next()?;
+
+            let mut items = vec![node];
+            items.extend(self.parse_sequence(Token::RParen)?);
+
+            return Ok(Node::Tuple { items });
+        }
+
+        self.expect(Token::RParen)?;
+        Ok(node)
+    }
+
+    fn parse_list(&mut self) -> ParseResult {
+        Ok(Node::List {
+            items: self.parse_sequence(Token::RBracket)?,
+        })
+    }
+
+    fn parse_dict(&mut self) -> ParseResult {
+        let mut entries = Vec::new();
+
+        while let Some((token, _)) = self.peek()? {
+            if token == Token::RBrace {
+                break;
+            }
+
+            let key = self.parse_expression()?;
+            self.expect(Token::Colon)?;
+            entries.push((key, self.parse_expression()?));
+
+            if let Some((Token::Comma, _)) = self.peek()? {
+                self.next()?;
+            } else {
+                break;
+            }
+        }
+
+        self.expect(Token::RBrace)?;
+
+        Ok(Node::Dict { entries })
+    }
+
+    /// Parses comma separated expressions (allowing a trailing comma) up to and including `end`.
+    fn parse_sequence(&mut self, end: Token) -> Result<Vec<Node>, GlassError> {
+        let mut items = Vec::new();
+
+        while let Some((token, _)) = self.peek()? {
+            if token == end {
+                break;
+            }
+
+            items.push(self.parse_expression()?);
+
+            if let Some((Token::Comma, _)) = self.peek()? {
+                self.next()?;
+            } else {
+                break;
+            }
+        }
+
+        self.expect(end)?;
+
+        Ok(items)
+    }
+
+    // a type is a name, which is checked by the type checker, but `void` is also a keyword
+    fn parse_type(&mut self) -> Result<TypeAnnotation, GlassError> {
+        let (name, span) = match self.peek()? {
+            Some((Token::Void, span)) => {
+                self.next()?;
+                ("void".to_string(), span)
+            }
+            _ => self.expect_identifier_spanned()?,
+        };
+
+        Ok(TypeAnnotation { name, span })
+    }
+
+    fn expect_identifier_spanned(&mut self) -> Result<(String, Span), GlassError> {
+        match self.next()? {
+            Some((Token::Identifier(name), span)) => Ok((name, span)),
+            Some((_, span)) => Err(GlassError::UnexpectedToken {
+                expected: Some(Token::Identifier(String::new())),
+                src: Rc::clone(&self.src),
                 filename: Rc::clone(&self.filename),
+                span,
             }),
+            None => Err(self.end_of_input()),
         }
     }
 
@@ -173,7 +719,7 @@ impl Parser {
 
         if let Some((next_token, span)) = next {
             if next_token == token {
-                return Ok(next_token);
+                Ok(next_token)
             } else {
                 Err(GlassError::UnexpectedToken {
                     expected: Some(token),
@@ -183,9 +729,18 @@ impl Parser {
                 })
             }
         } else {
-            Err(GlassError::UnexpectedEndOfInput {
-                filename: Rc::clone(&self.filename),
-            })
+            Err(self.end_of_input())
+        }
+    }
+
+    // points just past the last thing in the file, ignoring the whitespace after it
+    fn end_of_input(&self) -> GlassError {
+        let end = self.src.trim_end().len();
+
+        GlassError::UnexpectedEndOfInput {
+            src: Rc::clone(&self.src),
+            filename: Rc::clone(&self.filename),
+            span: end..end,
         }
     }
 
@@ -209,7 +764,7 @@ impl Parser {
 
     // add code to prevent checking the same token twice
     fn check_error(&mut self, token: Token, span: Span) -> Result<(Token, Span), GlassError> {
-        return match token {
+        match token {
             Token::Error => Err(GlassError::UnknownToken {
                 src: Rc::clone(&self.src),
                 filename: Rc::clone(&self.filename),
@@ -230,6 +785,6 @@ impl Parser {
                 }),
             },
             _ => Ok((token, span)),
-        };
+        }
     }
 }
@@ -0,0 +1,227 @@
+//! Measures where the interpreter spends its time, for `--profile`.
+//!
+//! Time is charged to whatever was running since the last time something was entered or left:
+//! the innermost statement, the innermost function call and the stack of calls it is in. The
+//! total time of a function includes the functions it calls, its own time doesn't.
+
+use crate::error::line_and_column;
+use crate::node::Node;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct FunctionStats {
+    calls: u64,
+    total: Duration,
+    own: Duration,
+}
+
+#[derive(Default)]
+struct LineStats {
+    hits: u64,
+    time: Duration,
+}
+
+// a statement, by the address of its source and where it starts in it, which is only turned
+// into a line for the report since finding the line of an offset is slow
+type StatementKey = (usize, usize);
+
+pub struct Profiler {
+    // when time was last charged, see `charge`
+    last: Instant,
+    // the functions being called and when, outermost first
+    calls: Vec<(Rc<str>, Instant)>,
+    // the names of `calls` joined by semicolons, as the collapsed stacks count them
+    stack: String,
+    // the statements being run, innermost last
+    statements: Vec<StatementKey>,
+    functions: HashMap<Rc<str>, FunctionStats>,
+    lines: HashMap<StatementKey, LineStats>,
+    stacks: HashMap<String, Duration>,
+    // the filename and source of every address in a `StatementKey`
+    sources: HashMap<usize, (Rc<str>, Rc<str>)>,
+}
+
+impl Profiler {
+    /// Starts profiling, counting the top level of the script as a call to `global`.
+    pub fn new() -> Self {
+        let mut profiler = Self {
+            last: Instant::now(),
+            calls: Vec::new(),
+            stack: String::new(),
+            statements: Vec::new(),
+            functions: HashMap::new(),
+            lines: HashMap::new(),
+            stacks: HashMap::new(),
+            sources: HashMap::new(),
+        };
+
+        profiler.enter_function("global");
+        profiler
+    }
+
+    pub fn enter_function(&mut self, name: &str) {
+        self.charge();
+
+        let name: Rc<str> = Rc::from(name);
+        self.functions.entry(Rc::clone(&name)).or_default().calls += 1;
+
+        if !self.stack.is_empty() {
+            self.stack.push(';');
+        }
+
+        self.stack.push_str(&name);
+        self.calls.push((name, self.last));
+    }
+
+    pub fn exit_function(&mut self) {
+        self.charge();
+
+        let Some((name, started)) = self.calls.pop() else {
+            return;
+        };
+
+        // a recursive call is already counted by the outermost call of the function
+        if !self.calls.iter().any(|(outer, _)| *outer == name) {
+            self.functions.entry(Rc::clone(&name)).or_default().total += self.last - started;
+        }
+
+        let length = self.stack.len().saturating_sub(name.len() + 1);
+        self.stack.truncate(length);
+    }
+
+    pub fn enter_statement(&mut self, src: &Rc<str>, filename: &Rc<str>, statement: &Node) {
+        self.charge();
+
+        let address = Rc::as_ptr(src) as *const u8 as usize;
+        let key = (address, statement.start().unwrap_or(0));
+
+        self.sources
+            .entry(address)
+            .or_insert_with(|| (Rc::clone(filename), Rc::clone(src)));
+        self.lines.entry(key).or_default().hits += 1;
+        self.statements.push(key);
+    }
+
+    pub fn exit_statement(&mut self) {
+        self.charge();
+        self.statements.pop();
+    }
+
+    // gives the time since the last charge to whatever is innermost
+    fn charge(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+
+        if let Some(statement) = self.statements.last() {
+            self.lines.entry(*statement).or_default().time += elapsed;
+        }
+
+        if let Some((name, _)) = self.calls.last() {
+            self.functions.entry(Rc::clone(name)).or_default().own += elapsed;
+            // only allocates the first time a stack is seen
+            match self.stacks.get_mut(&self.stack) {
+                Some(time) => *time += elapsed,
+                None => {
+                    self.stacks.insert(self.stack.clone(), elapsed);
+                }
+            }
+        }
+    }
+
+    /// Stops profiling, leaving every call that is still running.
+    pub fn finish(&mut self) {
+        while !self.calls.is_empty() {
+            self.exit_function();
+        }
+    }
+
+    /// The time spent in every function and on every line, the slowest first.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|(a_name, a), (b_name, b)| {
+            b.total.cmp(&a.total).then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut report = String::from("Functions by total time\n");
+        report.push_str("     calls   total ms    self ms  function\n");
+
+        for (name, stats) in functions {
+            report.push_str(&format!(
+                "{:>10} {:>10.3} {:>10.3}  {}\n",
+                stats.calls,
+                milliseconds(stats.total),
+                milliseconds(stats.own),
+                name
+            ));
+        }
+
+        report.push_str("\nLines by self time\n");
+        report.push_str("      hits    self ms  line\n");
+
+        for (filename, src, line, stats) in self.line_stats() {
+            let text = src.lines().nth(line - 1).unwrap_or_default().trim();
+
+            report.push_str(&format!(
+                "{:>10} {:>10.3}  {}:{}  {}\n",
+                stats.hits,
+                milliseconds(stats.time),
+                filename,
+                line,
+                text
+            ));
+        }
+
+        report
+    }
+
+    /// The time spent in every stack of calls in microseconds, one stack per line with the
+    /// functions separated by semicolons, which is what flamegraph tools read.
+    pub fn collapsed_stacks(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+
+        stacks
+            .into_iter()
+            // a stack that took less than a microsecond would be drawn with no width anyway
+            .filter(|(_, time)| time.as_micros() > 0)
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect()
+    }
+
+    // the statements added up by the line they start on, the slowest first
+    fn line_stats(&self) -> Vec<(&Rc<str>, &Rc<str>, usize, LineStats)> {
+        let mut lines: HashMap<(usize, usize), LineStats> = HashMap::new();
+
+        for ((address, start), stats) in &self.lines {
+            let (_, src) = &self.sources[address];
+            let line = lines
+                .entry((*address, line_and_column(src, *start).0))
+                .or_default();
+
+            line.hits += stats.hits;
+            line.time += stats.time;
+        }
+
+        let mut lines: Vec<_> = lines
+            .into_iter()
+            .map(|((address, line), stats)| {
+                let (filename, src) = &self.sources[&address];
+                (filename, src, line, stats)
+            })
+            .collect();
+
+        lines.sort_by(|a, b| {
+            b.3.time
+                .cmp(&a.3.time)
+                .then_with(|| (a.0, a.2).cmp(&(b.0, b.2)))
+        });
+        lines
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,70 @@
+//! Prints errors and warnings, either for people to read or as JSON for tools like CI to parse.
+//!
+//! In the JSON format every diagnostic is an object on its own line of stderr, with the line and
+//! column counted from 1 and the span in byte offsets. Anything that isn't known, like the
+//! position of an error that doesn't point into a script, is null.
+
+use crate::error::{line_and_column, GlassError};
+use clap::ValueEnum;
+use serde_json::{json, Value as Json};
+use std::cell::Cell;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+thread_local! {
+    static FORMAT: Cell<ErrorFormat> = const { Cell::new(ErrorFormat::Text) };
+}
+
+pub fn set_format(format: ErrorFormat) {
+    FORMAT.with(|current| current.set(format));
+}
+
+/// An error that stopped glass.
+pub fn fatal(error: &GlassError) {
+    report(error, "error", "Fatal exception during execution -> ");
+}
+
+/// An error that doesn't stop glass on its own, like a type error found by `glass check`.
+pub fn error(error: &GlassError) {
+    report(error, "error", "");
+}
+
+pub fn warning(warning: &GlassError) {
+    report(warning, "warning", "Warning -> ");
+}
+
+fn report(error: &GlassError, severity: &str, prefix: &str) {
+    match FORMAT.with(Cell::get) {
+        ErrorFormat::Text => eprintln!("{}{}", prefix, error),
+        ErrorFormat::Json => eprintln!("{}", to_json(error, severity)),
+    }
+}
+
+fn to_json(error: &GlassError, severity: &str) -> Json {
+    let (line, column, span) = match error.location() {
+        Some((src, _, span)) => {
+            let (line, column) = line_and_column(src, span.start);
+            (
+                Some(line),
+                Some(column),
+                Some(json!({ "start": span.start, "end": span.end })),
+            )
+        }
+        None => (None, None, None),
+    };
+
+    json!({
+        "severity": severity,
+        "kind": error.kind(),
+        "message": error.message(),
+        "file": error.filename().map(|filename| filename.to_string()),
+        "line": line,
+        "column": column,
+        "span": span,
+    })
+}
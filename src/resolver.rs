@@ -0,0 +1,401 @@
+//! Binds every variable to the scope that declares it before the script is run, so that the
+//! interpreter and the VM can read local variables from slots instead of looking them up by
+//! name, and reports mistakes that would otherwise only be found at runtime, if at all.
+//!
+//! Like at runtime, a variable is declared in the function it is assigned in, and a function
+//! can read the variables of the functions around it until it assigns its own variable with the
+//! same name. So a variable is resolved to the slot of every enclosing function that declares
+//! it, which are tried in order before falling back to a global variable.
+
+use crate::error::GlassError;
+use crate::lexer::Token;
+use crate::node::{Node, Slot};
+use logos::Span;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+struct Variable {
+    name: String,
+    // where the variable is first assigned, which is only known for variables declared by an
+    // assignment. parameters, loop variables and structs are not checked for being unused
+    declared_at: Option<Span>,
+    used: bool,
+    // defined before the script started, like the builtins
+    predefined: bool,
+}
+
+#[derive(Default)]
+struct Scope {
+    // in a function, the index of a variable is its slot
+    variables: Vec<Variable>,
+    indices: HashMap<String, usize>,
+    is_function: bool,
+    loops: usize,
+}
+
+impl Scope {
+    fn declare(&mut self, name: &str, declared_at: Option<Span>) {
+        if !self.indices.contains_key(name) {
+            self.indices.insert(name.into(), self.variables.len());
+            self.variables.push(Variable {
+                name: name.into(),
+                declared_at,
+                used: false,
+                predefined: false,
+            });
+        }
+    }
+}
+
+/// What the resolver found. The script can't be run if there are any errors, but it is resolved
+/// as far as it can be, so that every error is found and not just the first.
+pub struct Resolved {
+    pub errors: Vec<GlassError>,
+    pub warnings: Vec<GlassError>,
+}
+
+pub struct Resolver {
+    src: Rc<str>,
+    filename: Rc<str>,
+    scopes: Vec<Scope>,
+    errors: Vec<GlassError>,
+    warnings: Vec<GlassError>,
+}
+
+impl Resolver {
+    pub fn new(src: Rc<str>, filename: Rc<str>) -> Self {
+        Self {
+            src,
+            filename,
+            scopes: Vec::new(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Resolves every variable in `ast`, returning the errors and warnings found along the way.
+    /// `globals` are the variables that already exist when the script starts, like the builtins.
+    pub fn resolve<'a>(
+        mut self,
+        ast: &mut Node,
+        globals: impl Iterator<Item = &'a String>,
+    ) -> Resolved {
+        let mut scope = Scope::default();
+
+        for name in globals {
+            scope.declare(name, None);
+        }
+
+        for variable in &mut scope.variables {
+            variable.predefined = true;
+        }
+
+        self.scopes.push(scope);
+        self.declare_all(ast);
+        self.resolve_node(ast);
+
+        Resolved {
+            errors: self.errors,
+            warnings: self.warnings,
+        }
+    }
+
+    fn current(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("Resolver has no scope")
+    }
+
+    /// Declares every variable assigned in a scope up front, so that a variable used before the
+    /// assignment that declares it still gets its slot. Functions have their own scope, so
+    /// their bodies are skipped.
+    fn declare_all(&mut self, node: &Node) {
+        match node {
+            Node::Assignment { left, .. } => {
+                if let Node::Identifier { name, span, .. } = &**left {
+                    self.current().declare(name, Some(span.clone()));
+                }
+            }
+            Node::For { variable, body, .. } | Node::ForEach { variable, body, .. } => {
+                self.current().declare(variable, None);
+                self.declare_all(body);
+            }
+            Node::StructDefinition { name, .. } => self.current().declare(name, None),
+            Node::Try {
+                variable: Some(variable),
+                ..
+            } => {
+                self.current().declare(variable, None);
+
+                for child in node.children() {
+                    self.declare_all(child);
+                }
+            }
+            Node::FunctionDefinition { .. } => {}
+            node => {
+                for child in node.children() {
+                    self.declare_all(child);
+                }
+            }
+        }
+    }
+
+    fn resolve_node(&mut self, node: &mut Node) {
+        match node {
+            Node::Identifier { name, span, slots } => *slots = self.lookup(name, span),
+            Node::Assignment {
+                op, left, right, ..
+            } => {
+                match &mut **left {
+                    Node::Identifier { name, span, slots } => {
+                        // a plain assignment doesn't read the variable, so it can't be undefined
+                        *slots = match op {
+                            Token::Equal => self.find(name, false).unwrap_or_default(),
+                            _ => self.lookup(name, span),
+                        };
+
+                        self.check_shadowing(name, span);
+                    }
+                    left => self.resolve_node(left),
+                }
+
+                self.resolve_node(right);
+            }
+            Node::FunctionDefinition {
+                signature,
+                body,
+                locals,
+                ..
+            } => {
+                let mut scope = Scope {
+                    is_function: true,
+                    ..Scope::default()
+                };
+
+                // the parameters are the first slots
+                for param in signature.iter() {
+                    scope.declare(param, None);
+                }
+
+                self.scopes.push(scope);
+
+                if let Some(body) = Rc::get_mut(body) {
+                    self.declare_all(body);
+                    self.resolve_node(body);
+                }
+
+                let scope = self.scopes.pop().expect("Resolver has no scope");
+                *locals = scope
+                    .variables
+                    .iter()
+                    .map(|variable| variable.name.clone())
+                    .collect();
+
+                for variable in scope.variables {
+                    if let (Some(span), false) = (variable.declared_at, variable.used) {
+                        if !variable.name.starts_with('_') {
+                            let warning = GlassError::UnusedVariable {
+                                name: variable.name,
+                            };
+                            self.warnings.push(self.spanned(warning, span));
+                        }
+                    }
+                }
+            }
+            Node::StructDefinition {
+                name,
+                slot,
+                methods,
+                ..
+            } => {
+                for (_, method) in methods {
+                    self.resolve_node(method);
+                }
+
+                *slot = self.local_slot(name);
+            }
+            Node::For {
+                variable,
+                slot,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.resolve_node(start);
+                self.resolve_node(end);
+                *slot = self.local_slot(variable);
+                self.resolve_loop_body(body);
+            }
+            Node::ForEach {
+                variable,
+                slot,
+                iterable,
+                body,
+                ..
+            } => {
+                self.resolve_node(iterable);
+                *slot = self.local_slot(variable);
+                self.resolve_loop_body(body);
+            }
+            Node::While { condition, body } => {
+                self.resolve_node(condition);
+                self.resolve_loop_body(body);
+            }
+            Node::Try {
+                body,
+                variable,
+                slot,
+                catch_body,
+                finally_body,
+                ..
+            } => {
+                self.resolve_node(body);
+
+                if let Some(variable) = variable {
+                    *slot = self.local_slot(variable);
+                }
+
+                for child in catch_body.iter_mut().chain(finally_body) {
+                    self.resolve_node(child);
+                }
+            }
+            Node::Return { value, span } => {
+                if !self.current().is_function {
+                    self.errors
+                        .push(self.spanned(GlassError::ReturnOutsideFunction, span.clone()));
+                }
+
+                self.resolve_node(value);
+            }
+            Node::Break { span } if self.current().loops == 0 => {
+                self.errors.push(self.outside_loop("break", span.clone()))
+            }
+            Node::Continue { span } if self.current().loops == 0 => self
+                .errors
+                .push(self.outside_loop("continue", span.clone())),
+            node => {
+                for child in node.children_mut() {
+                    self.resolve_node(child);
+                }
+            }
+        }
+    }
+
+    fn resolve_loop_body(&mut self, body: &mut Node) {
+        self.current().loops += 1;
+        self.resolve_node(body);
+        self.current().loops -= 1;
+    }
+
+    /// Resolves a variable that is read, which has to be declared somewhere.
+    fn lookup(&mut self, name: &str, span: &Span) -> Vec<Slot> {
+        let slots = match self.find(name, true) {
+            Some(slots) => slots,
+            None => {
+                let error = GlassError::UndefinedVariable { name: name.into() };
+                self.errors.push(self.spanned(error, span.clone()));
+                return Vec::new();
+            }
+        };
+
+        // only a variable of the current scope can be used before it is defined, since a
+        // function can be called after the variables around it are defined
+        let declared_here = self.current().indices.get(name).copied();
+        let declared_outside = self.scopes[..self.scopes.len() - 1]
+            .iter()
+            .any(|scope| scope.indices.contains_key(name));
+
+        if let (Some(index), false) = (declared_here, declared_outside) {
+            let declared_at = &self.current().variables[index].declared_at;
+
+            if declared_at
+                .as_ref()
+                .is_some_and(|declared_at| declared_at.start >= span.start)
+            {
+                let warning = GlassError::UsedBeforeDefinition { name: name.into() };
+                self.warnings.push(self.spanned(warning, span.clone()));
+            }
+        }
+
+        slots
+    }
+
+    /// Finds the slots of every function that declares a variable, nearest first, or `None` if
+    /// no scope declares it at all.
+    fn find(&mut self, name: &str, mark_used: bool) -> Option<Vec<Slot>> {
+        let mut slots = Vec::new();
+        let mut found = false;
+        let mut depth = 0;
+
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(&index) = scope.indices.get(name) {
+                found = true;
+                scope.variables[index].used |= mark_used;
+
+                if scope.is_function {
+                    slots.push(Slot { depth, index });
+                }
+            }
+
+            if scope.is_function {
+                depth += 1;
+            }
+        }
+
+        found.then_some(slots)
+    }
+
+    fn local_slot(&mut self, name: &str) -> Option<usize> {
+        let scope = self.current();
+
+        if scope.is_function {
+            scope.indices.get(name).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Warns when the assignment declaring a variable in a function hides a variable from
+    /// outside of it, since the outer variable is left unchanged by it.
+    fn check_shadowing(&mut self, name: &str, span: &Span) {
+        let scope = self.current();
+
+        let declares = match scope.indices.get(name) {
+            Some(&index) => scope.variables[index].declared_at.as_ref() == Some(span),
+            None => false,
+        };
+
+        if !scope.is_function || !declares {
+            return;
+        }
+
+        // builtins are meant to be replaced, so they aren't counted
+        let shadows = self.scopes[..self.scopes.len() - 1].iter().any(|scope| {
+            scope
+                .indices
+                .get(name)
+                .is_some_and(|&index| !scope.variables[index].predefined)
+        });
+
+        if shadows {
+            let warning = GlassError::ShadowedVariable { name: name.into() };
+            self.warnings.push(self.spanned(warning, span.clone()));
+        }
+    }
+
+    fn outside_loop(&self, statement: &str, span: Span) -> GlassError {
+        let error = GlassError::ControlFlowOutsideLoop {
+            statement: statement.into(),
+        };
+
+        self.spanned(error, span)
+    }
+
+    fn spanned(&self, error: GlassError, span: Span) -> GlassError {
+        GlassError::Spanned {
+            error: Box::new(error),
+            src: Rc::clone(&self.src),
+            filename: Rc::clone(&self.filename),
+            span,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Limits on what a script can do, for running scripts that aren't trusted.
+//!
+//! The limits apply to everything run on the thread once they are set, and sizes are checked
+//! before a value is made, so that `"x" * 1e12` fails before anything tries to allocate it.
+//! Every limit except the recursion limit is off by default, which is always on so that deep
+//! recursion fails with an error the script can catch instead of overflowing the stack.
+
+use crate::error::GlassError;
+use crate::value::Value;
+use clap::ValueEnum;
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::time::{Duration, Instant};
+
+// looking at the clock is slow compared to a step, so the timeout is only checked this often
+const STEPS_PER_CLOCK_CHECK: u64 = 1024;
+
+/// How many function calls can be nested when no limit is given. Scripts are run on a thread
+/// with a stack big enough for this many calls in a debug build.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// The most nodes the interpreter can evaluate, or instructions the VM can run.
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    /// The most bytes the strings, lists and dictionaries a script makes can take up in total.
+    pub max_memory: Option<usize>,
+    /// The most function calls that can be nested inside of each other, which is
+    /// `DEFAULT_MAX_DEPTH` if it isn't set.
+    pub max_depth: Option<usize>,
+    pub denied: Vec<Capability>,
+}
+
+/// Something outside of the script that builtins can reach.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Capability {
+    /// Environment variables and the arguments the script was run with
+    Environment,
+    /// Stopping or starting processes, like exit()
+    Process,
+}
+
+impl Capability {
+    fn description(self) -> &'static str {
+        match self {
+            Capability::Environment => "environment",
+            Capability::Process => "process",
+        }
+    }
+}
+
+thread_local! {
+    static LIMITS: RefCell<Limits> = RefCell::new(Limits::default());
+    // kept apart from the other limits, since they are read on every step
+    static MAX_STEPS: Cell<u64> = const { Cell::new(u64::MAX) };
+    static STEPS: Cell<u64> = const { Cell::new(0) };
+    static STARTED: Cell<Option<Instant>> = const { Cell::new(None) };
+    // the bytes of every value made so far, which are never taken off again since values
+    // aren't tracked once they are made
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Sets the limits for everything run on this thread from now on, starting the timeout.
+pub fn set_limits(limits: Limits) {
+    MAX_STEPS.with(|max| max.set(limits.max_steps.unwrap_or(u64::MAX)));
+    STEPS.with(|steps| steps.set(0));
+    STARTED.with(|started| started.set(Some(Instant::now())));
+    ALLOCATED.with(|allocated| allocated.set(0));
+    LIMITS.with(|current| *current.borrow_mut() = limits);
+}
+
+/// Counts a step of the script, failing once it has taken too many or run for too long.
+pub fn step() -> Result<(), GlassError> {
+    let steps = STEPS.with(|steps| {
+        let count = steps.get() + 1;
+        steps.set(count);
+        count
+    });
+
+    let max_steps = MAX_STEPS.with(Cell::get);
+
+    if steps > max_steps {
+        return Err(GlassError::StepLimitExceeded { limit: max_steps });
+    }
+
+    if !steps.is_multiple_of(STEPS_PER_CLOCK_CHECK) {
+        return Ok(());
+    }
+
+    let timeout = LIMITS.with(|limits| limits.borrow().timeout);
+
+    match (timeout, STARTED.with(Cell::get)) {
+        (Some(timeout), Some(started)) if started.elapsed() > timeout => {
+            Err(GlassError::TimeLimitExceeded {
+                limit_ms: timeout.as_millis(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails if a function call `depth` calls deep is too deep.
+pub fn check_depth(depth: usize) -> Result<(), GlassError> {
+    let limit = LIMITS.with(|limits| limits.borrow().max_depth.unwrap_or(DEFAULT_MAX_DEPTH));
+
+    if depth > limit {
+        return Err(GlassError::RecursionLimitExceeded { limit });
+    }
+
+    Ok(())
+}
+
+/// Counts a new string of `len` bytes towards the memory limit, failing instead if it would go
+/// over it. This is called before the string is made.
+pub fn check_str(len: usize) -> Result<(), GlassError> {
+    allocate(len)
+}
+
+/// Counts `len` new list, tuple, set or dictionary items towards the memory limit, failing
+/// instead if they would go over it.
+pub fn check_items(len: usize) -> Result<(), GlassError> {
+    allocate(len.saturating_mul(mem::size_of::<Value>()))
+}
+
+/// Fails if text of `len` bytes that is being formatted would go over the memory limit. The
+/// text isn't counted, since it is only kept if it becomes a string, which is counted then.
+pub fn check_formatted(len: usize) -> Result<(), GlassError> {
+    over_limit(len).map(|_| ())
+}
+
+fn allocate(size: usize) -> Result<(), GlassError> {
+    let total = over_limit(size)?;
+    ALLOCATED.with(|allocated| allocated.set(total));
+
+    Ok(())
+}
+
+// the bytes allocated so far with `size` more, or an error if that is over the limit
+fn over_limit(size: usize) -> Result<usize, GlassError> {
+    let total = ALLOCATED.with(Cell::get).saturating_add(size);
+
+    match LIMITS.with(|limits| limits.borrow().max_memory) {
+        Some(limit) if total > limit => Err(GlassError::MemoryLimitExceeded { size: total, limit }),
+        _ => Ok(total),
+    }
+}
+
+/// Fails if `function` needs a capability that is denied.
+pub fn check_capability(function: &str, capability: Capability) -> Result<(), GlassError> {
+    let denied = LIMITS.with(|limits| limits.borrow().denied.contains(&capability));
+
+    if denied {
+        return Err(GlassError::CapabilityDisabled {
+            function: function.into(),
+            capability: capability.description().into(),
+        });
+    }
+
+    Ok(())
+}
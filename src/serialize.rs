@@ -0,0 +1,640 @@
+//! The binary format of compiled `.glassc` files.
+//!
+//! A file starts with a header identifying the build of glass that wrote it and the source it
+//! was compiled from, followed by the top level chunk. All integers are little endian, and
+//! strings and lists are prefixed with their length as a `u32`.
+
+use crate::bytecode::{BinOp, Chunk, Constant, FunctionProto, Instruction, StructProto};
+use crate::node::Slot;
+use git_version::git_version;
+use std::rc::Rc;
+
+const MAGIC: &[u8; 6] = b"GLASSC";
+// bump whenever the layout below changes, so old files are rejected instead of misread
+const FORMAT_VERSION: u32 = 3;
+
+pub const GLASS_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_REVISION: &str = git_version!(fallback = "<unknown>");
+
+/// Identifies the build of glass a file was compiled with and the source it was compiled from.
+#[derive(Debug)]
+pub struct Header {
+    pub glass_version: String,
+    pub git_revision: String,
+    pub source_path: String,
+    pub source_hash: u64,
+}
+
+impl Header {
+    pub fn new(source_path: String, src: &str) -> Self {
+        Self {
+            glass_version: GLASS_VERSION.into(),
+            git_revision: GIT_REVISION.into(),
+            source_path,
+            source_hash: hash_source(src),
+        }
+    }
+
+    /// Whether the file was written by this exact build. Bytecode is only guaranteed to be
+    /// compatible with the build that compiled it, so anything else is rejected.
+    pub fn is_current(&self) -> bool {
+        self.glass_version == GLASS_VERSION && self.git_revision == GIT_REVISION
+    }
+}
+
+/// FNV-1a, which unlike the std hashers is guaranteed to be stable between builds.
+pub fn hash_source(src: &str) -> u64 {
+    src.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub fn serialize(header: &Header, chunk: &Chunk) -> Vec<u8> {
+    let mut writer = Writer::default();
+
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.u32(FORMAT_VERSION);
+    writer.str(&header.glass_version);
+    writer.str(&header.git_revision);
+    writer.str(&header.source_path);
+    writer.u64(header.source_hash);
+    writer.chunk(chunk);
+
+    writer.bytes
+}
+
+/// Reads just the header of a file, so that it can be checked before the chunk is loaded.
+pub fn read_header(bytes: &[u8]) -> Result<Header, String> {
+    Reader { bytes, pos: 0 }.header()
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<(Header, Chunk), String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let header = reader.header()?;
+    let chunk = reader.chunk(0)?;
+
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "{} unexpected bytes after the end of the chunk",
+            bytes.len() - reader.pos
+        ));
+    }
+
+    Ok((header, chunk))
+}
+
+fn bin_op_code(op: BinOp) -> u8 {
+    match op {
+        BinOp::Add => 0,
+        BinOp::Sub => 1,
+        BinOp::Mul => 2,
+        BinOp::Div => 3,
+        BinOp::Rem => 4,
+        BinOp::Pow => 5,
+        BinOp::Eq => 6,
+        BinOp::Ne => 7,
+        BinOp::Lt => 8,
+        BinOp::Gt => 9,
+        BinOp::Le => 10,
+        BinOp::Ge => 11,
+        BinOp::And => 12,
+        BinOp::Or => 13,
+    }
+}
+
+fn bin_op_from_code(code: u8) -> Option<BinOp> {
+    Some(match code {
+        0 => BinOp::Add,
+        1 => BinOp::Sub,
+        2 => BinOp::Mul,
+        3 => BinOp::Div,
+        4 => BinOp::Rem,
+        5 => BinOp::Pow,
+        6 => BinOp::Eq,
+        7 => BinOp::Ne,
+        8 => BinOp::Lt,
+        9 => BinOp::Gt,
+        10 => BinOp::Le,
+        11 => BinOp::Ge,
+        12 => BinOp::And,
+        13 => BinOp::Or,
+        _ => return None,
+    })
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn str(&mut self, str: &str) {
+        self.len(str.len());
+        self.bytes.extend_from_slice(str.as_bytes());
+    }
+
+    fn strs(&mut self, strs: &[String]) {
+        self.len(strs.len());
+
+        for str in strs {
+            self.str(str);
+        }
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.len(chunk.constants.len());
+
+        for constant in &chunk.constants {
+            self.constant(constant);
+        }
+
+        self.len(chunk.code.len());
+
+        for instruction in &chunk.code {
+            self.instruction(*instruction);
+        }
+
+        self.len(chunk.spans.len());
+
+        for (instruction, span) in &chunk.spans {
+            self.u32(*instruction);
+            self.len(span.start);
+            self.len(span.end);
+        }
+
+        self.len(chunk.slots.len());
+
+        for slots in &chunk.slots {
+            self.len(slots.len());
+
+            for slot in slots {
+                self.len(slot.depth);
+                self.len(slot.index);
+            }
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Num(num) => {
+                self.u8(0);
+                self.u64(num.to_bits());
+            }
+            Constant::Str(str) => {
+                self.u8(1);
+                self.str(str);
+            }
+            Constant::Function(function) => {
+                self.u8(2);
+                self.str(&function.name);
+                self.strs(&function.params);
+                self.strs(&function.locals);
+                self.chunk(&function.chunk);
+            }
+            Constant::Struct(def) => {
+                self.u8(3);
+                self.str(&def.name);
+                self.strs(&def.fields);
+                self.strs(&def.methods);
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Constant(index) => {
+                self.u8(0);
+                self.u32(index);
+            }
+            Instruction::True => self.u8(1),
+            Instruction::False => self.u8(2),
+            Instruction::Void => self.u8(3),
+            Instruction::Pop => self.u8(4),
+            Instruction::Dup => self.u8(5),
+            Instruction::Dup2 => self.u8(6),
+            Instruction::Load { name, slots } => {
+                self.u8(7);
+                self.u32(name);
+                self.u32(slots);
+            }
+            Instruction::Store(name) => {
+                self.u8(8);
+                self.u32(name);
+            }
+            Instruction::BinaryOp(op) => {
+                self.u8(9);
+                self.u8(bin_op_code(op));
+            }
+            Instruction::Negate => self.u8(10),
+            Instruction::Not => self.u8(11),
+            Instruction::List(count) => {
+                self.u8(12);
+                self.u32(count);
+            }
+            Instruction::Tuple(count) => {
+                self.u8(13);
+                self.u32(count);
+            }
+            Instruction::Dict(count) => {
+                self.u8(14);
+                self.u32(count);
+            }
+            Instruction::Index => self.u8(15),
+            Instruction::SetIndex => self.u8(16),
+            Instruction::GetField(field) => {
+                self.u8(17);
+                self.u32(field);
+            }
+            Instruction::SetField(field) => {
+                self.u8(18);
+                self.u32(field);
+            }
+            Instruction::Call(count) => {
+                self.u8(19);
+                self.u32(count);
+            }
+            Instruction::CallMethod(method, count) => {
+                self.u8(20);
+                self.u32(method);
+                self.u32(count);
+            }
+            Instruction::Closure(index) => {
+                self.u8(21);
+                self.u32(index);
+            }
+            Instruction::Struct(index) => {
+                self.u8(22);
+                self.u32(index);
+            }
+            Instruction::Jump(target) => {
+                self.u8(23);
+                self.u32(target);
+            }
+            Instruction::JumpIfFalse(target) => {
+                self.u8(24);
+                self.u32(target);
+            }
+            Instruction::AndShortCircuit(target) => {
+                self.u8(25);
+                self.u32(target);
+            }
+            Instruction::OrShortCircuit(target) => {
+                self.u8(26);
+                self.u32(target);
+            }
+            Instruction::RangeInit { inclusive } => {
+                self.u8(27);
+                self.bool(inclusive);
+            }
+            Instruction::RangeNext { inclusive, exit } => {
+                self.u8(28);
+                self.bool(inclusive);
+                self.u32(exit);
+            }
+            Instruction::RangeStep => self.u8(29),
+            Instruction::IterInit => self.u8(30),
+            Instruction::IterNext { exit } => {
+                self.u8(31);
+                self.u32(exit);
+            }
+            Instruction::Return => self.u8(32),
+            Instruction::OutsideLoop { is_break } => {
+                self.u8(33);
+                self.bool(is_break);
+            }
+            Instruction::Try(target) => {
+                self.u8(34);
+                self.u32(target);
+            }
+            Instruction::TryFinally(target) => {
+                self.u8(35);
+                self.u32(target);
+            }
+            Instruction::EndTry => self.u8(36),
+            Instruction::Throw => self.u8(37),
+            Instruction::Rethrow => self.u8(38),
+            Instruction::StoreSlot(index) => {
+                self.u8(39);
+                self.u32(index);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        match self.bytes.get(self.pos..self.pos.saturating_add(len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err("unexpected end of file".into()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(format!("invalid boolean {}", byte)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+
+        match std::str::from_utf8(self.take(len)?) {
+            Ok(str) => Ok(str.into()),
+            Err(_) => Err("invalid utf-8 in string".into()),
+        }
+    }
+
+    fn strs(&mut self) -> Result<Vec<String>, String> {
+        let len = self.u32()?;
+        (0..len).map(|_| self.str()).collect()
+    }
+
+    fn header(&mut self) -> Result<Header, String> {
+        if self.take(MAGIC.len())? != MAGIC {
+            return Err("not a compiled glass file".into());
+        }
+
+        let format_version = self.u32()?;
+
+        if format_version != FORMAT_VERSION {
+            return Err(format!(
+                "unsupported format version {} (expected {})",
+                format_version, FORMAT_VERSION
+            ));
+        }
+
+        Ok(Header {
+            glass_version: self.str()?,
+            git_revision: self.str()?,
+            source_path: self.str()?,
+            source_hash: self.u64()?,
+        })
+    }
+
+    fn slots(&mut self) -> Result<Vec<Slot>, String> {
+        let len = self.u32()?;
+
+        (0..len)
+            .map(|_| {
+                Ok(Slot {
+                    depth: self.u32()? as usize,
+                    index: self.u32()? as usize,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads a chunk that runs in a call with `locals` slots, which is 0 for the top level.
+    fn chunk(&mut self, locals: usize) -> Result<Chunk, String> {
+        let constant_count = self.u32()?;
+        let constants = (0..constant_count)
+            .map(|_| self.constant())
+            .collect::<Result<_, _>>()?;
+
+        let code_len = self.u32()?;
+        let code = (0..code_len)
+            .map(|_| self.instruction())
+            .collect::<Result<_, _>>()?;
+
+        let span_count = self.u32()?;
+        let spans = (0..span_count)
+            .map(|_| Ok((self.u32()?, self.u32()? as usize..self.u32()? as usize)))
+            .collect::<Result<_, String>>()?;
+
+        let slot_count = self.u32()?;
+        let slots = (0..slot_count)
+            .map(|_| self.slots())
+            .collect::<Result<_, _>>()?;
+
+        let chunk = Chunk {
+            code,
+            constants,
+            spans,
+            slots,
+        };
+        validate(&chunk, locals)?;
+
+        Ok(chunk)
+    }
+
+    fn constant(&mut self) -> Result<Constant, String> {
+        Ok(match self.u8()? {
+            0 => Constant::Num(f64::from_bits(self.u64()?)),
+            1 => Constant::Str(self.str()?),
+            2 => {
+                let name = self.str()?;
+                let params = self.strs()?;
+                let locals: Rc<[String]> = Rc::from(self.strs()?);
+
+                // the arguments of a call are put in the first slots
+                if params.len() > locals.len() {
+                    return Err(format!(
+                        "function '{}' has fewer slots than parameters",
+                        name
+                    ));
+                }
+
+                Constant::Function(Rc::new(FunctionProto {
+                    chunk: self.chunk(locals.len())?,
+                    name,
+                    params,
+                    locals,
+                }))
+            }
+            3 => Constant::Struct(Rc::new(StructProto {
+                name: self.str()?,
+                fields: self.strs()?,
+                methods: self.strs()?,
+            })),
+            tag => return Err(format!("invalid constant tag {}", tag)),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, String> {
+        Ok(match self.u8()? {
+            0 => Instruction::Constant(self.u32()?),
+            1 => Instruction::True,
+            2 => Instruction::False,
+            3 => Instruction::Void,
+            4 => Instruction::Pop,
+            5 => Instruction::Dup,
+            6 => Instruction::Dup2,
+            7 => Instruction::Load {
+                name: self.u32()?,
+                slots: self.u32()?,
+            },
+            8 => Instruction::Store(self.u32()?),
+            9 => {
+                let code = self.u8()?;
+
+                match bin_op_from_code(code) {
+                    Some(op) => Instruction::BinaryOp(op),
+                    None => return Err(format!("invalid binary operator {}", code)),
+                }
+            }
+            10 => Instruction::Negate,
+            11 => Instruction::Not,
+            12 => Instruction::List(self.u32()?),
+            13 => Instruction::Tuple(self.u32()?),
+            14 => Instruction::Dict(self.u32()?),
+            15 => Instruction::Index,
+            16 => Instruction::SetIndex,
+            17 => Instruction::GetField(self.u32()?),
+            18 => Instruction::SetField(self.u32()?),
+            19 => Instruction::Call(self.u32()?),
+            20 => Instruction::CallMethod(self.u32()?, self.u32()?),
+            21 => Instruction::Closure(self.u32()?),
+            22 => Instruction::Struct(self.u32()?),
+            23 => Instruction::Jump(self.u32()?),
+            24 => Instruction::JumpIfFalse(self.u32()?),
+            25 => Instruction::AndShortCircuit(self.u32()?),
+            26 => Instruction::OrShortCircuit(self.u32()?),
+            27 => Instruction::RangeInit {
+                inclusive: self.bool()?,
+            },
+            28 => Instruction::RangeNext {
+                inclusive: self.bool()?,
+                exit: self.u32()?,
+            },
+            29 => Instruction::RangeStep,
+            30 => Instruction::IterInit,
+            31 => Instruction::IterNext { exit: self.u32()? },
+            32 => Instruction::Return,
+            33 => Instruction::OutsideLoop {
+                is_break: self.bool()?,
+            },
+            34 => Instruction::Try(self.u32()?),
+            35 => Instruction::TryFinally(self.u32()?),
+            36 => Instruction::EndTry,
+            37 => Instruction::Throw,
+            38 => Instruction::Rethrow,
+            39 => Instruction::StoreSlot(self.u32()?),
+            opcode => return Err(format!("invalid opcode {}", opcode)),
+        })
+    }
+}
+
+/// Checks that every operand of a loaded chunk refers to a constant of the right kind, every
+/// jump stays inside the chunk and every assigned slot is one of the `locals` slots of its call,
+/// since the VM trusts the compiler to only emit valid chunks.
+fn validate(chunk: &Chunk, locals: usize) -> Result<(), String> {
+    let check_constant = |index: u32, expected: &str| {
+        let valid = match chunk.constants.get(index as usize) {
+            Some(Constant::Num(_)) => expected == "value",
+            Some(Constant::Str(_)) => expected == "value" || expected == "name",
+            Some(Constant::Function(_)) => expected == "function",
+            Some(Constant::Struct(_)) => expected == "struct",
+            None => false,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("constant {} is not a valid {}", index, expected))
+        }
+    };
+
+    let check_jump = |target: u32| {
+        if target as usize <= chunk.code.len() {
+            Ok(())
+        } else {
+            Err(format!("jump target {} is out of bounds", target))
+        }
+    };
+
+    for instruction in &chunk.code {
+        match *instruction {
+            Instruction::Constant(index) => check_constant(index, "value")?,
+            Instruction::Load { name, slots } => {
+                check_constant(name, "name")?;
+
+                if slots as usize >= chunk.slots.len() {
+                    return Err(format!("slot list {} is out of bounds", slots));
+                }
+            }
+            Instruction::StoreSlot(index) if index as usize >= locals => {
+                return Err(format!("slot {} is out of bounds", index));
+            }
+            Instruction::Store(name)
+            | Instruction::GetField(name)
+            | Instruction::SetField(name)
+            | Instruction::CallMethod(name, _) => check_constant(name, "name")?,
+            Instruction::Closure(index) => check_constant(index, "function")?,
+            Instruction::Struct(index) => check_constant(index, "struct")?,
+            Instruction::Jump(target)
+            | Instruction::JumpIfFalse(target)
+            | Instruction::AndShortCircuit(target)
+            | Instruction::OrShortCircuit(target)
+            | Instruction::Try(target)
+            | Instruction::TryFinally(target)
+            | Instruction::RangeNext { exit: target, .. }
+            | Instruction::IterNext { exit: target } => check_jump(target)?,
+            _ => {}
+        }
+    }
+
+    // spans are looked up with a binary search, so they have to stay in order
+    let mut previous = None;
+
+    for (instruction, span) in &chunk.spans {
+        if *instruction as usize >= chunk.code.len() || previous >= Some(*instruction) {
+            return Err(format!(
+                "span of instruction {} is out of place",
+                instruction
+            ));
+        }
+
+        if span.start > span.end {
+            return Err(format!("span {:?} is invalid", span));
+        }
+
+        previous = Some(*instruction);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A struct declared with `struct Name { fields... }`. Calling it constructs an instance,
+/// taking the initial value of each field in the order they were declared.
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: HashMap<String, Value>,
+    // the fields of builtin modules like `math` can't be assigned to
+    pub read_only: bool,
+}
+
+impl StructDef {
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|name| name == field)
+    }
+}
+
+/// An instance of a struct. Like lists and dictionaries, instances are shared by reference.
+pub struct Struct {
+    pub def: Rc<StructDef>,
+    pub values: Vec<Value>,
+}
+
+impl Struct {
+    pub fn get_field(&self, field: &str) -> Option<Value> {
+        self.def
+            .field_index(field)
+            .map(|index| self.values[index].clone())
+    }
+
+    /// Sets the value of an existing field, returning whether the struct has that field.
+    pub fn set_field(&mut self, field: &str, value: Value) -> bool {
+        match self.def.field_index(field) {
+            Some(index) => {
+                self.values[index] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.def.fields.iter().zip(&self.values)
+    }
+}
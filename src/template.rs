@@ -0,0 +1,194 @@
+//! The templates of `string.format`, like `"{name}: {:>8.2}".format(total, {"name": "sum"})`.
+//!
+//! A field is `{}` for the next argument, `{1}` for the argument at that index or `{name}` for
+//! that key of a dictionary argument or field of a struct argument. It can end with a spec after
+//! a colon, `[[fill]align][width][.precision]`, where the align is `<`, `>` or `^`. Widths are
+//! counted in characters, and numbers are aligned right by default, everything else left.
+//! `{{` and `}}` are literal braces.
+
+use crate::error::GlassError;
+use crate::sandbox;
+use crate::value::Value;
+
+// the standard library panics when formatting a number with more decimals than this
+const MAX_PRECISION: usize = u16::MAX as usize;
+
+struct Spec {
+    fill: char,
+    align: Option<char>,
+    width: usize,
+    precision: Option<usize>,
+}
+
+fn template_error(message: String) -> GlassError {
+    GlassError::InvalidArgument {
+        function: "format".into(),
+        message,
+    }
+}
+
+/// Fills in the fields of `template` with `args`. The result counts towards the memory limit,
+/// since it becomes a string.
+pub fn format(template: &str, args: &[Value]) -> Result<String, GlassError> {
+    let mut result = String::new();
+    let mut next = 0;
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        result.push_str(&rest[..start]);
+        let brace = &rest[start..];
+
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            result.push_str(&brace[..1]);
+            rest = &brace[2..];
+            continue;
+        }
+
+        if brace.starts_with('}') {
+            return Err(template_error(
+                "'}' has no matching '{', use '}}' for a literal brace".into(),
+            ));
+        }
+
+        let end = brace.find('}').ok_or_else(|| {
+            template_error("'{' is never closed, use '{{' for a literal brace".into())
+        })?;
+
+        let field = &brace[1..end];
+        let (name, spec) = field.split_once(':').unwrap_or((field, ""));
+
+        let value = argument(name, args, &mut next)?;
+        result.push_str(&parse_spec(spec)?.apply(&value)?);
+        sandbox::check_formatted(result.len())?;
+
+        rest = &brace[end + 1..];
+    }
+
+    result.push_str(rest);
+    sandbox::check_str(result.len())?;
+
+    Ok(result)
+}
+
+// the argument a field refers to, where `next` is the index of the next `{}`
+fn argument(name: &str, args: &[Value], next: &mut usize) -> Result<Value, GlassError> {
+    let index = if name.is_empty() {
+        *next += 1;
+        Some(*next - 1)
+    } else {
+        name.parse::<usize>().ok()
+    };
+
+    if let Some(index) = index {
+        return args.get(index).cloned().ok_or_else(|| {
+            template_error(format!(
+                "there is no argument {} since only {} were given",
+                index,
+                args.len()
+            ))
+        });
+    }
+
+    for arg in args {
+        let found = match arg {
+            Value::Dict(dict) => dict.borrow().get(&Value::Str(name.into()))?.cloned(),
+            Value::Struct(instance) => instance.borrow().get_field(name),
+            _ => None,
+        };
+
+        if let Some(found) = found {
+            return Ok(found);
+        }
+    }
+
+    Err(template_error(format!(
+        "no dictionary or struct argument has '{}'",
+        name
+    )))
+}
+
+fn parse_spec(spec: &str) -> Result<Spec, GlassError> {
+    let invalid = || template_error(format!("'{}' is not a valid format spec", spec));
+    let is_align = |char: char| matches!(char, '<' | '>' | '^');
+
+    let mut chars = spec.chars();
+    let (fill, align, rest) = match (chars.next(), chars.next()) {
+        (Some(fill), Some(align)) if is_align(align) => (fill, Some(align), chars.as_str()),
+        (Some(align), _) if is_align(align) => (' ', Some(align), &spec[1..]),
+        _ => (' ', None, spec),
+    };
+
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(precision.parse().map_err(|_| invalid())?)),
+        None => (rest, None),
+    };
+
+    if let Some(precision) = precision.filter(|&precision| precision > MAX_PRECISION) {
+        return Err(template_error(format!(
+            "a precision of {} is more than the limit of {}",
+            precision, MAX_PRECISION
+        )));
+    }
+
+    let width = match width {
+        "" => 0,
+        width => width.parse().map_err(|_| invalid())?,
+    };
+
+    Ok(Spec {
+        fill,
+        align,
+        width,
+        precision,
+    })
+}
+
+impl Spec {
+    fn apply(&self, value: &Value) -> Result<String, GlassError> {
+        // the precision is the number of decimals of a number, and the most characters of
+        // anything else
+        let text = match (value, self.precision) {
+            (Value::Num(num), Some(precision)) => {
+                // the whole part, the point and the decimals
+                let len = format!("{:.0}", num).len() + 1 + precision;
+                sandbox::check_formatted(len)?;
+                format!("{:.*}", precision, num)
+            }
+            (value, Some(precision)) => value.format(false)?.chars().take(precision).collect(),
+            (value, None) => value.format(false)?,
+        };
+
+        let align = match (self.align, value) {
+            (Some(align), _) => align,
+            (None, Value::Num(_)) => '>',
+            (None, _) => '<',
+        };
+
+        pad(&text, self.width, self.fill, align)
+    }
+}
+
+/// Pads `text` with `fill` to `width` characters, putting it on the left for `>`, the right for
+/// `<` and splitting it between both sides for `^`. Text that is already wide enough is kept as
+/// it is.
+pub fn pad(text: &str, width: usize, fill: char, align: char) -> Result<String, GlassError> {
+    let padding = width.saturating_sub(text.chars().count());
+
+    if padding == 0 {
+        return Ok(text.into());
+    }
+
+    sandbox::check_formatted(
+        text.len()
+            .saturating_add(padding.saturating_mul(fill.len_utf8())),
+    )?;
+
+    let (left, right) = match align {
+        '>' => (padding, 0),
+        '^' => (padding / 2, padding - padding / 2),
+        _ => (0, padding),
+    };
+
+    let fill = fill.to_string();
+    Ok(fill.repeat(left) + text + &fill.repeat(right))
+}
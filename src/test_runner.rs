@@ -0,0 +1,130 @@
+//! `glass test`, which runs the `test_*` functions defined at the top level of scripts.
+
+use crate::context::Context;
+use crate::error::GlassError;
+use crate::interpreter::Interpreter;
+use crate::node::Node;
+use crate::{collect_scripts, parse_source, read_source};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// A test that failed, and why.
+struct Failure {
+    name: String,
+    error: GlassError,
+}
+
+/// Runs the tests in every script under `paths`, printing a line per test and a summary of the
+/// failures at the end. Each test runs the whole script again in a fresh context first, so
+/// tests can't see what other tests did.
+pub fn run_tests(paths: &[PathBuf]) -> Result<(), GlassError> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        collect_scripts(path, &mut files)?;
+    }
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+
+    for file in &files {
+        let filename: Rc<str> = Rc::from(file.to_string_lossy().to_string());
+        let src = read_source(file, &filename)?;
+
+        // the globals are only used to resolve the script, every test gets its own context
+        let globals = Rc::new(RefCell::new(Context::new()));
+
+        let ast = match parse_source(Rc::clone(&src), Rc::clone(&filename), &globals) {
+            Ok(ast) => ast,
+            Err(error) => {
+                println!("\n{} ... FAILED", filename);
+                failures.push(Failure {
+                    name: filename.to_string(),
+                    error,
+                });
+                continue;
+            }
+        };
+
+        let tests = find_tests(&ast);
+
+        if tests.is_empty() {
+            continue;
+        }
+
+        println!("\nrunning {} test(s) from {}", tests.len(), filename);
+
+        for test in tests {
+            let context = Rc::new(RefCell::new(Context::new()));
+            let interpreter =
+                Interpreter::with_context(Rc::clone(&src), Rc::clone(&filename), context);
+
+            let result = interpreter.visit_node(&ast).and_then(|_| {
+                let function = interpreter.visit_identifier_node(test, &[])?;
+                interpreter.call_function(&function, Vec::new())
+            });
+
+            match result {
+                Ok(_) => {
+                    println!("test {} ... ok", test);
+                    passed += 1;
+                }
+                Err(error) => {
+                    println!("test {} ... FAILED", test);
+                    failures.push(Failure {
+                        name: format!("{}::{}", filename, test),
+                        error,
+                    });
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+
+        for failure in &failures {
+            println!("\n---- {} ----\n{}", failure.name, failure.error);
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len()
+    );
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(GlassError::TestsFailed {
+            failed: failures.len(),
+        })
+    }
+}
+
+/// The names of the `test_*` functions assigned at the top level of a script, in the order they
+/// are defined.
+fn find_tests(ast: &Node) -> Vec<&str> {
+    let statements = match ast {
+        Node::Block { statements } => statements.as_slice(),
+        statement => std::slice::from_ref(statement),
+    };
+
+    statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Node::Assignment { left, right, .. } => match (left.as_ref(), right.as_ref()) {
+                (Node::Identifier { name, .. }, Node::FunctionDefinition { .. })
+                    if name.starts_with("test_") =>
+                {
+                    Some(name.as_str())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
@@ -1,32 +1,655 @@
+use crate::dict::Dict;
 use crate::error::GlassError;
+use crate::function::Function;
 use crate::interpreter::InterpreterResult;
+use crate::sandbox;
+use crate::structs::{Struct, StructDef};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::rc::Rc;
 
-#[derive(Debug, PartialEq)]
+/// A glass value. Lists, dictionaries and sets are shared references, so cloning a `Value` never
+/// copies their contents; assigning a list to another variable or passing it to a function
+/// aliases it, and `copy`/`deepcopy` have to be used to get an independent one.
+#[derive(Clone)]
 pub enum Value {
     Num(f64),
     Str(String),
     Bool(bool),
-    // Func(Function), // todo
-    List(Vec<Value>),
-    Dict(HashMap<String, Value>),
-    // Struct(Struct), // todo
+    Func(Rc<Function>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Tuple(Vec<Value>),
+    Dict(Rc<RefCell<Dict>>),
+    // a set is stored as a dictionary whose values are all void
+    Set(Rc<RefCell<Dict>>),
+    StructDef(Rc<StructDef>),
+    Struct(Rc<RefCell<Struct>>),
     Void,
 }
 
+fn format_number(num: f64) -> String {
+    if num.is_nan() {
+        "nan".into()
+    } else if num.is_infinite() {
+        if num > 0.0 { "inf" } else { "-inf" }.into()
+    } else if num == 0.0 {
+        // don't print negative zero as "-0"
+        "0".into()
+    } else {
+        // f64's Display already leaves off the trailing ".0" for integral values
+        num.to_string()
+    }
+}
+
+fn quote_str(str: &str) -> String {
+    let mut quoted = String::with_capacity(str.len() + 2);
+    quoted.push('"');
+
+    for char in str.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\0' => quoted.push_str("\\0"),
+            _ => quoted.push(char),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Where `write_value` writes to. When it is `checked`, every value written is a step of the
+/// sandbox and the text can't grow past the memory limit, since a list that contains another
+/// list many times is small in memory but can be enormous once it is written out. `fmt::Error`
+/// can't say why writing stopped, so the error is kept in `error`.
+struct Writer<'a> {
+    out: &'a mut dyn fmt::Write,
+    len: usize,
+    checked: bool,
+    error: Option<GlassError>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(out: &'a mut dyn fmt::Write, checked: bool) -> Self {
+        Self {
+            out,
+            len: 0,
+            checked,
+            error: None,
+        }
+    }
+
+    fn check(&mut self, result: Result<(), GlassError>) -> fmt::Result {
+        result.map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+impl fmt::Write for Writer<'_> {
+    fn write_str(&mut self, str: &str) -> fmt::Result {
+        self.len += str.len();
+
+        if self.checked {
+            let result = sandbox::check_formatted(self.len);
+            self.check(result)?;
+        }
+
+        self.out.write_str(str)
+    }
+}
+
+/// Writes `value` to `f`, keeping track of the containers currently being printed in `seen` so
+/// that a container which (directly or indirectly) contains itself is printed as `[...]` or
+/// `{...}` instead of recursing forever.
+fn write_value(
+    f: &mut Writer,
+    value: &Value,
+    quote: bool,
+    seen: &mut Vec<*const ()>,
+) -> fmt::Result {
+    if f.checked {
+        let result = sandbox::step();
+        f.check(result)?;
+    }
+
+    match value {
+        Value::Num(num) => write!(f, "{}", format_number(*num)),
+        Value::Str(str) if quote => write!(f, "{}", quote_str(str)),
+        Value::Str(str) => write!(f, "{}", str),
+        Value::Bool(bool) => write!(f, "{}", bool),
+        Value::Void => write!(f, "void"),
+        Value::Func(function) => match function.as_ref() {
+            Function::User(function) => write!(f, "<func {}>", function.name),
+            Function::Compiled(function) => write!(f, "<func {}>", function.proto.name),
+            Function::Native(function) => write!(f, "<builtin func {}>", function.name),
+        },
+        Value::StructDef(def) => write!(f, "<struct {}>", def.name),
+        Value::Struct(instance) => {
+            let ptr = Rc::as_ptr(instance) as *const ();
+            let instance_ref = instance.borrow();
+
+            if seen.contains(&ptr) {
+                return write!(f, "{} {{...}}", instance_ref.def.name);
+            }
+
+            seen.push(ptr);
+            write!(f, "{} {{", instance_ref.def.name)?;
+
+            for (i, (field, item)) in instance_ref.fields().enumerate() {
+                write!(f, "{}{}: ", if i > 0 { ", " } else { " " }, field)?;
+                write_value(f, item, true, seen)?;
+            }
+
+            seen.pop();
+
+            if instance_ref.values.is_empty() {
+                write!(f, "}}")
+            } else {
+                write!(f, " }}")
+            }
+        }
+        Value::List(list) => {
+            let ptr = Rc::as_ptr(list) as *const ();
+
+            if seen.contains(&ptr) {
+                return write!(f, "[...]");
+            }
+
+            seen.push(ptr);
+            write!(f, "[")?;
+
+            for (i, item) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+
+                write_value(f, item, true, seen)?;
+            }
+
+            seen.pop();
+            write!(f, "]")
+        }
+        Value::Tuple(items) => {
+            write!(f, "(")?;
+
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+
+                write_value(f, item, true, seen)?;
+            }
+
+            // a tuple with one item needs a trailing comma to not look like a parenthesized value
+            if items.len() == 1 {
+                write!(f, ",")?;
+            }
+
+            write!(f, ")")
+        }
+        Value::Dict(dict) => {
+            let ptr = Rc::as_ptr(dict) as *const ();
+
+            if seen.contains(&ptr) {
+                return write!(f, "{{...}}");
+            }
+
+            seen.push(ptr);
+            write!(f, "{{")?;
+
+            // dictionaries keep their insertion order, so this is deterministic
+            for (i, (key, item)) in dict.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+
+                write_value(f, &key.to_value(), true, seen)?;
+                write!(f, ": ")?;
+                write_value(f, item, true, seen)?;
+            }
+
+            seen.pop();
+            write!(f, "}}")
+        }
+        // sets can't contain containers, so they can't contain themselves either
+        Value::Set(set) => {
+            let set = set.borrow();
+
+            // `{}` is an empty dictionary
+            if set.len() == 0 {
+                return write!(f, "set()");
+            }
+
+            write!(f, "{{")?;
+
+            for (i, (item, _)) in set.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+
+                write_value(f, &item.to_value(), true, seen)?;
+            }
+
+            write!(f, "}}")
+        }
+    }
+}
+
+/// Formats a value the way it would be written in source code, quoting strings.
+struct Repr<'a>(&'a Value);
+
+impl Display for Repr<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_value(&mut Writer::new(f, false), self.0, true, &mut Vec::new())
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_value(&mut Writer::new(f, false), self, false, &mut Vec::new())
+    }
+}
+
+// derived Debug would recurse forever on a list that contains itself
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_value(&mut Writer::new(f, false), self, true, &mut Vec::new())
+    }
+}
+
+/// Structural equality. Two aliases of the same container are always equal, and containers that
+/// are currently being compared (in `seen`) are assumed to be equal, which stops cyclic
+/// structures from recursing forever.
+fn values_equal(a: &Value, b: &Value, seen: &mut Vec<(*const (), *const ())>) -> bool {
+    match (a, b) {
+        (Value::Num(a), Value::Num(b)) => a == b,
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Void, Value::Void) => true,
+        (Value::Func(a), Value::Func(b)) => Rc::ptr_eq(a, b),
+        (Value::StructDef(a), Value::StructDef(b)) => Rc::ptr_eq(a, b),
+        (Value::Struct(a), Value::Struct(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+
+            if Rc::ptr_eq(a, b) || seen.contains(&pair) {
+                return true;
+            }
+
+            let (a, b) = (a.borrow(), b.borrow());
+
+            // instances of different structs are never equal, even with the same fields
+            if !Rc::ptr_eq(&a.def, &b.def) {
+                return false;
+            }
+
+            seen.push(pair);
+            let equal = a
+                .values
+                .iter()
+                .zip(&b.values)
+                .all(|(a, b)| values_equal(a, b, seen));
+            seen.pop();
+
+            equal
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b, seen))
+        }
+        (Value::List(a), Value::List(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+
+            if Rc::ptr_eq(a, b) || seen.contains(&pair) {
+                return true;
+            }
+
+            seen.push(pair);
+            let (a, b) = (a.borrow(), b.borrow());
+            let equal = a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| values_equal(a, b, seen));
+            seen.pop();
+
+            equal
+        }
+        (Value::Dict(a), Value::Dict(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+
+            if Rc::ptr_eq(a, b) || seen.contains(&pair) {
+                return true;
+            }
+
+            seen.push(pair);
+            let equal = a
+                .borrow()
+                .entries_eq(&b.borrow(), |a, b| values_equal(a, b, seen));
+            seen.pop();
+
+            equal
+        }
+        (Value::Set(a), Value::Set(b)) => a.borrow().entries_eq(&b.borrow(), |_, _| true),
+        _ => false,
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(self, other, &mut Vec::new())
+    }
+}
+
+/// Converts a possibly negative index into a position in a sequence of length `len`, counting
+/// negative indices from the end.
+fn resolve_index(index: f64, len: usize) -> Result<usize, GlassError> {
+    let out_of_bounds = || GlassError::IndexOutOfBounds {
+        index: format_number(index),
+        len,
+    };
+
+    if index.fract() != 0.0 {
+        return Err(out_of_bounds());
+    }
+
+    let position = if index < 0.0 {
+        len as f64 + index
+    } else {
+        index
+    };
+
+    if position < 0.0 || position >= len as f64 {
+        Err(out_of_bounds())
+    } else {
+        Ok(position as usize)
+    }
+}
+
 impl Value {
-    fn get_type(&self) -> String {
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    /// Creates a list that a script made, counting its items towards the memory limit.
+    pub fn checked_list(items: Vec<Value>) -> InterpreterResult {
+        sandbox::check_items(items.len())?;
+        Ok(Value::list(items))
+    }
+
+    pub fn dict(dict: Dict) -> Value {
+        Value::Dict(Rc::new(RefCell::new(dict)))
+    }
+
+    /// Creates a set of `items`, keeping the first of any that are equal.
+    pub fn set(items: impl IntoIterator<Item = Value>) -> InterpreterResult {
+        let mut set = Dict::new();
+
+        for item in items {
+            set.insert(&item, Value::Void)?;
+        }
+
+        Ok(Value::Set(Rc::new(RefCell::new(set))))
+    }
+
+    /// Returns a new list, dictionary or set with the same items as this one. Any other value is
+    /// returned as is, since it can't be mutated.
+    pub fn copy(&self) -> InterpreterResult {
+        Ok(match self {
+            Value::List(list) => {
+                sandbox::check_items(list.borrow().len())?;
+                Value::list(list.borrow().clone())
+            }
+            Value::Dict(dict) => {
+                sandbox::check_items(dict.borrow().len())?;
+                Value::dict(dict.borrow().clone())
+            }
+            Value::Set(set) => {
+                sandbox::check_items(set.borrow().len())?;
+                Value::Set(Rc::new(RefCell::new(set.borrow().clone())))
+            }
+            Value::Struct(instance) => {
+                let instance = instance.borrow();
+                sandbox::check_items(instance.values.len())?;
+
+                Value::Struct(Rc::new(RefCell::new(Struct {
+                    def: Rc::clone(&instance.def),
+                    values: instance.values.clone(),
+                })))
+            }
+            value => value.clone(),
+        })
+    }
+
+    /// Recursively copies this value. Containers that are referenced more than once are only
+    /// copied once, so aliasing (and cycles) within the value are preserved in the copy.
+    pub fn deepcopy(&self) -> InterpreterResult {
+        self.deepcopy_with(&mut HashMap::new())
+    }
+
+    fn deepcopy_with(&self, copies: &mut HashMap<*const (), Value>) -> InterpreterResult {
+        Ok(match self {
+            Value::List(list) => {
+                let ptr = Rc::as_ptr(list) as *const ();
+
+                if let Some(copy) = copies.get(&ptr) {
+                    return Ok(copy.clone());
+                }
+
+                sandbox::check_items(list.borrow().len())?;
+
+                let copy = Rc::new(RefCell::new(Vec::new()));
+                copies.insert(ptr, Value::List(Rc::clone(&copy)));
+
+                let items = list
+                    .borrow()
+                    .iter()
+                    .map(|item| item.deepcopy_with(copies))
+                    .collect::<Result<_, _>>()?;
+                *copy.borrow_mut() = items;
+
+                Value::List(copy)
+            }
+            Value::Dict(dict) => {
+                let ptr = Rc::as_ptr(dict) as *const ();
+
+                if let Some(copy) = copies.get(&ptr) {
+                    return Ok(copy.clone());
+                }
+
+                sandbox::check_items(dict.borrow().len())?;
+
+                let copy = Rc::new(RefCell::new(Dict::new()));
+                copies.insert(ptr, Value::Dict(Rc::clone(&copy)));
+
+                let entries: Vec<_> = dict
+                    .borrow()
+                    .iter()
+                    .map(|(key, item)| Ok((key.clone(), item.deepcopy_with(copies)?)))
+                    .collect::<Result<_, GlassError>>()?;
+                copy.borrow_mut().extend(entries);
+
+                Value::Dict(copy)
+            }
+            Value::Set(set) => {
+                let ptr = Rc::as_ptr(set) as *const ();
+
+                if let Some(copy) = copies.get(&ptr) {
+                    return Ok(copy.clone());
+                }
+
+                // the items of a set are never mutable, so they don't have to be copied
+                let copy = self.copy()?;
+                copies.insert(ptr, copy.clone());
+                copy
+            }
+            Value::Tuple(items) => {
+                sandbox::check_items(items.len())?;
+
+                Value::Tuple(
+                    items
+                        .iter()
+                        .map(|item| item.deepcopy_with(copies))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            value => value.clone(),
+        })
+    }
+
+    /// The items a `for` loop goes through: the keys of a dictionary, the characters of a string
+    /// and the items of anything else that has them. They are a snapshot, so the loop can
+    /// change what it is going through.
+    pub fn into_items(self) -> Result<Vec<Value>, GlassError> {
+        Ok(match self {
+            Value::List(list) => list.borrow().clone(),
+            Value::Tuple(items) => items,
+            Value::Dict(dict) | Value::Set(dict) => dict
+                .borrow()
+                .iter()
+                .map(|(key, _)| key.to_value())
+                .collect(),
+            Value::Str(str) => str.chars().map(|char| Value::Str(char.into())).collect(),
+            value => {
+                return Err(GlassError::NotIterable {
+                    type_name: value.get_type(),
+                })
+            }
+        })
+    }
+
+    /// Returns the representation of this value used by the REPL. Unlike the `Display`
+    /// implementation, strings are quoted and escaped.
+    pub fn repr(&self) -> String {
+        Repr(self).to_string()
+    }
+
+    /// Formats this value for a script, like `Display` or quoted like `repr`. Unlike them, this
+    /// counts towards the step and time limits and fails once the text is over the memory limit.
+    pub fn format(&self, quote: bool) -> Result<String, GlassError> {
+        let mut text = String::new();
+        let mut writer = Writer::new(&mut text, true);
+        let written = write_value(&mut writer, self, quote, &mut Vec::new());
+
+        match (written, writer.error) {
+            (_, Some(error)) => Err(error),
+            (Ok(()), None) => Ok(text),
+            (Err(_), None) => Err(GlassError::UnknownError {
+                error_message: "Formatting a value failed".into(),
+            }),
+        }
+    }
+
+    pub fn get_type(&self) -> String {
         match self {
+            // instances are of the type of their struct, which is what errors should show
+            Value::Struct(instance) => return instance.borrow().def.name.clone(),
+            Value::StructDef(_) => "struct",
             Value::Num(_) => "number",
             Value::Str(_) => "string",
             Value::Bool(_) => "boolean",
             Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Func(_) => "function",
             Value::Dict(_) => "dictionary",
+            Value::Set(_) => "set",
             Value::Void => "void",
         }
         .into()
     }
 
+    pub fn index(self, index: Value) -> InterpreterResult {
+        match (self, index) {
+            (Value::List(items), Value::Num(index)) => {
+                let items = items.borrow();
+                Ok(items[resolve_index(index, items.len())?].clone())
+            }
+            (Value::Tuple(mut items), Value::Num(index)) => {
+                let position = resolve_index(index, items.len())?;
+                Ok(items.swap_remove(position))
+            }
+            // strings are indexed by character, not by byte
+            (Value::Str(str), Value::Num(index)) => {
+                let position = resolve_index(index, str.chars().count())?;
+                Ok(Value::Str(str.chars().nth(position).unwrap().into()))
+            }
+            (Value::Dict(dict), key) => match dict.borrow().get(&key)? {
+                Some(value) => Ok(value.clone()),
+                None => Err(GlassError::KeyNotFound { key: key.repr() }),
+            },
+            (a, b) => Err(GlassError::InvalidOperation {
+                operation: "[]".into(),
+                left: a.get_type(),
+                right: b.get_type(),
+            }),
+        }
+    }
+
+    pub fn get_field(&self, field: &str) -> InterpreterResult {
+        let found = match self {
+            Value::Struct(instance) => instance.borrow().get_field(field),
+            // methods can be accessed through the struct itself, e.g. Point.new(1, 2)
+            Value::StructDef(def) => def.methods.get(field).cloned(),
+            _ => None,
+        };
+
+        found.ok_or_else(|| GlassError::NoSuchField {
+            field: field.into(),
+            type_name: self.get_type(),
+        })
+    }
+
+    pub fn set_field(&self, field: &str, value: Value) -> Result<(), GlassError> {
+        let found = match self {
+            Value::Struct(instance)
+                if instance.borrow().def.read_only
+                    && instance.borrow().def.field_index(field).is_some() =>
+            {
+                return Err(GlassError::ReadOnlyField {
+                    field: field.into(),
+                    type_name: self.get_type(),
+                })
+            }
+            Value::Struct(instance) => instance.borrow_mut().set_field(field, value),
+            _ => false,
+        };
+
+        if found {
+            Ok(())
+        } else {
+            Err(GlassError::NoSuchField {
+                field: field.into(),
+                type_name: self.get_type(),
+            })
+        }
+    }
+
+    /// Sets an item of a list or dictionary in place, which is visible through every reference
+    /// to it.
+    pub fn set_index(&self, index: Value, value: Value) -> Result<(), GlassError> {
+        match (self, index) {
+            (Value::List(items), Value::Num(index)) => {
+                let mut items = items.borrow_mut();
+                let position = resolve_index(index, items.len())?;
+                items[position] = value;
+                Ok(())
+            }
+            (Value::Dict(dict), key) => {
+                dict.borrow_mut().insert(&key, value)?;
+                Ok(())
+            }
+            (a, b) => Err(GlassError::InvalidOperation {
+                operation: "[]=".into(),
+                left: a.get_type(),
+                right: b.get_type(),
+            }),
+        }
+    }
+
     pub fn pow(self, other: Value) -> InterpreterResult {
         match (self, other) {
             (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a.powf(b))),
@@ -63,16 +686,38 @@ impl Value {
     pub fn add(self, other: Value) -> InterpreterResult {
         match (self, other) {
             (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a + b)),
-            (Value::Str(a), Value::Str(b)) => Ok(Value::Str(a + &b)),
-            (Value::List(mut a), Value::List(b)) => {
-                a.extend(b);
-                Ok(Value::List(a))
+            (Value::Str(a), Value::Str(b)) => {
+                sandbox::check_str(a.len() + b.len())?;
+                Ok(Value::Str(a + &b))
+            }
+            (Value::List(a), Value::List(b)) => {
+                sandbox::check_items(a.borrow().len() + b.borrow().len())?;
+
+                let mut items = a.borrow().clone();
+                items.extend(b.borrow().iter().cloned());
+                Ok(Value::list(items))
+            }
+            (Value::Str(a), Value::Num(b)) => {
+                let b = b.to_string();
+                sandbox::check_str(a.len() + b.len())?;
+                Ok(Value::Str(a + &b))
             }
-            (Value::Str(a), Value::Num(b)) => Ok(Value::Str(a + &b.to_string())),
-            (Value::Num(a), Value::Str(b)) => Ok(Value::Str(a.to_string() + &b)),
-            (Value::Dict(mut a), Value::Dict(b)) => {
-                a.extend(b);
-                Ok(Value::Dict(a))
+            (Value::Num(a), Value::Str(b)) => {
+                let a = a.to_string();
+                sandbox::check_str(a.len() + b.len())?;
+                Ok(Value::Str(a + &b))
+            }
+            (Value::Dict(a), Value::Dict(b)) => {
+                let mut dict = a.borrow().clone();
+                dict.extend(
+                    b.borrow()
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone())),
+                );
+
+                // the keys they share are only counted once
+                sandbox::check_items(dict.len())?;
+                Ok(Value::dict(dict))
             }
             (a, b) => Err(GlassError::InvalidOperation {
                 operation: "+".into(),
@@ -97,6 +742,7 @@ impl Value {
         match (self, other) {
             (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a * b)),
             (Value::Str(a), Value::Num(b)) | (Value::Num(b), Value::Str(a)) => {
+                sandbox::check_str(a.len().saturating_mul(b as usize))?;
                 Ok(Value::Str(a.repeat(b as usize)))
             }
             (a, b) => Err(GlassError::InvalidOperation {
@@ -109,6 +755,9 @@ impl Value {
 
     pub fn div(self, other: Value) -> InterpreterResult {
         match (self, other) {
+            (Value::Num(_), Value::Num(0.0)) => Err(GlassError::DivisionByZero {
+                operation: "/".into(),
+            }),
             (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a / b)),
             (a, b) => Err(GlassError::InvalidOperation {
                 operation: "/".into(),
@@ -120,6 +769,9 @@ impl Value {
 
     pub fn rem(self, other: Value) -> InterpreterResult {
         match (self, other) {
+            (Value::Num(_), Value::Num(0.0)) => Err(GlassError::DivisionByZero {
+                operation: "%".into(),
+            }),
             (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a % b)),
             (a, b) => Err(GlassError::InvalidOperation {
                 operation: "%".into(),
@@ -0,0 +1,350 @@
+use crate::bytecode::{Chunk, Constant, Instruction};
+use crate::dict::Dict;
+use crate::error::GlassError;
+use crate::function::{CompiledFunction, Function};
+use crate::interpreter::{Interpreter, InterpreterResult};
+use crate::sandbox;
+use crate::structs::StructDef;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Where to go when an error is raised inside of a try. The stack is cut back to how it was
+/// when the handler was registered.
+struct Handler {
+    target: usize,
+    stack_len: usize,
+    pending_len: usize,
+    finally: bool,
+}
+
+#[derive(Default)]
+struct State {
+    stack: Vec<Value>,
+    ip: usize,
+    handlers: Vec<Handler>,
+    // the errors that the finally blocks being run will rethrow once they are done
+    pending: Vec<GlassError>,
+    filename: Rc<str>,
+}
+
+impl State {
+    // compiled code never takes more off of the stack than it put on it, but a corrupt .glassc
+    // file can, which has to fail instead of panicking
+    fn invalid_bytecode(&self, reason: &str) -> GlassError {
+        GlassError::InvalidBytecode {
+            filename: Rc::clone(&self.filename),
+            reason: reason.into(),
+        }
+    }
+
+    /// The top `count` values of the stack, in the order they were pushed.
+    fn top(&mut self, count: usize) -> Result<&mut [Value], GlassError> {
+        match self.stack.len().checked_sub(count) {
+            Some(start) => Ok(&mut self.stack[start..]),
+            None => Err(self.invalid_bytecode("the stack is empty")),
+        }
+    }
+
+    /// Pops the top `count` values off of the stack, in the order they were pushed.
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Value>, GlassError> {
+        let start = self.stack.len() - self.top(count)?.len();
+        Ok(self.stack.split_off(start))
+    }
+
+    fn pop(&mut self) -> Result<Value, GlassError> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(self.invalid_bytecode("the stack is empty")),
+        }
+    }
+}
+
+/// Runs a chunk in the context of `interpreter`, which is used for variables and to call
+/// functions, so that calls behave exactly like they do in the tree-walking interpreter.
+/// Returns the value of the first `Return` instruction, or void if there is none.
+pub fn run(interpreter: &Interpreter, chunk: &Chunk) -> InterpreterResult {
+    let mut vm = State {
+        stack: Vec::with_capacity(16),
+        filename: Rc::clone(interpreter.filename()),
+        ..State::default()
+    };
+
+    while let Some(&instruction) = chunk.code.get(vm.ip) {
+        vm.ip += 1;
+
+        let error =
+            match sandbox::step().and_then(|_| execute(interpreter, chunk, &mut vm, instruction)) {
+                Ok(None) => continue,
+                Ok(Some(value)) => return Ok(value),
+                // the handlers of a corrupt chunk can't be trusted to be any better
+                Err(error @ GlassError::InvalidBytecode { .. }) => return Err(error),
+                Err(error) => match chunk.span(vm.ip - 1) {
+                    Some(span) => interpreter.locate(error, span),
+                    None => error,
+                },
+            };
+
+        // an error that can't be caught skips the catch blocks, but still runs finally blocks
+        let handler = loop {
+            match vm.handlers.pop() {
+                Some(handler) if handler.finally || error.is_catchable() => break handler,
+                Some(_) => {}
+                None => return Err(error),
+            }
+        };
+
+        vm.stack.truncate(handler.stack_len);
+        vm.pending.truncate(handler.pending_len);
+
+        if handler.finally {
+            vm.pending.push(error);
+        } else {
+            vm.stack.push(Interpreter::caught_value(error));
+        }
+
+        vm.ip = handler.target;
+    }
+
+    Ok(Value::Void)
+}
+
+/// Executes a single instruction, returning the value being returned if it is a `Return`.
+fn execute(
+    interpreter: &Interpreter,
+    chunk: &Chunk,
+    vm: &mut State,
+    instruction: Instruction,
+) -> Result<Option<Value>, GlassError> {
+    match instruction {
+        Instruction::Constant(index) => vm.stack.push(match &chunk.constants[index as usize] {
+            Constant::Num(num) => Value::Num(*num),
+            Constant::Str(str) => Value::Str(str.clone()),
+            constant => {
+                return Err(GlassError::UnknownError {
+                    error_message: format!("Invalid value constant {:?}", constant),
+                })
+            }
+        }),
+        Instruction::True => vm.stack.push(Value::Bool(true)),
+        Instruction::False => vm.stack.push(Value::Bool(false)),
+        Instruction::Void => vm.stack.push(Value::Void),
+        Instruction::Pop => {
+            vm.pop()?;
+        }
+        Instruction::Dup => {
+            let top = vm.top(1)?.to_vec();
+            vm.stack.extend(top);
+        }
+        Instruction::Dup2 => {
+            let top = vm.top(2)?.to_vec();
+            vm.stack.extend(top);
+        }
+        Instruction::Load { name, slots } => vm.stack.push(
+            interpreter.visit_identifier_node(chunk.name(name), &chunk.slots[slots as usize])?,
+        ),
+        Instruction::Store(name) => {
+            let value = vm.pop()?;
+            interpreter
+                .context()
+                .borrow_mut()
+                .set(chunk.name(name), value);
+        }
+        Instruction::StoreSlot(index) => {
+            let value = vm.pop()?;
+            interpreter
+                .context()
+                .borrow_mut()
+                .set_slot(index as usize, value);
+        }
+        Instruction::BinaryOp(op) => {
+            let right = vm.pop()?;
+            let left = vm.pop()?;
+            vm.stack.push(op.apply(left, right)?);
+        }
+        Instruction::Negate => {
+            let value = vm.pop()?;
+            vm.stack.push(value.neg()?);
+        }
+        Instruction::Not => {
+            let value = vm.pop()?;
+            vm.stack.push(value.not()?);
+        }
+        Instruction::List(count) => {
+            let items = vm.pop_many(count as usize)?;
+            vm.stack.push(Value::checked_list(items)?);
+        }
+        Instruction::Tuple(count) => {
+            let items = vm.pop_many(count as usize)?;
+            sandbox::check_items(items.len())?;
+            vm.stack.push(Value::Tuple(items));
+        }
+        Instruction::Dict(count) => {
+            let mut dict = Dict::new();
+            let mut items = vm.pop_many(count as usize * 2)?.into_iter();
+
+            while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                dict.insert(&key, value)?;
+            }
+
+            vm.stack.push(Value::dict(dict));
+        }
+        Instruction::Index => {
+            let index = vm.pop()?;
+            let target = vm.pop()?;
+            vm.stack.push(target.index(index)?);
+        }
+        Instruction::SetIndex => {
+            let value = vm.pop()?;
+            let index = vm.pop()?;
+            let target = vm.pop()?;
+            target.set_index(index, value)?;
+        }
+        Instruction::GetField(field) => {
+            let target = vm.pop()?;
+            vm.stack.push(target.get_field(chunk.name(field))?);
+        }
+        Instruction::SetField(field) => {
+            let value = vm.pop()?;
+            let target = vm.pop()?;
+            target.set_field(chunk.name(field), value)?;
+        }
+        Instruction::Call(count) => {
+            let args = vm.pop_many(count as usize)?;
+            let function = vm.pop()?;
+            vm.stack.push(interpreter.call_function(&function, args)?);
+        }
+        Instruction::CallMethod(method, count) => {
+            let args = vm.pop_many(count as usize)?;
+            let target = vm.pop()?;
+            vm.stack
+                .push(interpreter.call_method(target, chunk.name(method), args)?);
+        }
+        Instruction::Closure(index) => match &chunk.constants[index as usize] {
+            Constant::Function(proto) => {
+                vm.stack
+                    .push(Value::Func(Rc::new(Function::Compiled(CompiledFunction {
+                        proto: Rc::clone(proto),
+                        closure: Rc::clone(interpreter.context()),
+                        src: Rc::clone(interpreter.src()),
+                        filename: Rc::clone(interpreter.filename()),
+                    }))))
+            }
+            constant => {
+                return Err(GlassError::UnknownError {
+                    error_message: format!("Invalid function constant {:?}", constant),
+                })
+            }
+        },
+        Instruction::Struct(index) => match &chunk.constants[index as usize] {
+            Constant::Struct(proto) => {
+                let functions = vm.pop_many(proto.methods.len())?;
+                let methods: HashMap<_, _> = proto.methods.iter().cloned().zip(functions).collect();
+
+                let def = StructDef {
+                    name: proto.name.clone(),
+                    fields: proto.fields.clone(),
+                    methods,
+                    read_only: false,
+                };
+
+                vm.stack.push(Value::StructDef(Rc::new(def)));
+            }
+            constant => {
+                return Err(GlassError::UnknownError {
+                    error_message: format!("Invalid struct constant {:?}", constant),
+                })
+            }
+        },
+        Instruction::Jump(target) => vm.ip = target as usize,
+        Instruction::JumpIfFalse(target) => match vm.pop()? {
+            Value::Bool(true) => {}
+            Value::Bool(false) => vm.ip = target as usize,
+            value => {
+                return Err(GlassError::InvalidCondition {
+                    condition_type: value.get_type(),
+                })
+            }
+        },
+        Instruction::AndShortCircuit(target) => {
+            if let Some(Value::Bool(false)) = vm.stack.last() {
+                vm.ip = target as usize;
+            }
+        }
+        Instruction::OrShortCircuit(target) => {
+            if let Some(Value::Bool(true)) = vm.stack.last() {
+                vm.ip = target as usize;
+            }
+        }
+        Instruction::RangeInit { inclusive } => {
+            if let [start, end] = vm.top(2)? {
+                if !matches!((&start, &end), (Value::Num(_), Value::Num(_))) {
+                    return Err(GlassError::InvalidOperation {
+                        operation: if inclusive { "..=" } else { ".." }.into(),
+                        left: start.get_type(),
+                        right: end.get_type(),
+                    });
+                }
+            }
+        }
+        Instruction::RangeNext { inclusive, exit } => {
+            if let [Value::Num(i), Value::Num(end)] = *vm.top(2)? {
+                if i < end || (inclusive && i == end) {
+                    vm.stack.push(Value::Num(i));
+                } else {
+                    vm.ip = exit as usize;
+                }
+            }
+        }
+        Instruction::RangeStep => {
+            if let [Value::Num(i), _] = vm.top(2)? {
+                *i += 1.0;
+            }
+        }
+        Instruction::IterInit => {
+            let items = vm.pop()?.into_items()?;
+
+            vm.stack.push(Value::Tuple(items));
+            vm.stack.push(Value::Num(0.0));
+        }
+        Instruction::IterNext { exit } => {
+            if let [Value::Tuple(items), Value::Num(index)] = vm.top(2)? {
+                match items.get(*index as usize) {
+                    Some(item) => {
+                        let item = item.clone();
+                        *index += 1.0;
+                        vm.stack.push(item);
+                    }
+                    None => vm.ip = exit as usize,
+                }
+            }
+        }
+        Instruction::Return => return Ok(Some(vm.pop()?)),
+        Instruction::Try(target) | Instruction::TryFinally(target) => vm.handlers.push(Handler {
+            target: target as usize,
+            stack_len: vm.stack.len(),
+            pending_len: vm.pending.len(),
+            finally: matches!(instruction, Instruction::TryFinally(_)),
+        }),
+        Instruction::EndTry => {
+            vm.handlers.pop();
+        }
+        Instruction::Throw => {
+            let value = vm.pop()?;
+            return Err(interpreter.throw(value, chunk.span(vm.ip - 1)));
+        }
+        Instruction::Rethrow => {
+            return Err(match vm.pending.pop() {
+                Some(error) => error,
+                None => vm.invalid_bytecode("there is no error to rethrow"),
+            })
+        }
+        Instruction::OutsideLoop { is_break } => {
+            return Err(GlassError::ControlFlowOutsideLoop {
+                statement: if is_break { "break" } else { "continue" }.into(),
+            })
+        }
+    }
+
+    Ok(None)
+}
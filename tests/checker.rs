@@ -0,0 +1,181 @@
+//! Checks the type errors reported by `glass check`, which shouldn't run the script.
+
+mod common;
+
+use common::{glass, script, scripts_in, stderr};
+use std::path::Path;
+use std::process::Output;
+
+fn check(path: &Path) -> Output {
+    glass(["check".as_ref(), path.as_os_str()])
+}
+
+fn type_errors(src: &str, name: &str) -> String {
+    let output = check(&script(name, src));
+    assert!(output.stdout.is_empty(), "{} should not have run", name);
+
+    stderr(&output)
+}
+
+#[test]
+fn operations_follow_the_value_rules() {
+    let errors = type_errors(
+        "println(\"start\")\nname = \"glass\"\nrepeated = name * 3\nbroken = name - 1\nflag = not repeated",
+        "operations.glass",
+    );
+
+    assert!(
+        errors.contains("Cannot use operation '-' on type 'string' and 'number'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("(Ln:4, Col:15..16)"), "{}", errors);
+    assert!(
+        errors.contains("Unary operator '!' cannot be applied to type 'string'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Found 2 type error(s)"), "{}", errors);
+}
+
+#[test]
+fn annotations_are_checked() {
+    let errors = type_errors(
+        "count: number = 3\ncount = \"three\"\nscale = func(x: number, by: number) -> list => x * by\nscale(1, \"2\")\nsize: sizes = 1",
+        "annotations.glass",
+    );
+
+    assert!(
+        errors.contains("Expected type 'number' but found 'string' at \n\n\tcount = \"three\""),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Expected type 'list' but found 'number'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Invalid argument passed to 'scale': expected 'number' but found 'string'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Unknown type 'sizes'"), "{}", errors);
+}
+
+#[test]
+fn types_flow_through_functions_and_structs() {
+    let errors = type_errors(
+        "struct Point {\n    x, y\n    norm = func(self) => self.x + self.y\n}\np = Point(1, 2)\nlater = func() => name() - 1\nname = func() => \"point\"\np.z\np.norm(1)\nPoint(1)",
+        "inference.glass",
+    );
+
+    // `name` is defined after `later`, but still has a known return type
+    assert!(
+        errors.contains("Cannot use operation '-' on type 'string' and 'number'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("No field 'z' on type 'Point'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Function 'Point.norm' expected 1 argument(s) but 2 were given"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Function 'Point' expected 2 argument(s) but 1 were given"),
+        "{}",
+        errors
+    );
+}
+
+#[test]
+fn unannotated_parameters_are_not_errors() {
+    let errors = type_errors(
+        "twice = func(x) => x * 2\nprintln(twice(\"a\"), twice(2), twice(3) - 1)",
+        "gradual.glass",
+    );
+
+    assert!(errors.is_empty(), "{}", errors);
+}
+
+#[test]
+fn math_functions_are_known() {
+    let errors = type_errors(
+        "root = math.sqrt(2, 3)\nangle = math.pi - \"half\"\nmath.sqr(2)\nlength = math.hypot(3, 4) - [5]",
+        "math.glass",
+    );
+
+    assert!(
+        errors.contains("Function 'math.sqrt' expected 1 argument(s) but 2 were given"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Cannot use operation '-' on type 'number' and 'string'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("No method 'sqr' on type 'math'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Cannot use operation '-' on type 'number' and 'list'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Found 4 type error(s)"), "{}", errors);
+}
+
+#[test]
+fn sets_are_their_own_type() {
+    let errors = type_errors(
+        "seen: set = set([1])\nseen = [1]\ntotal = set() + 1\nseen.push(2)",
+        "sets.glass",
+    );
+
+    assert!(
+        errors.contains("Expected type 'set' but found 'list'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("Cannot use operation '+' on type 'set' and 'number'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("No method 'push' on type 'set'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Found 3 type error(s)"), "{}", errors);
+}
+
+#[test]
+fn programs_without_type_errors_pass() {
+    for program in scripts_in("tests/programs") {
+        let name = program.file_name().unwrap().to_string_lossy();
+
+        // the other error programs fail while running, which the checker can't know about
+        if name == "type_error.glass" || name == "method_error.glass" {
+            continue;
+        }
+
+        let output = check(&program);
+        let stderr = stderr(&output);
+
+        assert!(
+            !stderr.contains("type error(s)"),
+            "{} has type errors: {}",
+            program.display(),
+            stderr
+        );
+    }
+}
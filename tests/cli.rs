@@ -0,0 +1,93 @@
+//! Checks the ways of passing code and arguments to glass, and how scripts exit.
+
+mod common;
+
+use common::{command, glass, glass_with_input, script, stdout};
+
+#[test]
+fn code_is_run_from_the_command_line() {
+    let output = glass(["-e", "println(1 + 2)"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+
+    let output = glass(["--vm", "--eval", "println(args)", "a", "-b", "test"]);
+    assert_eq!(stdout(&output), "[\"a\", \"-b\", \"test\"]\n");
+}
+
+#[test]
+fn scripts_are_read_from_stdin() {
+    let output = glass_with_input(["-", "first", "second"], "println(len(args), args[1])\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2 second\n");
+}
+
+#[test]
+fn arguments_after_the_script_are_passed_to_it() {
+    let path = script("args.glass", "for arg in args { println(arg) }\n");
+    let output = glass([path.to_str().unwrap(), "one", "--vm", "3"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "one\n--vm\n3\n");
+
+    // a first argument that is also a command has to come after --
+    let output = glass([path.to_str().unwrap(), "--", "check"]);
+    assert_eq!(stdout(&output), "check\n");
+}
+
+#[test]
+fn exit_stops_the_script_with_its_code() {
+    let output = glass(["-e", "println(\"before\")\nexit(3)\nprintln(\"after\")"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "before\n");
+    assert!(output.stderr.is_empty());
+
+    assert_eq!(glass(["-e", "exit()"]).status.code(), Some(0));
+    let output = glass(["-e", "exit(1.5)"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1.5 is not a valid exit code"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn exit_codes_outside_a_byte_are_rejected() {
+    for code in ["256", "-1"] {
+        let output = glass(["-e", &format!("exit({})", code)]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_ne!(output.status.code(), Some(0), "exit({}) succeeded", code);
+        assert!(
+            stderr.contains(&format!("{} is not a valid exit code", code)),
+            "{}",
+            stderr
+        );
+    }
+
+    let output = glass([
+        "-e",
+        "try {\n    exit(256)\n} catch err {\n    println(err.kind)\n}",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "InvalidArgument\n");
+}
+
+#[test]
+fn exit_cannot_be_caught_but_runs_finally_blocks() {
+    let src = "f = func() => {\n    try {\n        exit(4)\n    } catch error {\n        println(\"caught\")\n    } finally {\n        println(\"finally\")\n    }\n}\ntry {\n    f()\n} catch error {\n    println(\"caught outside\")\n}\n";
+    let path = script("exit_finally.glass", src);
+
+    for args in [vec![], vec!["--vm"]] {
+        let output = command()
+            .args(args)
+            .arg(&path)
+            .output()
+            .expect("Failed to run glass");
+
+        assert_eq!(output.status.code(), Some(4));
+        assert_eq!(stdout(&output), "finally\n");
+    }
+}
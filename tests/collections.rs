@@ -0,0 +1,84 @@
+//! Checks sorting lists in place with functions that call back into the script.
+
+mod common;
+
+use common::run_both;
+
+#[test]
+fn lists_are_sorted_in_place() {
+    let src = "\
+xs = [3, 1, 2]
+ys = xs
+xs.sort()
+println(ys)
+xs.sort(func(x) => -x)
+println(ys)
+xs.sort_by(func(a, b) => a - b)
+println(ys)
+";
+
+    assert_eq!(
+        run_both("sort_in_place.glass", src),
+        "[1, 2, 3]\n[3, 2, 1]\n[1, 2, 3]\n"
+    );
+}
+
+#[test]
+fn modifying_a_list_while_it_is_sorted_is_an_error() {
+    let src = "\
+xs = [3, 1, 2]
+try {
+    xs.sort(func(x) => {
+        xs.push(x)
+        return x
+    })
+} catch err {
+    println(err.kind, err.message)
+}
+try {
+    xs.sort_by(func(a, b) => {
+        xs.push(0)
+        xs.pop()
+        return a - b
+    })
+} catch err {
+    println(err.kind)
+}
+println(xs)
+";
+
+    assert_eq!(
+        run_both("sort_modified.glass", src),
+        "\
+ModifiedWhileSorting The list was modified by the function passed to 'sort' while it was being sorted
+ModifiedWhileSorting
+[3, 1, 2]
+"
+    );
+}
+
+#[test]
+fn a_failing_sort_leaves_the_list_as_it_was() {
+    let src = "\
+xs = [3, 1, 2]
+try {
+    xs.sort_by(func(a, b) => {
+        println(len(xs))
+        throw \"stop\"
+    })
+} catch err {
+    println(err.message)
+}
+try {
+    [1, \"a\"].sort()
+} catch err {
+    println(err.kind)
+}
+println(xs)
+";
+
+    assert_eq!(
+        run_both("sort_failed.glass", src),
+        "0\nstop\nInvalidOperation\n[3, 1, 2]\n"
+    );
+}
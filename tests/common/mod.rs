@@ -0,0 +1,98 @@
+//! Helpers shared by the integration tests, which all run the glass binary.
+
+// every test includes this module, but none of them uses all of it
+#![allow(dead_code)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A command that runs glass, for tests that need more control over it than `glass` gives.
+pub fn command() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_glass_lang"))
+}
+
+pub fn glass<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    command().args(args).output().expect("Failed to run glass")
+}
+
+/// Runs glass with `input` written to its stdin.
+pub fn glass_with_input<I, S>(args: I, input: &str) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = command()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run glass");
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+    drop(stdin);
+
+    child.wait_with_output().unwrap()
+}
+
+/// Writes a script to the temporary directory of the tests, returning its path.
+pub fn script(name: &str, src: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, src).expect("Failed to write script");
+    path
+}
+
+/// Runs a script with both engines, checking that it succeeds and that they print the same
+/// thing, and returns what they printed.
+pub fn run_both(name: &str, src: &str) -> String {
+    let path = script(name, src);
+    let walked = glass([&path]);
+    let compiled = glass(["--vm".as_ref(), path.as_os_str()]);
+
+    assert!(walked.status.success(), "{:?}", walked);
+    assert_eq!(
+        stdout(&walked),
+        stdout(&compiled),
+        "the engines printed different things"
+    );
+
+    stdout(&walked)
+}
+
+/// An empty directory in the temporary directory of the tests.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    dir
+}
+
+/// The `.glass` files in a directory of the crate, like `tests/programs`, sorted by name.
+pub fn scripts_in(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+
+    let mut scripts: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("Failed to read {}: {}", dir.display(), err))
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "glass"))
+        .collect();
+
+    scripts.sort();
+    scripts
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
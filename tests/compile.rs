@@ -0,0 +1,140 @@
+//! Compiles the programs in `tests/programs` to `.glassc` files and checks that running them
+//! behaves exactly like running the source, and that stale files are recompiled.
+
+mod common;
+
+use common::{glass, scratch_dir, scripts_in};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn compiled_programs_match_source() {
+    let dir = scratch_dir("compiled_programs");
+
+    for program in scripts_in("tests/programs") {
+        let output = dir
+            .join(program.file_name().unwrap())
+            .with_extension("glassc");
+        let compiled = glass([Path::new("compile"), &program, Path::new("-o"), &output]);
+        assert!(
+            compiled.status.success() && output.exists(),
+            "Failed to compile {}: {}",
+            program.display(),
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+
+        let expected = glass([&program]);
+        let actual = glass([&output]);
+
+        assert_eq!(
+            String::from_utf8_lossy(&expected.stdout),
+            String::from_utf8_lossy(&actual.stdout),
+            "{} printed something different once compiled",
+            program.display()
+        );
+        // warnings are reported when compiling instead of when running the compiled file
+        assert_eq!(
+            String::from_utf8_lossy(&expected.stderr),
+            String::from_utf8_lossy(&[compiled.stderr, actual.stderr].concat()),
+            "{} failed differently once compiled",
+            program.display()
+        );
+    }
+}
+
+#[test]
+fn stale_files_are_recompiled() {
+    let dir = scratch_dir("stale_files");
+    let source = dir.join("script.glass");
+    let output = dir.join("script.glassc");
+
+    fs::write(&source, "println(\"before\")").unwrap();
+    glass([Path::new("compile"), &source]);
+    assert_eq!(glass([&output]).stdout, b"before\n");
+
+    fs::write(&source, "println(\"after\")").unwrap();
+    assert_eq!(glass([&output]).stdout, b"after\n");
+
+    // the source is no longer needed once it has been compiled
+    fs::remove_file(&source).unwrap();
+    assert_eq!(glass([&output]).stdout, b"after\n");
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let dir = scratch_dir("invalid_files");
+    let source = dir.join("script.glass");
+    let output = dir.join("script.glassc");
+
+    fs::write(&source, "println(1 + 2)").unwrap();
+    glass([Path::new("compile"), &source]);
+    fs::remove_file(&source).unwrap();
+
+    let mut bytes = fs::read(&output).unwrap();
+    bytes.truncate(bytes.len() - 3);
+    fs::write(&output, bytes).unwrap();
+
+    let result = glass([&output]);
+    assert!(result.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("is not a valid compiled glass file"));
+}
+
+// the length of the header of a compiled file, which ends after its three strings and the hash
+// of the source
+fn header_len(bytes: &[u8]) -> usize {
+    let mut offset = "GLASSC".len() + 4;
+
+    for _ in 0..3 {
+        let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        offset += 4 + len as usize;
+    }
+
+    offset + 8
+}
+
+#[test]
+fn malformed_bytecode_fails_without_panicking() {
+    let dir = scratch_dir("malformed_bytecode");
+    let source = dir.join("script.glass");
+    let output = dir.join("script.glassc");
+
+    fs::write(&source, "println(1)").unwrap();
+    glass([Path::new("compile"), &source]);
+    fs::remove_file(&source).unwrap();
+
+    let compiled = fs::read(&output).unwrap();
+    let header = &compiled[..header_len(&compiled)];
+
+    // Pop, Dup, Dup2, RangeInit, RangeStep, Return and Rethrow on an empty stack, and a
+    // StoreSlot at the top level, which has no slots
+    for code in [
+        &[4][..],
+        &[5],
+        &[6],
+        &[27, 0],
+        &[29],
+        &[32],
+        &[38],
+        &[39, 0, 0, 0, 0],
+    ] {
+        let mut bytes = header.to_vec();
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(code);
+        // no spans and no slot lists
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        fs::write(&output, bytes).unwrap();
+
+        let result = glass([&output]);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+
+        assert!(
+            stderr.contains("script.glassc' is not a valid compiled glass file"),
+            "{:?}: {}",
+            code,
+            stderr
+        );
+        assert!(!stderr.contains("Unknown error"), "{:?}: {}", code, stderr);
+    }
+}
@@ -0,0 +1,297 @@
+//! Talks to `glass dap` like an editor would, over its stdin and stdout.
+
+mod common;
+
+use common::command;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+const SCRIPT: &str = "\
+add = func(a, b) => {
+    total = a + b
+    return total
+}
+
+items = [1, [2, 3]]
+point = {\"x\": 1, \"y\": 2}
+y = add(1, 2)
+println(\"y is\", y)
+";
+
+fn script(name: &str) -> PathBuf {
+    common::script(name, SCRIPT)
+}
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+    // events received while waiting for a response
+    events: Vec<Value>,
+}
+
+impl Client {
+    /// Launches the script with breakpoints on `lines`.
+    fn launch(path: &Path, lines: &[u64], stop_on_entry: bool) -> Self {
+        let mut child = command()
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to run glass");
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut client = Self {
+            child,
+            stdin,
+            stdout,
+            seq: 0,
+            events: Vec::new(),
+        };
+
+        let capabilities = client.request("initialize", json!({ "adapterID": "glass" }));
+        assert_eq!(
+            capabilities["body"]["supportsConfigurationDoneRequest"],
+            true
+        );
+        client.wait_for("initialized");
+
+        client.request(
+            "launch",
+            json!({ "program": path, "stopOnEntry": stop_on_entry }),
+        );
+
+        let breakpoints: Vec<_> = lines.iter().map(|line| json!({ "line": line })).collect();
+        let response = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": breakpoints }),
+        );
+        assert_eq!(
+            response["body"]["breakpoints"].as_array().unwrap().len(),
+            lines.len()
+        );
+
+        client.request("configurationDone", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                _ if header.trim_end().is_empty() => break,
+                _ => {}
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let seq = self.seq;
+
+        self.send(json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        }));
+
+        loop {
+            let message = self.receive();
+
+            if message["type"] == "response" && message["request_seq"] == seq {
+                return message;
+            }
+
+            self.events.push(message);
+        }
+    }
+
+    /// Returns the body of the next event called `name`, skipping any others.
+    fn wait_for(&mut self, name: &str) -> Value {
+        loop {
+            let event = match self.events.is_empty() {
+                true => self.receive(),
+                false => self.events.remove(0),
+            };
+
+            if event["event"] == name {
+                return event["body"].clone();
+            }
+        }
+    }
+
+    // the line of every frame, innermost first
+    fn stack(&mut self) -> Vec<(String, u64)> {
+        let response = self.request("stackTrace", json!({ "threadId": 1 }));
+
+        response["body"]["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                (
+                    frame["name"].as_str().unwrap().to_string(),
+                    frame["line"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn variables(&mut self, reference: &Value) -> Vec<(String, Value, Value)> {
+        let response = self.request("variables", json!({ "variablesReference": reference }));
+
+        response["body"]["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| {
+                (
+                    variable["name"].as_str().unwrap().to_string(),
+                    variable["value"].clone(),
+                    variable["variablesReference"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    fn finish(mut self) {
+        self.request("disconnect", json!({}));
+        drop(self.stdin);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+#[test]
+fn breakpoints_stop_the_script() {
+    let path = script("dap_breakpoints.glass");
+    let mut client = Client::launch(&path, &[2], false);
+
+    let stopped = client.wait_for("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(stopped["threadId"], 1);
+    assert_eq!(
+        client.stack(),
+        [("add".to_string(), 2), ("global".to_string(), 8)]
+    );
+
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    let source = &response["body"]["stackFrames"][0]["source"];
+    assert_eq!(source["name"], "dap_breakpoints.glass");
+    assert_eq!(response["body"]["stackFrames"][0]["column"], 5);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.wait_for("output")["output"], "y is 3\n");
+    assert_eq!(client.wait_for("exited")["exitCode"], 0);
+    client.wait_for("terminated");
+    client.finish();
+}
+
+#[test]
+fn stepping_moves_through_the_script() {
+    let path = script("dap_stepping.glass");
+    let mut client = Client::launch(&path, &[], true);
+
+    assert_eq!(client.wait_for("stopped")["reason"], "entry");
+    assert_eq!(client.stack(), [("global".to_string(), 1)]);
+
+    for _ in 0..3 {
+        client.request("next", json!({ "threadId": 1 }));
+        assert_eq!(client.wait_for("stopped")["reason"], "step");
+    }
+    assert_eq!(client.stack(), [("global".to_string(), 8)]);
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    client.wait_for("stopped");
+    assert_eq!(client.stack()[0], ("add".to_string(), 2));
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    client.wait_for("stopped");
+    assert_eq!(client.stack(), [("global".to_string(), 9)]);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.wait_for("terminated");
+    client.finish();
+}
+
+#[test]
+fn scopes_and_values_can_be_expanded() {
+    let path = script("dap_variables.glass");
+    let mut client = Client::launch(&path, &[3], false);
+    client.wait_for("stopped");
+
+    let response = client.request("scopes", json!({ "frameId": 1 }));
+    let scopes = response["body"]["scopes"].as_array().unwrap().clone();
+    assert_eq!(scopes[0]["name"], "Locals");
+    assert_eq!(scopes[1]["name"], "Globals");
+
+    let locals = client.variables(&scopes[0]["variablesReference"]);
+    let names: Vec<_> = locals.iter().map(|(name, ..)| name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "total"]);
+
+    let globals = client.variables(&scopes[1]["variablesReference"]);
+    let (_, value, reference) = globals.iter().find(|(name, ..)| name == "items").unwrap();
+    assert_eq!(value, "[1, [2, 3]]");
+
+    let items = client.variables(reference);
+    assert_eq!(items[0].0, "0");
+    assert_eq!(items[0].2, 0);
+    assert_eq!(client.variables(&items[1].2)[1].1, "3");
+
+    let (_, _, reference) = globals.iter().find(|(name, ..)| name == "point").unwrap();
+    let point = client.variables(reference);
+    assert_eq!(point[0].0, "\"x\"");
+    assert_eq!(point[1].1, "2");
+
+    // disconnecting stops the paused script
+    client.finish();
+}
+
+#[test]
+fn expressions_are_evaluated_in_the_paused_frame() {
+    let path = script("dap_evaluate.glass");
+    let mut client = Client::launch(&path, &[3], false);
+    client.wait_for("stopped");
+
+    let response = client.request(
+        "evaluate",
+        json!({ "expression": "total * a", "frameId": 1 }),
+    );
+    assert_eq!(response["body"]["result"], "3");
+
+    let response = client.request("evaluate", json!({ "expression": "y", "frameId": 0 }));
+    assert_eq!(response["success"], false);
+    assert_eq!(response["message"], "Variable 'y' is not defined");
+
+    let response = client.request("evaluate", json!({ "expression": "items[1]" }));
+    assert_eq!(response["body"]["result"], "[2, 3]");
+    assert_ne!(response["body"]["variablesReference"], 0);
+
+    // not paused anymore
+    client.request("continue", json!({ "threadId": 1 }));
+    client.wait_for("terminated");
+    let response = client.request("evaluate", json!({ "expression": "1" }));
+    assert_eq!(response["success"], false);
+
+    client.finish();
+}
@@ -0,0 +1,107 @@
+//! Drives `glass debug` through its prompt, checking where scripts pause and what it prints.
+
+mod common;
+
+use common::{glass_with_input, script, stdout};
+use std::path::Path;
+use std::process::Output;
+
+const SCRIPT: &str = "\
+add = func(a, b) => {
+    total = a + b
+    return total
+}
+
+x = 1
+y = add(x, 2)
+for i in 0..3 {
+    x = x + i
+}
+println(x, y)
+";
+
+// runs the script in the debugger, typing one command per line
+fn debug(path: &Path, commands: &[&str]) -> Output {
+    let mut input = commands.join("\n");
+    input.push('\n');
+
+    glass_with_input(["debug".as_ref(), path.as_os_str()], &input)
+}
+
+// the lines the script paused at, in order
+fn pauses(output: &Output) -> Vec<String> {
+    stdout(output)
+        .lines()
+        .filter_map(|line| line.split("Paused in ").nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn scripts_pause_at_breakpoints() {
+    let path = script("breakpoints.glass", SCRIPT);
+    let file = path.to_str().unwrap();
+    let output = debug(&path, &["break 9", "c", "c", "delete 9", "c"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        pauses(&output),
+        [
+            format!("global at {}:1", file),
+            format!("global at {}:9", file),
+            format!("global at {}:9", file),
+        ]
+    );
+    assert!(stdout(&output).contains("Breakpoint removed from line 9"));
+    assert!(stdout(&output).ends_with("4 3\nThe script finished\n"));
+}
+
+#[test]
+fn stepping_goes_into_over_and_out_of_calls() {
+    let path = script("stepping.glass", SCRIPT);
+    let file = path.to_str().unwrap();
+
+    let output = debug(&path, &["n", "n", "s", "s", "o", "q"]);
+    assert_eq!(
+        pauses(&output),
+        [
+            format!("global at {}:1", file),
+            format!("global at {}:6", file),
+            format!("global at {}:7", file),
+            format!("add at {}:2", file),
+            format!("add at {}:3", file),
+            format!("global at {}:8", file),
+        ]
+    );
+
+    let output = debug(&path, &["n", "n", "n", "n", "q"]);
+    assert_eq!(pauses(&output)[3], format!("global at {}:8", file));
+}
+
+#[test]
+fn paused_scripts_can_be_inspected() {
+    let path = script("inspect.glass", SCRIPT);
+    let output = debug(
+        &path,
+        &["b 3", "c", "vars", "stack", "p total * 10", "p x", "q"],
+    );
+    let stdout = stdout(&output);
+
+    assert!(stdout.contains("a = 1\nb = 2\ntotal = 3\n"), "{}", stdout);
+    assert!(stdout.contains("#1 add at line 3\n#0 global at line 7\n"));
+    assert!(stdout.contains("(glass) 30\n(glass) 1\n"));
+
+    // quitting stops the script before it prints anything
+    assert!(output.status.success());
+    assert!(!stdout.contains("4 3"));
+}
+
+#[test]
+fn evaluation_errors_do_not_stop_the_script() {
+    let path = script("evaluate_error.glass", "x = 1\nprintln(x)\n");
+    let output = debug(&path, &["p missing", "c"]);
+    let stdout = stdout(&output);
+
+    assert!(stdout.contains("Variable 'missing' is not defined"));
+    assert!(stdout.ends_with("1\nThe script finished\n"));
+}
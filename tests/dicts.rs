@@ -0,0 +1,77 @@
+//! Checks that dictionaries keep the order their keys were inserted in and which keys they take.
+
+mod common;
+
+use common::run_both;
+
+#[test]
+fn keys_keep_their_insertion_order() {
+    let src = "\
+d = {\"b\": 1, \"a\": 2, \"c\": 3}
+d[\"b\"] = 10
+d[\"d\"] = 4
+d.remove(\"a\")
+d[\"a\"] = 5
+keys = []
+for key in d {
+    keys.push(key)
+}
+println(keys)
+println(d)
+println(d.keys(), d.values())
+println({\"x\": 1, \"y\": 2} == {\"y\": 2, \"x\": 1})
+";
+
+    assert_eq!(
+        run_both("dict_order.glass", src),
+        "\
+[\"b\", \"c\", \"d\", \"a\"]
+{\"b\": 10, \"c\": 3, \"d\": 4, \"a\": 5}
+[\"b\", \"c\", \"d\", \"a\"] [10, 3, 4, 5]
+true
+"
+    );
+}
+
+#[test]
+fn any_hashable_value_is_a_key() {
+    let src = "\
+d = {1: \"one\", true: \"yes\", (1, \"a\"): \"tuple\", \"1\": \"string\", 2.5: void}
+println(d[1], d[true], d[(1, \"a\")], d[\"1\"], d[2.5], len(d))
+println({1: \"int\", 1.0: \"float\"})
+";
+
+    assert_eq!(
+        run_both("dict_keys.glass", src),
+        "one yes tuple string void 5\n{1: \"float\"}\n"
+    );
+}
+
+#[test]
+fn unhashable_keys_are_an_error() {
+    let src = "\
+d = {}
+for key in [[1], {}, func() => 1] {
+    try {
+        d[key] = 1
+    } catch err {
+        println(err.kind, err.message)
+    }
+}
+try {
+    println({[1]: 2})
+} catch err {
+    println(err.kind)
+}
+";
+
+    assert_eq!(
+        run_both("dict_unhashable.glass", src),
+        "\
+UnhashableType Type 'list' cannot be used as a dictionary key or set item
+UnhashableType Type 'dictionary' cannot be used as a dictionary key or set item
+UnhashableType Type 'function' cannot be used as a dictionary key or set item
+UnhashableType
+"
+    );
+}
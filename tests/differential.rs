@@ -0,0 +1,72 @@
+//! Runs every program in `tests/programs` with both the tree-walking interpreter and the
+//! bytecode VM, and checks that they behave identically.
+
+mod common;
+
+use common::{command, scripts_in, stderr, stdout};
+use std::path::Path;
+use std::process::Output;
+
+fn run(path: &Path, use_vm: bool) -> Output {
+    let mut command = command();
+
+    if use_vm {
+        command.arg("--vm");
+    }
+
+    command
+        .arg(path)
+        .output()
+        .unwrap_or_else(|err| panic!("Failed to run {}: {}", path.display(), err))
+}
+
+#[test]
+fn vm_matches_tree_walker() {
+    let programs = scripts_in("tests/programs");
+    assert!(!programs.is_empty(), "No programs found in tests/programs");
+
+    let mut mismatches = Vec::new();
+
+    for program in &programs {
+        let walked = run(program, false);
+        let compiled = run(program, true);
+
+        if walked.stdout != compiled.stdout
+            || walked.stderr != compiled.stderr
+            || walked.status.code() != compiled.status.code()
+        {
+            mismatches.push(format!(
+                "{}\n--- tree-walker (exit {:?})\n{}{}\n--- vm (exit {:?})\n{}{}",
+                program.display(),
+                walked.status.code(),
+                stdout(&walked),
+                stderr(&walked),
+                compiled.status.code(),
+                stdout(&compiled),
+                stderr(&compiled),
+            ));
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} programs behaved differently on the VM:\n\n{}",
+        mismatches.len(),
+        programs.len(),
+        mismatches.join("\n\n")
+    );
+}
+
+#[test]
+fn programs_produce_output() {
+    // guards against both engines failing the same way, e.g. by not running anything at all
+    for program in scripts_in("tests/programs") {
+        let output = run(&program, false);
+
+        assert!(
+            !output.stdout.is_empty(),
+            "{} printed nothing",
+            program.display()
+        );
+    }
+}
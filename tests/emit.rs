@@ -0,0 +1,120 @@
+//! Checks what `--emit` prints for each phase, and the execution trace of `--trace`.
+
+use serde_json::Value;
+mod common;
+
+use common::{glass, glass_with_input, script, stderr, stdout};
+use std::process::Output;
+
+const SCRIPT: &str = "x = 2\nf = func(n) => {\n    return n * x\n}\nprintln(f(3))\n";
+
+// runs SCRIPT with `args` before its path
+fn run(args: &[&str], name: &str) -> Output {
+    let path = script(name, SCRIPT);
+    glass(args.iter().map(AsRef::as_ref).chain([path.as_os_str()]))
+}
+
+#[test]
+fn tokens_are_printed_with_their_position() {
+    let output = run(&["--emit", "tokens"], "emit_tokens.glass");
+    let stdout = stdout(&output);
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines[..3],
+        ["1:1 Identifier(\"x\")", "1:3 Equal", "1:5 Number(2.0)"]
+    );
+    assert!(lines.contains(&"3:14 Star"), "{}", stdout);
+    // the script isn't run
+    assert!(!lines.contains(&"6"), "{}", stdout);
+}
+
+#[test]
+fn code_from_eval_and_stdin_is_emitted() {
+    let output = glass(["--emit", "tokens", "-e", "x = 2"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1:1 Identifier(\"x\")\n1:3 Equal\n1:5 Number(2.0)\n"
+    );
+
+    let output = glass_with_input(["--emit", "tokens", "-"], "println(1)");
+
+    assert!(output.status.success());
+    assert!(
+        stdout(&output).starts_with("1:1 Identifier(\"println\")\n"),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn the_ast_is_printed_as_json() {
+    let output = run(&["--emit", "ast-json"], "emit_ast_json.glass");
+    let ast: Value = serde_json::from_slice(&output.stdout).expect("Invalid JSON");
+
+    assert_eq!(ast["type"], "Block");
+
+    let statements = ast["statements"].as_array().unwrap();
+    assert_eq!(statements.len(), 3);
+    assert_eq!(statements[0]["type"], "Assignment");
+    assert_eq!(statements[0]["left"]["name"], "x");
+    assert_eq!(statements[0]["left"]["span"]["start"], 0);
+    assert_eq!(statements[0]["right"]["value"], 2.0);
+
+    let function = &statements[1]["right"];
+    assert_eq!(function["type"], "FunctionDefinition");
+    assert_eq!(function["params"][0]["name"], "n");
+    assert_eq!(function["body"]["statements"][0]["value"]["op"], "*");
+}
+
+#[test]
+fn dump_ast_is_the_same_as_emitting_the_ast() {
+    let dumped = run(&["--dump-ast"], "dump_ast.glass");
+    let emitted = run(&["--emit", "ast"], "dump_ast.glass");
+
+    assert!(dumped.status.success());
+    assert_eq!(dumped.stdout, emitted.stdout);
+}
+
+#[test]
+fn verbose_works_with_the_vm() {
+    let quiet = run(&["--vm"], "quiet_vm.glass");
+    assert_eq!(stdout(&quiet), "6\n");
+
+    let output = run(&["--vm", "--verbose"], "verbose_vm.glass");
+    let stdout = stdout(&output);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.contains("Read 56 bytes from"), "{}", stdout);
+    assert!(stdout.ends_with("6\n"), "{}", stdout);
+    // the debug output isn't part of verbose mode
+    assert!(!stdout.contains("Bytecode >"), "{}", stdout);
+}
+
+#[test]
+fn trace_prints_every_evaluated_node() {
+    let output = run(&["--trace"], "trace.glass");
+    let stdout = stdout(&output);
+    let stderr = stderr(&output);
+
+    // the trace doesn't get mixed into what the script prints
+    assert_eq!(stdout, "6\n");
+    assert!(
+        stderr.contains("trace.glass:3:14 BinaryOp -> 6\n"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("trace.glass:5:10 FunctionCall -> 6\n"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("trace.glass:3:16 Identifier -> 2\n"),
+        "{}",
+        stderr
+    );
+}
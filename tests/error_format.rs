@@ -0,0 +1,128 @@
+//! Checks the exit codes glass stops with and the JSON printed by `--error-format json`.
+
+mod common;
+
+use common::glass;
+use serde_json::{json, Value};
+use std::process::Output;
+
+// every line of stderr is a diagnostic
+fn diagnostics(output: &Output) -> Vec<Value> {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Invalid JSON"))
+        .collect()
+}
+
+#[test]
+fn errors_have_distinct_exit_codes() {
+    assert_eq!(glass(["-e", "x = (1"]).status.code(), Some(65));
+    assert_eq!(glass(["-e", "break"]).status.code(), Some(65));
+    assert_eq!(glass(["-e", "[1][3]"]).status.code(), Some(1));
+    assert_eq!(glass(["missing.glass"]).status.code(), Some(1));
+    assert_eq!(glass(["-e", "println(1)"]).status.code(), Some(0));
+}
+
+#[test]
+fn runtime_errors_are_printed_as_json() {
+    let output = glass([
+        "--error-format",
+        "json",
+        "-e",
+        "x = 1\nprintln({}[\"key\"])",
+    ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        diagnostics(&output),
+        [json!({
+            "severity": "error",
+            "kind": "KeyNotFound",
+            "message": "Key \"key\" not found in dictionary",
+            "file": "<eval>",
+            "line": 2,
+            "column": 11,
+            "span": { "start": 16, "end": 17 },
+        })]
+    );
+}
+
+#[test]
+fn syntax_errors_and_warnings_are_printed_as_json() {
+    let output = glass(["--error-format=json", "-e", "x = \"\\q\""]);
+    let errors = diagnostics(&output);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(errors[0]["kind"], "UnknownEscapeSequence");
+    assert_eq!(errors[0]["message"], "Unknown escape sequence '\\q'");
+    assert_eq!(errors[0]["line"], 1);
+    assert_eq!(errors[0]["column"], 5);
+
+    let output = glass([
+        "--error-format",
+        "json",
+        "-e",
+        "f = func() => {\n    unused = 1\n}",
+    ]);
+    let warnings = diagnostics(&output);
+
+    assert!(output.status.success());
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["severity"], "warning");
+    assert_eq!(warnings[0]["kind"], "UnusedVariable");
+    assert_eq!(warnings[0]["line"], 2);
+}
+
+#[test]
+fn text_and_json_errors_report_the_same_column() {
+    let src = "if true {\n    println({}[\"key\"])\n}";
+    let json = glass(["--error-format=json", "-e", src]);
+    let text = glass(["--error-format=text", "-e", src]);
+    let stderr = String::from_utf8_lossy(&text.stderr);
+
+    assert_eq!(diagnostics(&json)[0]["column"], 15);
+    assert!(stderr.contains("(Ln:2, Col:15..16)"), "{}", stderr);
+    // the line is printed without its indentation, and the caret follows it
+    assert!(
+        stderr.contains("\tprintln({}[\"key\"])\n\t          ^\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn the_end_of_the_file_has_a_position() {
+    let output = glass(["--error-format=json", "-e", "x = [\n    1,\n    2 +\n\n"]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        diagnostics(&output),
+        [json!({
+            "severity": "error",
+            "kind": "UnexpectedEndOfInput",
+            "message": "Unexpected end of file",
+            "file": "<eval>",
+            "line": 3,
+            "column": 8,
+            "span": { "start": 20, "end": 20 },
+        })]
+    );
+}
+
+#[test]
+fn errors_without_a_position_have_null_fields() {
+    let output = glass(["--error-format", "json", "missing.glass"]);
+
+    assert_eq!(
+        diagnostics(&output),
+        [json!({
+            "severity": "error",
+            "kind": "FileNotFound",
+            "message": "File 'missing.glass' not found",
+            "file": "missing.glass",
+            "line": null,
+            "column": null,
+            "span": null,
+        })]
+    );
+}
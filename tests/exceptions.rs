@@ -0,0 +1,100 @@
+//! Checks how errors that are never caught are reported, and that runtime errors point at the
+//! operation that raised them on both engines.
+
+mod common;
+
+use common::{command, glass, script};
+use std::path::Path;
+
+/// Runs a script with both engines, returning their stderr after checking that it matches.
+fn stderr(path: &Path) -> String {
+    let run = |use_vm: bool| {
+        let mut command = command();
+
+        if use_vm {
+            command.arg("--vm");
+        }
+
+        let output = command.arg(path).output().expect("Failed to run glass");
+        common::stderr(&output)
+    };
+
+    let walked = run(false);
+    assert_eq!(walked, run(true), "the engines reported different errors");
+
+    walked
+}
+
+#[test]
+fn uncaught_errors_show_where_they_were_thrown() {
+    let path = script(
+        "uncaught.glass",
+        "check = func(n) => {\n    if n < 0 {\n        throw \"negative\"\n    }\n}\ncheck(-1)",
+    );
+    let stderr = stderr(&path);
+
+    assert!(stderr.contains("Uncaught Error: negative"), "{}", stderr);
+    assert!(
+        stderr.contains("\tthrow \"negative\"\n\t^^^^^\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("(Ln:3, Col:9..14)"), "{}", stderr);
+}
+
+#[test]
+fn thrown_values_that_are_not_errors_are_shown_as_they_are() {
+    let path = script("uncaught_value.glass", "throw [1, \"two\"]");
+    let stderr = stderr(&path);
+
+    assert!(
+        stderr.contains("Uncaught exception [1, \"two\"]"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn runtime_errors_point_at_the_operation() {
+    let path = script(
+        "located.glass",
+        "scores = {\"a\": 1}\ntotal = 0\ntotal += scores[\"a\"]\nprintln(total + scores[\"b\"])",
+    );
+    let stderr = stderr(&path);
+
+    assert!(
+        stderr.contains("Key \"b\" not found in dictionary"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("(Ln:4, Col:23..24)"), "{}", stderr);
+}
+
+#[test]
+fn caught_errors_carry_their_kind_and_position() {
+    let path = script(
+        "caught.glass",
+        "try {\n    [].missing()\n} catch err {\n    println(err.kind, err.span)\n}",
+    );
+    let output = glass([&path]);
+
+    assert_eq!(common::stdout(&output), "NoSuchMethod (2, 8)\n");
+}
+
+#[test]
+fn dividing_by_zero_raises_a_catchable_error() {
+    let path = script(
+        "division_by_zero.glass",
+        "divide = func(a, b) => {\n    try {\n        return a / b\n    } catch err {\n        return err.kind\n    }\n}\nx = 7\ntry {\n    x %= 0\n} catch err {\n    println(err.message)\n}\nprintln(divide(1, 0), divide(0, 0), divide(-1, 0), divide(6, 3), x)",
+    );
+
+    for engine in [&[][..], &["--vm"][..]] {
+        let output = glass(engine.iter().map(AsRef::as_ref).chain([path.as_os_str()]));
+
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            common::stdout(&output),
+            "Cannot use operation '%' with a divisor of zero\nDivisionByZero DivisionByZero DivisionByZero 2 7\n"
+        );
+    }
+}
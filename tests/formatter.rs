@@ -0,0 +1,142 @@
+//! Checks the style `glass fmt` prints scripts in, and that formatting is idempotent.
+
+mod common;
+
+use common::{command, scratch_dir};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+fn fmt(args: &[&str], path: &Path) -> Output {
+    command()
+        .arg("fmt")
+        .args(args)
+        .arg(path)
+        .output()
+        .expect("Failed to run glass")
+}
+
+const MESSY: &str = "\n\n// leading comment\nx=1+2*-3   // trailing\nd={\"a\":1,\"b\":[1,2,3][0]}\nf = func(a,b)->number=>{\n\n\n    // inside\n  return a-b\n}\n\n\n\nif x>1{println( \"big\" )}else{\nprintln(f(1,-2), -x, not true)\n}\nnested = foo({\n\"k\": 1,\n})\n";
+
+const FORMATTED: &str = "// leading comment\nx = 1 + 2 * -3 // trailing\nd = {\"a\": 1, \"b\": [1, 2, 3][0]}\nf = func(a, b) -> number => {\n    // inside\n    return a - b\n}\n\nif x > 1 {\n    println(\"big\")\n} else {\n    println(f(1, -2), -x, not true)\n}\nnested = foo({\n    \"k\": 1,\n})\n";
+
+#[test]
+fn scripts_are_printed_in_the_canonical_style() {
+    let dir = scratch_dir("fmt_style");
+    let script = dir.join("messy.glass");
+    fs::write(&script, MESSY).expect("Failed to write script");
+
+    let output = fmt(&[], &script);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&script).unwrap(), FORMATTED);
+}
+
+#[test]
+fn line_breaks_are_normalized() {
+    let dir = scratch_dir("fmt_line_breaks");
+    let scripts = [
+        "x = 1 y = [1,\n2, 3]\nif x { y.push(4) } else { println(\"no\") }\nz = x +\n    y[0]\nfor i in 0..3 { if i == 1 { continue } println(i) }\n",
+        "x = 1\ny = [\n    1, 2,\n    3]\nif x\n{\n\ny.push(4)\n}\nelse\n{\nprintln(\"no\")\n\n}\nz = x\n    + y[0]\nfor i in 0..3 {\n    if i == 1 { continue }\n    println(i)\n}\n",
+    ];
+
+    for (index, src) in scripts.iter().enumerate() {
+        let script = dir.join(format!("broken_{}.glass", index));
+        fs::write(&script, src).expect("Failed to write script");
+
+        assert!(fmt(&[], &script).status.success());
+        assert_eq!(
+            fs::read_to_string(&script).unwrap(),
+            "\
+x = 1
+y = [
+    1,
+    2,
+    3
+]
+if x {
+    y.push(4)
+} else {
+    println(\"no\")
+}
+z = x + y[0]
+for i in 0..3 {
+    if i == 1 {
+        continue
+    }
+    println(i)
+}
+"
+        );
+    }
+}
+
+#[test]
+fn check_lists_unformatted_scripts_without_changing_them() {
+    let dir = scratch_dir("fmt_check");
+    fs::write(dir.join("messy.glass"), MESSY).expect("Failed to write script");
+    fs::write(dir.join("tidy.glass"), FORMATTED).expect("Failed to write script");
+
+    let output = fmt(&["--check"], &dir);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("messy.glass"), "{}", stdout);
+    assert!(!stdout.contains("tidy.glass"), "{}", stdout);
+    assert_eq!(fs::read_to_string(dir.join("messy.glass")).unwrap(), MESSY);
+}
+
+#[test]
+fn indent_width_is_configurable() {
+    let dir = scratch_dir("fmt_indent");
+    let script = dir.join("indent.glass");
+    fs::write(&script, "while true {\nif false {\nbreak\n}\n}\n").expect("Failed to write script");
+
+    fmt(&["--indent", "2"], &script);
+
+    assert_eq!(
+        fs::read_to_string(&script).unwrap(),
+        "while true {\n  if false {\n    break\n  }\n}\n"
+    );
+}
+
+#[test]
+fn scripts_that_dont_parse_are_left_alone() {
+    let dir = scratch_dir("fmt_invalid");
+    let script = dir.join("invalid.glass");
+    fs::write(&script, "x = = 2\n").expect("Failed to write script");
+
+    let output = fmt(&[], &script);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unexpected token '='"));
+    assert_eq!(fs::read_to_string(&script).unwrap(), "x = = 2\n");
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let dir = scratch_dir("fmt_idempotent");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    for source in ["tests/programs", "tests/golden"] {
+        for entry in fs::read_dir(root.join(source)).expect("Failed to read scripts") {
+            let path = entry.expect("Failed to read directory entry").path();
+
+            // the golden scripts with syntax errors can't be formatted
+            if path.extension().is_some_and(|ext| ext == "glass")
+                && fmt(&["--check"], &path).stderr.is_empty()
+            {
+                fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+    }
+
+    fs::write(dir.join("messy.glass"), MESSY).expect("Failed to write script");
+
+    assert!(fmt(&[], &dir).status.success());
+
+    let output = fmt(&["--check"], &dir);
+    assert!(
+        output.status.success(),
+        "formatting again changed: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
@@ -0,0 +1,77 @@
+//! Checks function calls, closures, loops and the control flow statements that leave them.
+
+mod common;
+
+use common::{glass, script, stdout};
+use std::process::Output;
+
+// runs `src` as a script
+fn run(name: &str, src: &str) -> Output {
+    glass([script(name, src)])
+}
+
+#[test]
+fn functions_are_called_with_their_arguments() {
+    let src = "\
+square = func(x) => x * x
+fib = func(n) => {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+println(square(7), fib(10), square, type(square))
+";
+
+    assert_eq!(
+        stdout(&run("calls.glass", src)),
+        "49 55 <func square> function\n"
+    );
+}
+
+#[test]
+fn closures_see_the_variables_they_were_defined_in() {
+    let src = "\
+make_adder = func(n) => {
+    return func(x) => x + n
+}
+add_two = make_adder(2)
+n = 100
+println(add_two(1), make_adder(5)(1))
+";
+
+    assert_eq!(stdout(&run("closures.glass", src)), "3 6\n");
+}
+
+#[test]
+fn loops_stop_at_break_and_skip_at_continue() {
+    let src = "\
+total = 0
+for i in 0..10 {
+    if i == 5 {
+        break
+    }
+    if i % 2 == 0 {
+        continue
+    }
+    total += i
+}
+for i in 1..=3 { print(i) }
+for x in [\"a\", \"b\"] { print(x) }
+i = 0
+while i < 3 { i += 1 }
+println(\"\", total, i)
+";
+
+    assert_eq!(stdout(&run("loops.glass", src)), "123ab 4 3\n");
+}
+
+#[test]
+fn calls_fail_with_the_wrong_number_of_arguments() {
+    let output = run("arity.glass", "f = func(a, b) => a\nf(1)");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stdout(&output).is_empty());
+    assert!(stderr.contains("'f'"), "{}", stderr);
+    assert!(stderr.contains("2"), "{}", stderr);
+}
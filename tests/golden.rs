@@ -0,0 +1,100 @@
+//! Runs every script in `tests/golden` and compares its stdout, stderr and exit code to the
+//! `.expected` file next to it.
+//!
+//! Set `GLASS_UPDATE_GOLDEN=1` to write the current output to the `.expected` files instead,
+//! then review the changes to them before committing.
+
+mod common;
+
+use common::{command, scripts_in, stderr, stdout};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const UPDATE_VAR: &str = "GLASS_UPDATE_GOLDEN";
+
+/// Runs a script from the crate root, so that the paths in its errors don't depend on where
+/// the crate is checked out, and formats everything it did like an `.expected` file.
+fn run(script: &Path) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let relative = script.strip_prefix(root).unwrap_or(script);
+
+    let output = command()
+        .arg(relative)
+        .current_dir(root)
+        .output()
+        .unwrap_or_else(|err| panic!("Failed to run {}: {}", script.display(), err));
+
+    format!(
+        "exit: {}\n--- stdout\n{}--- stderr\n{}",
+        output
+            .status
+            .code()
+            .map_or("signal".to_string(), |code| code.to_string()),
+        stdout(&output),
+        stderr(&output),
+    )
+}
+
+#[test]
+fn output_matches_expected() {
+    let scripts = scripts_in("tests/golden");
+    assert!(!scripts.is_empty(), "No scripts found in tests/golden");
+
+    let update = env::var_os(UPDATE_VAR).is_some();
+    let mut mismatches = Vec::new();
+
+    for script in &scripts {
+        let actual = run(script);
+        let expected_path = script.with_extension("expected");
+
+        if update {
+            fs::write(&expected_path, &actual).unwrap_or_else(|err| {
+                panic!("Failed to write {}: {}", expected_path.display(), err)
+            });
+            continue;
+        }
+
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => mismatches.push(format!(
+                "{}\n=== expected\n{}=== actual\n{}",
+                script.display(),
+                expected,
+                actual
+            )),
+            Err(_) => mismatches.push(format!(
+                "{} has no {}, run with {}=1 to create it",
+                script.display(),
+                expected_path.display(),
+                UPDATE_VAR
+            )),
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} script(s) did not match their expected output (run with {}=1 to update them):\n\n{}",
+        mismatches.len(),
+        scripts.len(),
+        UPDATE_VAR,
+        mismatches.join("\n\n")
+    );
+}
+
+#[test]
+fn every_expected_file_has_a_script() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    for entry in fs::read_dir(dir).expect("Failed to read tests/golden") {
+        let path = entry.expect("Failed to read directory entry").path();
+
+        if path.extension().is_some_and(|ext| ext == "expected") {
+            assert!(
+                path.with_extension("glass").exists(),
+                "{} is left over from a script that no longer exists",
+                path.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Talks to `glass lsp` like an editor would, over its stdin and stdout.
+
+mod common;
+
+use common::command;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+const URI: &str = "file:///script.glass";
+
+// `total` is a number and `name` a string, and `square` is defined on line 1
+const SCRIPT: &str = "// squares\nsquare = func(n: number) => {\n    return n * n\n}\ntotal = square(3)\nname = \"glass\"\nstruct Point { x, y }\nfor i in 0..3 { println(i + total) }\n";
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    // notifications received while waiting for a response
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = command()
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to run glass");
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut client = Self {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+            notifications: Vec::new(),
+        };
+
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["hoverProvider"], true);
+        client.notify("initialized", json!({}));
+
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                _ if header.trim_end().is_empty() => break,
+                _ => {}
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+
+        loop {
+            let message = self.receive();
+
+            if message["id"] == id {
+                return message["result"].clone();
+            }
+
+            self.notifications.push(message);
+        }
+    }
+
+    fn open(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "glass", "version": 1, "text": text },
+            }),
+        );
+
+        self.diagnostics()
+    }
+
+    /// Waits for the next diagnostics to be published.
+    fn diagnostics(&mut self) -> Vec<Value> {
+        let message = match self.notifications.pop() {
+            Some(message) => message,
+            None => self.receive(),
+        };
+
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        message["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+
+    fn at(&mut self, method: &str, line: u64, character: u64) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+#[test]
+fn diagnostics_are_published_when_a_document_changes() {
+    let mut client = Client::start();
+    assert_eq!(client.open(SCRIPT), Vec::<Value>::new());
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "x = 1 +\n" }],
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["code"], "UnexpectedEndOfInput");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 3 },
+            "contentChanges": [{ "text": "f = func() => {\n    unused = 1\n}\ny = \"a\"\nprintln(y - 1)\n" }],
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+
+    // the warning for the unused variable comes from the resolver, before type checking
+    assert_eq!(diagnostics[0]["severity"], 2);
+    assert_eq!(diagnostics[0]["code"], "UnusedVariable");
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 10 } })
+    );
+    assert_eq!(diagnostics[1]["code"], "InvalidOperation");
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], 4);
+
+    client.shutdown();
+}
+
+#[test]
+fn the_last_analysis_is_kept_while_a_document_does_not_parse() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": format!("{}x = (", SCRIPT) }],
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "UnexpectedEndOfInput");
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 8);
+
+    assert_eq!(
+        client.at("textDocument/hover", 4, 2)["contents"]["value"],
+        "total: number"
+    );
+    assert_eq!(
+        client.at("textDocument/definition", 4, 9)["range"]["start"]["line"],
+        1
+    );
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    assert_eq!(symbols.as_array().unwrap().len(), 4);
+
+    client.shutdown();
+}
+
+#[test]
+fn every_error_after_parsing_is_published() {
+    let mut client = Client::start();
+    let diagnostics = client.open("println(a)\nbreak\nprintln(b)\nc = \"c\" - 1\n");
+    let codes: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(
+        codes,
+        [
+            "UndefinedVariable",
+            "ControlFlowOutsideLoop",
+            "UndefinedVariable",
+            "AlwaysFails",
+            // the type checker still runs, since the whole script was parsed
+            "InvalidOperation",
+        ]
+    );
+    assert_eq!(diagnostics[2]["range"]["start"]["line"], 2);
+    assert_eq!(diagnostics[3]["severity"], 2);
+
+    client.shutdown();
+}
+
+#[test]
+fn hover_shows_the_inferred_type() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let hover = client.at("textDocument/hover", 4, 2);
+    assert_eq!(hover["contents"]["value"], "total: number");
+    assert_eq!(
+        hover["range"],
+        json!({ "start": { "line": 4, "character": 0 }, "end": { "line": 4, "character": 5 } })
+    );
+
+    assert_eq!(
+        client.at("textDocument/hover", 5, 0)["contents"]["value"],
+        "name: string"
+    );
+    assert_eq!(
+        client.at("textDocument/hover", 7, 4)["contents"]["value"],
+        "i: number"
+    );
+    assert_eq!(client.at("textDocument/hover", 0, 3), Value::Null);
+
+    client.shutdown();
+}
+
+#[test]
+fn definitions_are_found_for_variables_and_functions() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    // the call of square
+    let definition = client.at("textDocument/definition", 4, 10);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"],
+        json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 6 } })
+    );
+
+    // the parameter n, used in the body
+    let definition = client.at("textDocument/definition", 2, 11);
+    assert_eq!(
+        definition["range"],
+        json!({ "start": { "line": 1, "character": 14 }, "end": { "line": 1, "character": 15 } })
+    );
+
+    // builtins aren't defined in the document
+    assert_eq!(client.at("textDocument/definition", 7, 17), Value::Null);
+
+    client.shutdown();
+}
+
+#[test]
+fn document_symbols_are_the_top_level_definitions() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let symbols: Vec<_> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| {
+            (
+                symbol["name"].as_str().unwrap(),
+                symbol["kind"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        symbols,
+        [("square", 12), ("total", 13), ("name", 13), ("Point", 23)]
+    );
+
+    client.shutdown();
+}
+
+#[test]
+fn completion_offers_builtins_and_keywords() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let items = client.at("textDocument/completion", 8, 0);
+    let labels: Vec<_> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+
+    for label in [
+        "println",
+        "assert_eq",
+        "Error",
+        "while",
+        "func",
+        "return",
+        "square",
+    ] {
+        assert!(labels.contains(&label), "'{}' is not offered", label);
+    }
+
+    client.shutdown();
+}
+
+#[test]
+fn unknown_requests_are_rejected() {
+    let mut client = Client::start();
+
+    client.send(json!({ "jsonrpc": "2.0", "id": 99, "method": "glass/unknown" }));
+    let response = client.receive();
+    assert_eq!(response["id"], 99);
+    assert_eq!(response["error"]["code"], -32601);
+
+    client.shutdown();
+}
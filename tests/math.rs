@@ -0,0 +1,73 @@
+//! Checks the `math` module's domain errors and that its fields can't be replaced.
+
+mod common;
+
+use common::run_both;
+
+#[test]
+fn functions_raise_domain_errors_outside_their_domain() {
+    let src = "\
+calls = [
+    func() => math.pow(0, -1),
+    func() => math.pow(-8, 0.5),
+    func() => math.sqrt(-1),
+    func() => math.log(0),
+    func() => math.log(-1),
+    func() => math.log(8, 1),
+    func() => math.log(8, 0),
+]
+for call in calls {
+    try {
+        println(call())
+    } catch err {
+        println(err.kind, err.message)
+    }
+}
+println(math.pow(0, 0), math.pow(-2, 3), math.sqrt(0), math.log(8, 2))
+";
+
+    assert_eq!(
+        run_both("math_domain.glass", src),
+        "\
+DomainError 'math.pow' is not defined for 0 and -1
+DomainError 'math.pow' is not defined for -8 and 0.5
+DomainError 'math.sqrt' is not defined for -1
+DomainError 'math.log' is not defined for 0
+DomainError 'math.log' is not defined for -1
+DomainError 'math.log' is not defined for 8 and 1
+DomainError 'math.log' is not defined for 8 and 0
+1 -8 0 3
+"
+    );
+}
+
+#[test]
+fn module_fields_are_read_only() {
+    let src = "\
+m = math
+for assign in [func() => { math.pi = 3 }, func() => { m.sqrt = 0 }, func() => { math.e += 1 }] {
+    try {
+        assign()
+    } catch err {
+        println(err.kind, err.message)
+    }
+}
+try {
+    math.missing = 1
+} catch err {
+    println(err.kind)
+}
+println(math.pi, math.sqrt(4), math.e > 2)
+";
+
+    assert_eq!(
+        run_both("math_read_only.glass", src),
+        "\
+ReadOnlyField Field 'pi' of type 'math' is read-only
+ReadOnlyField Field 'sqrt' of type 'math' is read-only
+ReadOnlyField Field 'e' of type 'math' is read-only
+NoSuchField
+3.141592653589793 2 true
+"
+    );
+}
@@ -0,0 +1,88 @@
+//! Checks calling methods on values with `value.name(args)`, and the error for a method that the
+//! type doesn't have.
+
+mod common;
+
+use common::run_both;
+
+#[test]
+fn list_methods_change_the_list() {
+    let src = "\
+xs = [3, 1, 2]
+ys = xs
+xs.push(4)
+xs.insert(0, 0)
+println(ys)
+println(xs.pop(), xs.remove(1), xs, xs.len())
+println(xs.contains(2), xs.contains(9), xs.find(2), xs.find(9), [1, 2].join(\"-\"))
+";
+
+    assert_eq!(
+        run_both("list_methods.glass", src),
+        "[0, 3, 1, 2, 4]\n4 3 [0, 1, 2] 3\ntrue false 2 -1 1-2\n"
+    );
+}
+
+#[test]
+fn dict_methods_change_the_dict() {
+    let src = "\
+d = {\"one\": 1}
+d.insert(\"two\", 2)
+println(d.keys(), d.values())
+println(d.remove(\"one\"), d, d.contains(\"two\"))
+";
+
+    assert_eq!(
+        run_both("dict_methods.glass", src),
+        "[\"one\", \"two\"] [1, 2]\n1 {\"two\": 2} true\n"
+    );
+}
+
+#[test]
+fn string_methods_return_new_values() {
+    let src = "\
+s = \"  a,b,c  \"
+t = s.trim()
+println(s.len(), t, t.split(\",\"), \"+\".join(t.split(\",\")))
+println(t.replace(\",\", \";\"), t.find(\"b\"), t.find(\"z\"), t.contains(\"c\"), t.upper())
+println((-2.5).abs(), 2.5.floor(), 2.5.round())
+";
+
+    assert_eq!(
+        run_both("string_methods.glass", src),
+        "9 a,b,c [\"a\", \"b\", \"c\"] a+b+c\na;b;c 2 -1 true A,B,C\n2.5 2 3\n"
+    );
+}
+
+#[test]
+fn missing_methods_name_the_type() {
+    let src = "\
+calls = [
+    func() => [].missing(),
+    func() => \"s\".push(1),
+    func() => 1.keys(),
+    func() => ({}).split(),
+    func() => void.len(),
+    func() => [1].push(),
+]
+for call in calls {
+    try {
+        call()
+    } catch err {
+        println(err.kind, err.message)
+    }
+}
+";
+
+    assert_eq!(
+        run_both("missing_methods.glass", src),
+        "\
+NoSuchMethod No method 'missing' on type 'list'
+NoSuchMethod No method 'push' on type 'string'
+NoSuchMethod No method 'keys' on type 'number'
+NoSuchMethod No method 'split' on type 'dictionary'
+NoSuchMethod No method 'len' on type 'void'
+ArgumentCount Function 'list.push' expected 1 argument(s) but 0 were given
+"
+    );
+}
@@ -0,0 +1,72 @@
+//! Checks the AST passes through `--emit ast` and the errors they report.
+
+mod common;
+
+use common::{script, stderr, stdout};
+use std::process::Output;
+
+fn glass(arg: &str, src: &str, name: &str) -> Output {
+    common::glass([arg.as_ref(), script(name, src).as_os_str()])
+}
+
+#[test]
+fn constants_are_folded() {
+    let output = glass("--emit=ast", "x = 60 * 60 * 24 + -1", "folded.glass");
+    let ast = stdout(&output);
+
+    assert!(ast.contains("value: 86399.0"), "{}", ast);
+    assert!(!ast.contains("BinaryOp"), "{}", ast);
+}
+
+#[test]
+fn dead_branches_are_removed() {
+    let src = "if false { println(\"dead\") } else { println(\"alive\") }\nwhile false { x = 1 }";
+    let output = glass("--emit=ast", src, "dead_branches.glass");
+    let ast = stdout(&output);
+
+    assert!(ast.contains("alive"), "{}", ast);
+    assert!(!ast.contains("dead"), "{}", ast);
+    assert!(!ast.contains("While"), "{}", ast);
+}
+
+#[test]
+fn failing_constants_are_left_to_fail_at_runtime() {
+    let src = "f = func() => {\n    y = \"a\" - 1\n}\ntry {\n    x = \"a\" - 1\n} catch error {\n    println(error.message)\n}";
+
+    for arg in ["--error-format=text", "--vm"] {
+        let output = glass(arg, src, "folding_error.glass");
+
+        let stderr = stderr(&output);
+
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            stdout(&output),
+            "Cannot use operation '-' on type 'string' and 'number'\n"
+        );
+        // both operations are warned about, even the one in a function that is never called
+        assert_eq!(
+            stderr.matches("Warning -> This operation always fails when it is run: Cannot use operation '-'").count(),
+            2,
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("(Ln:2, Col:13..14)"), "{}", stderr);
+        assert!(stderr.contains("(Ln:5, Col:13..14)"), "{}", stderr);
+    }
+
+    let output = glass("--error-format=json", src, "folding_error.glass");
+    let warning: serde_json::Value =
+        serde_json::from_str(stderr(&output).lines().next().unwrap()).unwrap();
+    assert_eq!(warning["severity"], "warning");
+    assert_eq!(warning["kind"], "AlwaysFails");
+    assert_eq!(warning["line"], 2);
+}
+
+#[test]
+fn large_strings_are_not_folded() {
+    let output = glass("--emit=ast", "x = \"x\" * 1000000000", "large_string.glass");
+    let ast = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(ast.contains("BinaryOp"), "{}", ast);
+}
@@ -0,0 +1,120 @@
+//! Checks the report and collapsed stacks written by `--profile`.
+
+mod common;
+
+use common::{glass, stderr, stdout};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// fib(6) calls fib 25 times, 12 of which take the early return
+const SCRIPT: &str = "\
+fib = func(n) => {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+
+println(fib(6))
+";
+
+fn script(name: &str) -> PathBuf {
+    common::script(name, SCRIPT)
+}
+
+// the columns of the line of the report that ends with `name`
+fn row(report: &str, name: &str) -> Vec<String> {
+    report
+        .lines()
+        .find(|line| line.ends_with(name))
+        .unwrap_or_else(|| panic!("No row for {} in\n{}", name, report))
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn functions_and_lines_are_counted() {
+    let path = script("profile_counts.glass");
+    let output = glass(["--profile", path.to_str().unwrap()]);
+    let report = stderr(&output);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "8\n");
+
+    assert_eq!(row(&report, "  fib")[0], "25");
+    assert_eq!(row(&report, "  global")[0], "1");
+    assert_eq!(row(&report, "  println")[0], "1");
+
+    assert_eq!(row(&report, "if n < 2 {")[0], "25");
+    assert_eq!(row(&report, "return n")[0], "13");
+    assert_eq!(row(&report, "return fib(n - 1) + fib(n - 2)")[0], "12");
+
+    let line = row(&report, "println(fib(6))");
+    assert!(line[2].ends_with("profile_counts.glass:8"), "{:?}", line);
+
+    // the report is sorted by time, so the script as a whole comes first
+    let functions: Vec<_> = report.lines().skip(2).take(3).collect();
+    assert!(functions[0].ends_with("global"), "{}", report);
+}
+
+#[test]
+fn collapsed_stacks_are_written_for_flamegraphs() {
+    let path = script("profile_stacks.glass");
+    let folded = path.with_extension("folded");
+    let _ = fs::remove_file(&folded);
+
+    // the stacks are only written when asked for
+    let output = glass(["--profile", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(!folded.exists());
+
+    let output = glass([
+        "--profile",
+        "--profile-output",
+        folded.to_str().unwrap(),
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let stacks = fs::read_to_string(&folded).unwrap();
+
+    for line in stacks.lines() {
+        let (stack, time) = line.rsplit_once(' ').unwrap();
+        assert!(stack.starts_with("global"), "{}", line);
+        assert!(time.parse::<u64>().unwrap() > 0);
+    }
+
+    assert!(stacks.contains("global;fib;fib;fib "), "{}", stacks);
+
+    let folded = Path::new(env!("CARGO_TARGET_TMPDIR")).join("custom.folded");
+    let output = glass([
+        "--profile",
+        "--profile-output",
+        folded.to_str().unwrap(),
+        "-e",
+        "f = func() => { return 1 }\nf()",
+    ]);
+
+    assert!(output.status.success());
+    assert!(fs::read_to_string(&folded).unwrap().starts_with("global"));
+}
+
+#[test]
+fn failing_scripts_are_profiled_up_to_the_error() {
+    let folded = Path::new(env!("CARGO_TARGET_TMPDIR")).join("failing.folded");
+    let output = glass([
+        "--profile",
+        "--profile-output",
+        folded.to_str().unwrap(),
+        "-e",
+        "f = func(x) => { return x[3] }\nf([1])",
+    ]);
+    let stderr = stderr(&output);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(row(&stderr, "  f")[0], "1");
+    assert!(stderr.contains("Index 3 is out of bounds"), "{}", stderr);
+
+    assert!(!glass(["--profile", "--vm", "-e", "1"]).status.success());
+}
@@ -0,0 +1,57 @@
+//! Checks that lists and dictionaries are shared references, and that copies are independent.
+
+mod common;
+
+use common::{glass, script, stdout};
+
+// runs `src` as a script, returning what it printed
+fn run(name: &str, src: &str) -> String {
+    stdout(&glass([script(name, src)]))
+}
+
+#[test]
+fn functions_mutate_the_callers_list() {
+    let src = "\
+push_one = func(l) => {
+    l[0] = 1
+}
+xs = [0, 0]
+ys = xs
+push_one(xs)
+println(xs, ys)
+";
+
+    assert_eq!(run("alias.glass", src), "[1, 0] [1, 0]\n");
+}
+
+#[test]
+fn copies_do_not_share_items() {
+    let src = "\
+a = [[1], {\"k\": 1}]
+shallow = copy(a)
+deep = deepcopy(a)
+shallow[1] = 2
+a[0][0] = 3
+println(a, shallow, deep)
+println(a == deepcopy(a), copy(a)[0] == a[0])
+";
+
+    assert_eq!(
+        run("copy.glass", src),
+        "[[3], {\"k\": 1}] [[3], 2] [[1], {\"k\": 1}]\ntrue true\n"
+    );
+}
+
+#[test]
+fn cyclic_values_are_compared_and_copied() {
+    let src = "\
+a = [1]
+a[0] = a
+b = deepcopy(a)
+println(a, b, a == b)
+b[0][0] = 2
+println(b)
+";
+
+    assert_eq!(run("cycle.glass", src), "[[...]] [[...]] true\n[2]\n");
+}
@@ -0,0 +1,97 @@
+//! Checks the errors and warnings reported by the resolver before a script is run.
+
+mod common;
+
+use common::{glass, script, stderr};
+use std::process::Output;
+
+fn run(src: &str, name: &str) -> Output {
+    glass([script(name, src)])
+}
+
+fn assert_rejected(src: &str, name: &str, message: &str) {
+    let output = run(src, name);
+    let stderr = stderr(&output);
+
+    assert!(output.stdout.is_empty(), "{} should not have run", name);
+    assert!(stderr.contains(message), "{}", stderr);
+}
+
+fn warnings(src: &str, name: &str) -> String {
+    let output = run(src, name);
+    stderr(&output)
+}
+
+#[test]
+fn undefined_variables_are_errors() {
+    assert_rejected(
+        "println(\"start\")\nf = func() => missing + 1",
+        "undefined.glass",
+        "Variable 'missing' is not defined at \n\n\tf = func() => missing + 1\n\t              ^^^^^^^",
+    );
+}
+
+#[test]
+fn control_flow_outside_of_its_construct_is_an_error() {
+    assert_rejected(
+        "println(\"start\")\nwhile true {\n    f = func() => {\n        break\n    }\n}",
+        "break_in_function.glass",
+        "'break' used outside of a loop",
+    );
+    assert_rejected(
+        "println(\"start\")\ncontinue",
+        "continue.glass",
+        "'continue' used outside of a loop",
+    );
+    assert_rejected(
+        "println(\"start\")\nreturn 1",
+        "return.glass",
+        "'return' used outside of a function",
+    );
+}
+
+#[test]
+fn unused_variables_are_warned_about() {
+    let warnings = warnings(
+        "f = func(unused_param) => {\n    unused = 1\n    _ignored = 2\n    used = 3\n    return used\n}\nf(0)",
+        "unused.glass",
+    );
+
+    assert!(
+        warnings.contains("Variable 'unused' is assigned but never used"),
+        "{}",
+        warnings
+    );
+    assert_eq!(warnings.matches("Warning ->").count(), 1, "{}", warnings);
+}
+
+#[test]
+fn shadowing_is_warned_about() {
+    let warnings = warnings(
+        "total = 0\nadd = func(n) => {\n    total += n\n    return total\n}\nprintln(add(1), total)",
+        "shadowing.glass",
+    );
+
+    assert!(
+        warnings.contains("Variable 'total' shadows a variable of the same name"),
+        "{}",
+        warnings
+    );
+    assert!(warnings.contains("(Ln:3, Col:5..10)"), "{}", warnings);
+}
+
+#[test]
+fn use_before_definition_is_warned_about() {
+    let warnings = warnings(
+        "f = func() => later\nprintln(early)\nearly = 1\nlater = 2",
+        "before_definition.glass",
+    );
+
+    assert!(
+        warnings.contains("Variable 'early' is used before it is defined"),
+        "{}",
+        warnings
+    );
+    // functions can use variables defined after them, as long as they are called after
+    assert!(!warnings.contains("'later'"), "{}", warnings);
+}
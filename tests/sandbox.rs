@@ -0,0 +1,183 @@
+//! Checks the limits scripts can be run with, and the errors they stop with.
+
+mod common;
+
+use common::glass;
+use serde_json::Value;
+
+// runs the code on both the interpreter and the VM, returning the kind of error it stops with
+fn error_kinds(limit: &[&str], code: &str) -> Vec<String> {
+    [vec![], vec!["--vm"]]
+        .into_iter()
+        .map(|vm| {
+            let mut args = vec!["--error-format=json"];
+            args.extend(vm);
+            args.extend(limit);
+            args.extend(["-e", code]);
+
+            let output = glass(&args);
+            assert_eq!(output.status.code(), Some(1));
+
+            let error: Value = serde_json::from_slice(&output.stderr).expect("Invalid JSON");
+            error["kind"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn scripts_stop_after_too_many_steps() {
+    assert_eq!(
+        error_kinds(&["--max-steps", "1000"], "while true {}"),
+        ["StepLimitExceeded", "StepLimitExceeded"]
+    );
+
+    let output = glass(["--max-steps", "1000", "-e", "x = 1 + 2\nprintln(x)"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn scripts_stop_after_the_timeout() {
+    // a catch block can't keep the script going
+    let code = "try {\n    while true {}\n} catch error {\n    println(\"caught\")\n}";
+
+    assert_eq!(
+        error_kinds(&["--timeout", "100"], code),
+        ["TimeLimitExceeded", "TimeLimitExceeded"]
+    );
+    assert!(glass(["--timeout", "100", "-e", code]).stdout.is_empty());
+}
+
+#[test]
+fn values_cannot_grow_past_the_memory_limit() {
+    let limit = ["--max-memory", "1000"];
+
+    // the size is checked before the string is made, so this fails instead of aborting
+    assert_eq!(
+        error_kinds(&limit, "n = 1000000000000\nx = \"x\" * n"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "x = \"x\" * 1000000000000"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "l = []\nfor i in 0..1000 { l.push(i) }"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "d = {}\nfor i in 0..1000 { d[i] = i }"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "n = 1000000000000\nx = \"x\".pad_start(n)"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "x = \"{:.60000}\".format(1)"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+
+    let output = glass([
+        "--max-memory",
+        "1000",
+        "-e",
+        "n = 1000\ntry {\n    x = \"ab\" * n\n} catch error {\n    println(error.message)\n}",
+    ]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Script would take up 2000 bytes, more than the limit of 1000 bytes\n"
+    );
+
+    // lists made by literals and methods count too, and so does everything made before them
+    assert_eq!(
+        error_kinds(&limit, "x = \"a,\" * 300\ny = x.split(\",\")"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&limit, "a = [1]\nfor i in 0..20 {\n    a = [a, a]\n}"),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(
+            &limit,
+            "x = [1, 2, 3, 4, 5, 6, 7, 8]\nfor i in 0..100 {\n    y = x.zip(x)\n}"
+        ),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+}
+
+#[test]
+fn printing_a_value_counts_towards_the_limits() {
+    // a list of lists that share each other is small, but prints to 2^40 items
+    let code = "a = [1]\nfor i in 0..40 {\n    a = [a, a]\n}\nprintln(a)";
+
+    assert_eq!(
+        error_kinds(&["--max-steps", "100000"], code),
+        ["StepLimitExceeded", "StepLimitExceeded"]
+    );
+    assert_eq!(
+        error_kinds(&["--timeout", "300"], code),
+        ["TimeLimitExceeded", "TimeLimitExceeded"]
+    );
+
+    let code = "a = [1]\nfor i in 0..40 {\n    a = [a, a]\n}\nx = str(a)";
+    assert_eq!(
+        error_kinds(&["--max-memory", "100000"], code),
+        ["MemoryLimitExceeded", "MemoryLimitExceeded"]
+    );
+}
+
+#[test]
+fn calls_cannot_be_nested_past_the_depth_limit() {
+    let code = "f = func(n) => {\n    return f(n + 1)\n}\nf(0)";
+
+    assert_eq!(
+        error_kinds(&["--max-depth", "50"], code),
+        ["RecursionLimitExceeded", "RecursionLimitExceeded"]
+    );
+
+    // without --max-depth the default limit still stops the script before the stack runs out
+    let code = "f = func(n) => {\n    return f(n + 1)\n}\ntry {\n    f(0)\n} catch error {\n    println(error.kind, error.message)\n}";
+    for vm in [None, Some("--vm")] {
+        let mut args: Vec<&str> = vm.into_iter().collect();
+        args.extend(["-e", code]);
+
+        let output = glass(&args);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "RecursionLimitExceeded Function calls are nested deeper than the limit of 1000\n"
+        );
+    }
+
+    let code = "f = func(n) => {\n    if n == 0 {\n        return 0\n    }\n    return f(n - 1)\n}\nprintln(f(49))";
+    let output = glass(["--max-depth", "50", "-e", code]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn denied_capabilities_make_builtins_fail() {
+    assert_eq!(
+        error_kinds(&["--deny", "process"], "exit(3)"),
+        ["CapabilityDisabled", "CapabilityDisabled"]
+    );
+
+    assert_eq!(
+        error_kinds(&["--deny", "environment"], "println(args)"),
+        ["CapabilityDisabled", "CapabilityDisabled"]
+    );
+
+    // only the builtin is denied, not variables that happen to be called args
+    let code = "f = func(args) => args\nprintln(f(1))\nexit(3)";
+    let output = glass(["--deny", "environment", "-e", code]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+
+    let output = glass(["--deny", "process", "-e", "exit(3)"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'exit' needs the process capability, which is denied"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,98 @@
+//! Checks declaring structs, constructing them and using their fields and methods.
+
+mod common;
+
+use common::run_both;
+
+const POINT: &str = "\
+struct Point {
+    x, y
+
+    length = func(self) => (self.x ** 2 + self.y ** 2) ** 0.5
+    move = func(self, dx) => {
+        self.x += dx
+    }
+    origin = func() => Point(0, 0)
+}
+";
+
+#[test]
+fn fields_are_read_and_assigned() {
+    let src = format!(
+        "{}\
+p = Point(3, 4)
+println(p, p.x, p.y, type(p), Point)
+p.y = 1
+q = p
+q.x = 9
+println(p, p == Point(9, 1), p == Point(0, 1))
+struct Empty {{}}
+println(Empty())
+",
+        POINT
+    );
+
+    assert_eq!(
+        run_both("struct_fields.glass", &src),
+        "\
+Point { x: 3, y: 4 } 3 4 Point <struct Point>
+Point { x: 9, y: 1 } true false
+Empty {}
+"
+    );
+}
+
+#[test]
+fn methods_get_the_instance_as_self() {
+    let src = format!(
+        "{}\
+p = Point(3, 4)
+println(p.length())
+p.move(2)
+println(p, Point.origin())
+",
+        POINT
+    );
+
+    assert_eq!(
+        run_both("struct_methods.glass", &src),
+        "5\nPoint { x: 5, y: 4 } Point { x: 0, y: 0 }\n"
+    );
+}
+
+#[test]
+fn errors_mention_the_struct() {
+    let src = format!(
+        "{}\
+p = Point(1, 2)
+calls = [
+    func() => p + 1,
+    func() => p.z,
+    func() => {{
+        p.z = 1
+    }},
+    func() => Point(1),
+    func() => p.fly(),
+]
+for call in calls {{
+    try {{
+        call()
+    }} catch err {{
+        println(err.kind, err.message)
+    }}
+}}
+",
+        POINT
+    );
+
+    assert_eq!(
+        run_both("struct_errors.glass", &src),
+        "\
+InvalidOperation Cannot use operation '+' on type 'Point' and 'number'
+NoSuchField No field 'z' on type 'Point'
+NoSuchField No field 'z' on type 'Point'
+ArgumentCount Function 'Point' expected 2 argument(s) but 1 were given
+NoSuchMethod No method 'fly' on type 'Point'
+"
+    );
+}
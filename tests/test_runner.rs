@@ -0,0 +1,91 @@
+//! Runs `glass test` on scripts with passing and failing tests.
+
+mod common;
+
+use common::{command, scratch_dir, stdout};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+fn glass_test(path: &Path) -> Output {
+    command()
+        .arg("test")
+        .arg(path)
+        .output()
+        .expect("Failed to run glass")
+}
+
+#[test]
+fn passing_tests_exit_successfully() {
+    let dir = scratch_dir("passing_tests");
+    fs::write(
+        dir.join("math.glass"),
+        "double = func(x) => x * 2\ntest_double = func() => {\n    assert_eq(double(2), 4)\n    assert(double(0) == 0, \"zero\")\n}\nhelper = func() => assert(false)",
+    )
+    .expect("Failed to write script");
+    // scripts without tests are skipped
+    fs::write(dir.join("no_tests.glass"), "println(\"not a test\")")
+        .expect("Failed to write script");
+
+    let output = glass_test(&dir);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("running 1 test(s)"), "{}", stdout);
+    assert!(stdout.contains("test test_double ... ok"), "{}", stdout);
+    assert!(!stdout.contains("not a test"), "{}", stdout);
+    assert!(
+        stdout.contains("test result: ok. 1 passed; 0 failed"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn failing_tests_are_reported() {
+    let dir = scratch_dir("failing_tests");
+    let script = dir.join("failing.glass");
+    fs::write(
+        &script,
+        "test_equal = func() => assert_eq([1, 2, 3], [1, 5, 3])\ntest_message = func() => assert(1 > 2, \"one is not bigger\")\ntest_ok = func() => assert(true)",
+    )
+    .expect("Failed to write script");
+
+    let output = glass_test(&script);
+    let stdout = stdout(&output);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("test test_equal ... FAILED"), "{}", stdout);
+    assert!(
+        stdout.contains("  [\n      1,\n-     2,\n+     5,\n      3,\n  ]"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Assertion failed: one is not bigger"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("test result: FAILED. 1 passed; 2 failed"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn tests_run_in_isolated_contexts() {
+    let dir = scratch_dir("isolated_tests");
+    let script = dir.join("isolated.glass");
+    fs::write(
+        &script,
+        "counter = [0]\ntest_first = func() => {\n    counter.push(1)\n    assert_eq(len(counter), 2)\n}\ntest_second = func() => {\n    counter.push(2)\n    assert_eq(counter, [0, 2])\n}",
+    )
+    .expect("Failed to write script");
+
+    let output = glass_test(&script);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("2 passed; 0 failed"), "{}", stdout);
+}
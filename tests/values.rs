@@ -0,0 +1,53 @@
+//! Checks how values are printed by `println` and `str`, and quoted by the REPL.
+
+mod common;
+
+use common::{glass, glass_with_input, script, stdout};
+
+#[test]
+fn values_are_displayed_for_people() {
+    let src = "\
+println(1, 2.0, -3.5, 0.1 + 0.2, -0, 1000000000000)
+println(\"text\", true, void, [1, 2, 3], [], (1,), (1, \"a\"))
+println([\"a\", [\"b\"]], {\"k\": \"v\", 2: [3.0]}, {})
+println(str(4.0) + \"!\", str([1.50, \"x\"]), type, func() => 1)
+";
+
+    assert_eq!(
+        stdout(&glass([script("display.glass", src)])),
+        "\
+1 2 -3.5 0.30000000000000004 0 1000000000000
+text true void [1, 2, 3] [] (1,) (1, \"a\")
+[\"a\", [\"b\"]] {\"k\": \"v\", 2: [3]} {}
+4! [1.5, \"x\"] <builtin func type> <func anonymous>
+"
+    );
+}
+
+#[test]
+fn cyclic_values_are_displayed_without_recursing_forever() {
+    let src = "\
+xs = [1]
+xs.push(xs)
+d = {\"self\": void}
+d[\"self\"] = d
+pair = [xs, xs]
+println(xs, d, pair)
+";
+
+    assert_eq!(
+        stdout(&glass([script("display_cycles.glass", src)])),
+        "[1, [...]] {\"self\": {...}} [[1, [...]], [1, [...]]]\n"
+    );
+}
+
+#[test]
+fn the_repl_shows_the_repr_of_results() {
+    let input = "\"a\\tb\"\n2.0\n[\"x\", 1]\nxs = [1]\nxs[0] = xs\nxs\nvoid\n";
+    let output = glass_with_input(Vec::<&str>::new(), input);
+
+    assert_eq!(
+        stdout(&output),
+        "> \"a\\tb\"\n> 2\n> [\"x\", 1]\n> > > [[...]]\n> > "
+    );
+}